@@ -1,24 +1,218 @@
-use std::path::Path;
+use std::path::PathBuf;
 use std::process::Command;
 
+/// The oldest PortAudio the bindings are compatible with, as reported by pkg-config. The
+/// `portaudio-2.0.pc` files of both PortAudio builds only report the major version.
+const MIN_SYSTEM_VERSION: &str = "19";
+/// The newest function the bindings use, which the header of a compatible PortAudio declares, and
+/// the release that introduced it.
+const MIN_SYSTEM_VERSION_SYMBOL: &str = "Pa_GetVersionInfo";
+const MIN_SYSTEM_VERSION_SYMBOL_RELEASE: &str = "19.5.0";
+
+/// A host API selected by a cargo feature.
+struct HostApi {
+    feature: &'static str,
+    cmake_option: &'static str,
+    /// The function registering the host API, which the library contains if it was built.
+    initializer: &'static str,
+    /// The host API's directory in the PortAudio sources.
+    source_dir: &'static str,
+    /// The host API's extensions header, which a system PortAudio built with it provides.
+    header: Option<&'static str>,
+    /// The libraries the host API uses, to link when PortAudio is linked statically.
+    libs: &'static [&'static str],
+    /// Whether the host API exists on a target. Its feature is ignored on other targets.
+    supports: fn(&str) -> bool,
+}
+
+const HOST_APIS: &[HostApi] = &[
+    HostApi {
+        feature: "alsa",
+        cmake_option: "PA_USE_ALSA",
+        initializer: "PaAlsa_Initialize",
+        source_dir: "src/hostapi/alsa",
+        header: Some("pa_linux_alsa.h"),
+        libs: &["asound"],
+        supports: |target| target.contains("linux"),
+    },
+    HostApi {
+        feature: "jack",
+        cmake_option: "PA_USE_JACK",
+        initializer: "PaJack_Initialize",
+        source_dir: "src/hostapi/jack",
+        header: Some("pa_jack.h"),
+        libs: &["jack"],
+        supports: |_| true,
+    },
+    HostApi {
+        feature: "oss",
+        cmake_option: "PA_USE_OSS",
+        initializer: "PaOSS_Initialize",
+        source_dir: "src/hostapi/oss",
+        header: None,
+        libs: &[],
+        supports: |target| !target.contains("apple") && !target.contains("windows"),
+    },
+    HostApi {
+        feature: "pulseaudio",
+        cmake_option: "PA_USE_PULSEAUDIO",
+        initializer: "PaPulseAudio_Initialize",
+        source_dir: "src/hostapi/pulseaudio",
+        header: Some("pa_linux_pulseaudio.h"),
+        libs: &["pulse"],
+        supports: |target| target.contains("linux"),
+    },
+];
+
+/// The skeleton host API (i.e. `Backend::Dummy`) is enabled by a define instead of an option.
+const SKELETON_INITIALIZER: &str = "PaSkeleton_Initialize";
+
 fn main() {
+    println!("cargo:rerun-if-env-changed=PORTAUDIO_SYS_USE_PKG_CONFIG");
+    println!("cargo:rerun-if-env-changed=PORTAUDIO_SYS_STATIC");
+    if std::env::var_os("CARGO_FEATURE_SYSTEM").is_some()
+        || is_env_set("PORTAUDIO_SYS_USE_PKG_CONFIG")
+    {
+        link_system();
+    } else {
+        build_vendored();
+    }
+}
+
+fn is_feature_enabled(feature: &str) -> bool {
+    std::env::var_os(format!("CARGO_FEATURE_{}", feature.to_uppercase())).is_some()
+}
+
+/// Returns the host APIs requested by features, that exist on the target.
+fn requested_host_apis(target: &str) -> Vec<&'static HostApi> {
+    HOST_APIS
+        .iter()
+        .filter(|api| is_feature_enabled(api.feature))
+        .filter(|api| {
+            let supported = (api.supports)(target);
+            if !supported {
+                println!(
+                    "cargo:warning=The {} host API does not exist on {}. Ignoring its feature.",
+                    api.feature, target
+                );
+            }
+            supported
+        })
+        .collect()
+}
+
+fn is_env_set(name: &str) -> bool {
+    std::env::var(name).map_or(false, |value| !value.is_empty() && value != "0")
+}
+
+/// Links the system's PortAudio, as found by pkg-config. Links dynamically, unless
+/// `PORTAUDIO_SYS_STATIC` is set.
+fn link_system() {
+    let library = pkg_config::Config::new()
+        .atleast_version(MIN_SYSTEM_VERSION)
+        .statik(is_env_set("PORTAUDIO_SYS_STATIC"))
+        .probe("portaudio-2.0")
+        .unwrap_or_else(|error| panic!("Could not find the system PortAudio: {}", error));
+    let find_header = |name: &str| {
+        library
+            .include_paths
+            .iter()
+            .cloned()
+            .chain(vec![
+                PathBuf::from("/usr/include"),
+                PathBuf::from("/usr/local/include"),
+            ])
+            .map(|dir| dir.join(name))
+            .find(|header| header.exists())
+    };
+    // The host API checks below rely on the headers, so they must be installed, and as recent as
+    // the library.
+    let header = find_header("portaudio.h")
+        .and_then(|header| std::fs::read_to_string(header).ok())
+        .expect("Could not read the system portaudio.h.");
+    assert!(
+        header.contains(MIN_SYSTEM_VERSION_SYMBOL),
+        "The system PortAudio does not declare {}, which the bindings require: it is older than \
+         {}.",
+        MIN_SYSTEM_VERSION_SYMBOL,
+        MIN_SYSTEM_VERSION_SYMBOL_RELEASE
+    );
+    // The system library's host APIs are fixed: only check that the requested ones are there.
     let target = std::env::var("TARGET").unwrap();
+    for api in requested_host_apis(&target) {
+        if let Some(header) = api.header {
+            assert!(
+                find_header(header).is_some(),
+                "The `{}` feature requires a system PortAudio built with the {} host API, but {} \
+                 is missing.",
+                api.feature,
+                api.feature,
+                header
+            );
+        }
+    }
+    assert!(
+        !is_feature_enabled("skeleton"),
+        "The system PortAudio does not include the skeleton host API. Disable the `skeleton` \
+         feature."
+    );
+}
 
-    if !Path::new("portaudio/CMakeLists.txt").exists() {
-        Command::new("git")
-            .args(&["submodule", "update", "--init"])
-            .status()
-            .expect("Could not update portaudio submodule. Is git in path?");
+/// Returns the directory of the PortAudio sources to build: `PORTAUDIO_SRC_DIR` if set, else the
+/// sources packaged with the crate.
+///
+/// Only a git checkout of the crate may lack the packaged sources, in which case the submodule is
+/// fetched. Builds from a package never run git, nor need a network.
+fn vendored_source_dir() -> PathBuf {
+    println!("cargo:rerun-if-env-changed=PORTAUDIO_SRC_DIR");
+    if let Some(dir) = std::env::var_os("PORTAUDIO_SRC_DIR") {
+        let dir = PathBuf::from(dir);
         assert!(
-            Path::new("portaudio/CMakeLists.txt").exists(),
-            "Could not fetch the portaudio submodule. Likely due to a git bug in an old version. \
-             Please manually remove the portaudio directory and try again."
+            dir.join("CMakeLists.txt").exists(),
+            "PORTAUDIO_SRC_DIR ({}) does not contain the PortAudio sources (i.e. CMakeLists.txt).",
+            dir.display()
         );
+        return dir;
     }
 
+    let dir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap()).join("portaudio");
+    if dir.join("CMakeLists.txt").exists() {
+        return dir;
+    }
+    let in_checkout = dir
+        .ancestors()
+        .skip(1)
+        .any(|ancestor| ancestor.join(".git").exists());
+    assert!(
+        in_checkout,
+        "The PortAudio sources are missing from {}. Set PORTAUDIO_SRC_DIR to a copy of them, or \
+         enable the `system` feature to link the system's PortAudio.",
+        dir.display()
+    );
+    let updated = Command::new("git")
+        .args(&["submodule", "update", "--init", "portaudio"])
+        .current_dir(dir.parent().unwrap())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false);
+    assert!(
+        updated && dir.join("CMakeLists.txt").exists(),
+        "Could not fetch the portaudio submodule. Run `git submodule update --init` (removing the \
+         portaudio directory first if an old version of git left it empty), or set \
+         PORTAUDIO_SRC_DIR to a copy of the PortAudio sources."
+    );
+    dir
+}
+
+/// Builds the vendored PortAudio with CMake, and links it statically.
+fn build_vendored() {
+    let target = std::env::var("TARGET").unwrap();
+
+    let source_dir = vendored_source_dir();
+
     #[cfg(feature = "regenerate_bindings")]
     bindgen::Builder::default()
-        .header("portaudio/include/portaudio.h")
+        .header(source_dir.join("include/portaudio.h").to_str().unwrap())
         .parse_callbacks(Box::new(bindgen::CargoCallbacks))
         .rustified_non_exhaustive_enum("PaHostApiTypeId|PaErrorCode|PaStreamCallbackResult")
         .new_type_alias("PaStream")
@@ -33,7 +227,21 @@ fn main() {
         .expect("Couldn't write bindings to file.");
 
     // Actually build.
-    let dst = cmake::Config::new("portaudio")
+    let host_apis = requested_host_apis(&target);
+    // The portaudio submodule predates the PulseAudio host API, whose `PaHostApiTypeId` variants
+    // were added to the bindings by hand. Fail early instead of building without it.
+    for api in &host_apis {
+        assert!(
+            source_dir.join(api.source_dir).exists(),
+            "The `{}` feature requires PortAudio sources with the {} host API, which {} lacks. Set \
+             PORTAUDIO_SRC_DIR to sources that include it, or enable the `system` feature.",
+            api.feature,
+            api.feature,
+            source_dir.display()
+        );
+    }
+    let mut config = cmake::Config::new(&source_dir);
+    config
         .define("PA_BUILD_SHARED", "OFF")
         // Don't use legacy windows APIs (DirectSound, MME, WDMKS).
         .define("PA_USE_DS", "OFF")
@@ -41,15 +249,53 @@ fn main() {
         .define("PA_USE_WDMKS", "OFF")
         .define("PA_USE_WDMKS_DEVICE_INFO", "OFF")
         // Keep library names consistent in Windows (since we don't build shared).
-        .define("PA_LIBNAME_ADD_SUFFIX", "OFF")
-        // Enable the usage of the skeleton API.
-        .cflag("-DPA_USE_SKELETON=1")
-        //        .cflag("-DPA_ENABLE_DEBUG_OUTPUT=1")
-        //        .cflag("-DPA_LOG_API_CALLS=1")
-        .build();
+        .define("PA_LIBNAME_ADD_SUFFIX", "OFF");
+    for api in HOST_APIS {
+        let requested = host_apis
+            .iter()
+            .any(|requested| requested.feature == api.feature);
+        config.define(api.cmake_option, if requested { "ON" } else { "OFF" });
+    }
+    if is_feature_enabled("skeleton") {
+        config.cflag("-DPA_USE_SKELETON=1");
+    }
+    //    config.cflag("-DPA_ENABLE_DEBUG_OUTPUT=1");
+    //    config.cflag("-DPA_LOG_API_CALLS=1");
+    let dst = config.build();
     println!("cargo:rustc-link-search=native={}/lib", dst.display());
     println!("cargo:rustc-link-lib=static=portaudio");
 
+    // CMake quietly leaves out host APIs whose dependencies are missing, so check that the library
+    // registers the requested ones.
+    let library_name = if target.contains("windows") {
+        "portaudio.lib"
+    } else {
+        "libportaudio.a"
+    };
+    let library = std::fs::read(dst.join("lib").join(library_name))
+        .expect("Could not read the built PortAudio library.");
+    let contains = |symbol: &str| {
+        library
+            .windows(symbol.len())
+            .any(|window| window == symbol.as_bytes())
+    };
+    for api in &host_apis {
+        assert!(
+            contains(api.initializer),
+            "The {} host API was requested by the `{}` feature, but could not be built. Are its \
+             development files installed?",
+            api.feature,
+            api.feature
+        );
+        for lib in api.libs {
+            println!("cargo:rustc-link-lib={}", lib);
+        }
+    }
+    assert!(
+        !is_feature_enabled("skeleton") || contains(SKELETON_INITIALIZER),
+        "The skeleton host API was requested by the `skeleton` feature, but could not be built."
+    );
+
     // OSX
     if target.contains("apple") {
         println!("cargo:rustc-link-lib=framework=AudioToolbox");
@@ -59,17 +305,5 @@ fn main() {
     } else if target.contains("windows") {
         println!("cargo:rustc-link-lib=ole32");
         println!("cargo:rustc-link-lib=uuid");
-    } else if target.contains("linux") {
-        let out_dir = std::env::var("OUT_DIR").unwrap();
-        // This is the easiest way I can think of to figure out if portaudio was compiled
-        // with ALSA.
-        if Path::new(&out_dir).join("include/pa_linux_alsa.h").exists() {
-            println!("cargo:rustc-link-lib=asound");
-        } else {
-            println!(
-                "cargo:warning=Could not find ALSA (libasound2-dev) on this machine. \
-                      Linux support will be disabled."
-            );
-        }
     }
 }
@@ -256,6 +256,9 @@ pub enum PaHostApiTypeId {
     paJACK = 12,
     paWASAPI = 13,
     paAudioScienceHPI = 14,
+    paAudioIO = 15,
+    paPulseAudio = 16,
+    paSndio = 17,
 }
 #[doc = " A structure containing information about a particular host API."]
 #[repr(C)]
@@ -10,6 +10,45 @@ pub use flags::*;
 #[allow(non_camel_case_types)]
 mod bindings;
 
+/// Functions specific to the ALSA host API (`pa_linux_alsa.h`). Requires the `alsa` feature, on
+/// Linux.
+#[cfg(all(feature = "alsa", target_os = "linux"))]
+#[allow(non_snake_case)]
+pub mod alsa {
+    use super::{PaError, PaStream};
+    use std::os::raw::c_int;
+
+    extern "C" {
+        /// Enables or disables realtime scheduling of the stream's callback thread. Must be called
+        /// before the stream is started.
+        pub fn PaAlsa_EnableRealtimeScheduling(s: *mut PaStream, enable: c_int);
+        /// Gets the ALSA card number of an input stream's device.
+        pub fn PaAlsa_GetStreamInputCard(s: *mut PaStream, card: *mut c_int) -> PaError;
+        /// Gets the ALSA card number of an output stream's device.
+        pub fn PaAlsa_GetStreamOutputCard(s: *mut PaStream, card: *mut c_int) -> PaError;
+        /// Sets the number of periods of the buffers of streams opened afterwards (4 by
+        /// default).
+        pub fn PaAlsa_SetNumPeriods(numPeriods: c_int) -> PaError;
+    }
+}
+
+/// Functions specific to the JACK host API (`pa_jack.h`). Requires the `jack` feature.
+#[cfg(feature = "jack")]
+#[allow(non_snake_case)]
+pub mod jack {
+    use super::PaError;
+    use std::os::raw::c_char;
+
+    extern "C" {
+        /// Sets the name of the JACK client. Must be called before `Pa_Initialize`, and `name`
+        /// must stay valid until then.
+        pub fn PaJack_SetClientName(name: *const c_char) -> PaError;
+        /// Gets the name of the JACK client, which is owned by PortAudio. Only valid while
+        /// PortAudio is initialized.
+        pub fn PaJack_GetClientName(clientName: *mut *const c_char) -> PaError;
+    }
+}
+
 impl From<PaError> for Result<c_int, PaErrorCode> {
     fn from(error: PaError) -> Result<c_int, PaErrorCode> {
         if error.0 >= 0 {
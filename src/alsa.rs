@@ -0,0 +1,118 @@
+//! Extensions of the ALSA backend ([`Backend::Alsa`](crate::Backend::Alsa)). Requires the `alsa`
+//! feature, on Linux.
+//!
+//! ALSA devices are either hardware devices, which stream to a sound card as-is, or plugins (e.g.
+//! `dmix` or `plug`), which may mix, convert or resample audio on the way. Only hardware devices
+//! are bit-exact, but they are usually exclusive to a single stream.
+//!
+//! # Examples
+//! ```no_run
+//! use audiohal::alsa::{AlsaDeviceKind, DeviceExt as _, StreamExt as _};
+//! # use audiohal::*;
+//!
+//! let mut host = Host::with_backend(Backend::Alsa)?;
+//! let mut device = host
+//!     .devices()?
+//!     .into_iter()
+//!     .find(|device| matches!(device.alsa_kind(), Ok(AlsaDeviceKind::Hardware { .. })))
+//!     .ok_or(Error::NoSuchDevice)?;
+//! device.set_alsa_n_periods(2)?;
+//! let mut stream = device.open_outstream(StreamOptions::<[i16; 2]> {
+//!     format: Format::I16,
+//!     ..Default::default()
+//! })?;
+//! stream.enable_alsa_realtime_scheduling(true)?;
+//! stream.start()?;
+//! # Result::Ok(())
+//! ```
+use crate::device::Device;
+use crate::error::{Error, Result};
+use crate::stream::Stream;
+
+/// How an ALSA device reaches its sound card.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+pub enum AlsaDeviceKind {
+    /// A raw hardware device (i.e. `hw:card,device`). Audio reaches the card unaltered, but the
+    /// device only supports the card's native formats and rates.
+    Hardware { card: i32, device: i32 },
+    /// A plugin (e.g. `default`, `dmix` or `plughw`), named after its PCM. Audio may be mixed,
+    /// converted or resampled.
+    Plugin { name: String },
+}
+
+/// ALSA-specific methods of [`Device`].
+///
+/// Methods return [`Error::WrongBackend`] for devices of other backends.
+pub trait DeviceExt {
+    /// Classifies the device as a hardware device or a plugin.
+    fn alsa_kind(&self) -> Result<AlsaDeviceKind>;
+
+    /// Sets the number of periods of the buffers of streams opened afterwards on this device (4
+    /// by default). The buffer is split into periods, each of which is one callback's worth of
+    /// frames: fewer periods lower latency, at the risk of underruns.
+    ///
+    /// Returns [`Error::Invalid`] if `n_periods` is less than 2.
+    fn set_alsa_n_periods(&mut self, n_periods: i32) -> Result<()>;
+}
+
+impl DeviceExt for Device {
+    fn alsa_kind(&self) -> Result<AlsaDeviceKind> {
+        self.as_portaudio().ok_or(Error::WrongBackend)?.alsa_kind()
+    }
+
+    fn set_alsa_n_periods(&mut self, n_periods: i32) -> Result<()> {
+        self.as_portaudio_mut()
+            .ok_or(Error::WrongBackend)?
+            .set_alsa_n_periods(n_periods)
+    }
+}
+
+/// ALSA-specific methods of [`Stream`].
+///
+/// Methods return [`Error::WrongBackend`] for streams of other backends.
+pub trait StreamExt {
+    /// Enables or disables realtime (i.e. `SCHED_FIFO`) scheduling of the stream's callback
+    /// thread, which may require privileges (e.g. `CAP_SYS_NICE` or an rtprio limit).
+    ///
+    /// Returns [`Error::StreamAlreadyStarted`] if the stream was started, as the thread is created
+    /// then.
+    fn enable_alsa_realtime_scheduling(&mut self, enable: bool) -> Result<()>;
+
+    /// Returns the number of the sound card the stream's device belongs to. Returns
+    /// [`Error::NoSuchDevice`] for plugins that are not backed by a card.
+    fn alsa_card(&self) -> Result<i32>;
+}
+
+impl<Frame> StreamExt for Stream<Frame> {
+    fn enable_alsa_realtime_scheduling(&mut self, enable: bool) -> Result<()> {
+        self.as_portaudio_mut()
+            .ok_or(Error::WrongBackend)?
+            .enable_alsa_realtime_scheduling(enable)
+    }
+
+    fn alsa_card(&self) -> Result<i32> {
+        self.as_portaudio().ok_or(Error::WrongBackend)?.alsa_card()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Host, NullHostOptions, StreamOptions};
+
+    #[test]
+    fn rejects_other_backends() -> Result<()> {
+        let mut host = Host::with_null_backend(NullHostOptions::default())?;
+        let mut device = host.default_output_device()?;
+        assert_eq!(device.alsa_kind(), Err(Error::WrongBackend));
+        assert_eq!(device.set_alsa_n_periods(2), Err(Error::WrongBackend));
+        let mut stream = device.open_outstream(StreamOptions::<[f32; 2]>::default())?;
+        assert_eq!(stream.alsa_card(), Err(Error::WrongBackend));
+        assert_eq!(
+            stream.enable_alsa_realtime_scheduling(true),
+            Err(Error::WrongBackend)
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,293 @@
+//! Executor-agnostic `futures` front-ends for streams. Requires the `async` feature.
+use std::pin::Pin;
+use std::sync::atomic::{self, AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Waker};
+
+use futures::task::AtomicWaker;
+
+use crate::buffered::{self, ConsumerParts, ProducerParts};
+use crate::error::{Error, Result};
+use crate::stream_options::Callback;
+use crate::Stream;
+
+/// An asynchronous output stream, implementing [`futures::Sink`].
+///
+/// Created by [`Device::open_outstream_sink`](crate::Device::open_outstream_sink). Chunks sent to
+/// the sink are buffered and played in order. The sink applies backpressure: it is only ready
+/// for a new chunk once the previous one fits in the buffer. Like [`Producer`](crate::Producer),
+/// underruns are filled with silence and counted.
+pub struct OutputSink<Frame> {
+    stream: Stream<Frame>,
+    state: SinkState<Frame>,
+}
+
+/// An asynchronous input stream, implementing [`futures::Stream`].
+///
+/// Created by [`Device::open_instream_source`](crate::Device::open_instream_source). Every item
+/// contains the frames captured since the previous item. Like [`Consumer`](crate::Consumer),
+/// frames that do not fit in the buffer are dropped and counted as overruns.
+pub struct InputSource<Frame> {
+    stream: Stream<Frame>,
+    state: SourceState<Frame>,
+}
+
+/// The part of an [`OutputSink`] shared with its stream callback.
+pub struct SinkState<Frame> {
+    parts: ProducerParts<Frame>,
+    wakeup: Arc<Wakeup>,
+    /// The chunk being moved into the buffer, and how much of it was already moved.
+    pending: Vec<Frame>,
+    pending_position: usize,
+}
+
+/// The part of an [`InputSource`] shared with its stream callback.
+pub struct SourceState<Frame> {
+    parts: ConsumerParts<Frame>,
+    wakeup: Arc<Wakeup>,
+    capacity: usize,
+}
+
+/// Wakes a front-end's task from the stream callback, but only while the task waits for the
+/// buffer to change: from full to not full for a sink, and from empty to not empty for a source.
+/// This keeps the audio thread from waking the task on every buffer.
+#[derive(Default)]
+struct Wakeup {
+    waker: AtomicWaker,
+    waiting: AtomicBool,
+}
+
+impl Wakeup {
+    /// Called by the task before it checks the buffer one last time and returns `Pending`.
+    fn wait(&self, waker: &Waker) {
+        self.waker.register(waker);
+        self.waiting.store(true, Ordering::Relaxed);
+        // Pairs with the fence in `notify`: either the task's last check sees the callback's
+        // update of the buffer, or the callback sees that the task is waiting.
+        atomic::fence(Ordering::SeqCst);
+    }
+
+    /// Called by the stream callback after it updated the buffer.
+    fn notify(&self) {
+        atomic::fence(Ordering::SeqCst);
+        if self.waiting.load(Ordering::Relaxed) {
+            self.waiting.store(false, Ordering::Relaxed);
+            self.waker.wake();
+        }
+    }
+}
+
+/// Creates an output stream callback that drains a buffer of `capacity` frames, and wakes the
+/// sink's task once room was made in a full buffer.
+pub fn output_callback<Frame>(capacity: usize) -> (Callback<Frame>, SinkState<Frame>)
+where
+    Frame: sample::Frame + Send + 'static,
+{
+    let wakeup = Arc::new(Wakeup::default());
+    let callback_wakeup = Arc::clone(&wakeup);
+    let (callback, parts) = buffered::output_callback(capacity, move || callback_wakeup.notify());
+    let state = SinkState {
+        parts,
+        wakeup,
+        pending: Vec::new(),
+        pending_position: 0,
+    };
+    (callback, state)
+}
+
+/// Creates an input stream callback that fills a buffer of `capacity` frames, and wakes the
+/// source's task once frames were captured into an empty buffer.
+pub fn input_callback<Frame>(capacity: usize) -> (Callback<Frame>, SourceState<Frame>)
+where
+    Frame: sample::Frame + Send + 'static,
+{
+    let wakeup = Arc::new(Wakeup::default());
+    let callback_wakeup = Arc::clone(&wakeup);
+    let (callback, parts) = buffered::input_callback(capacity, move || callback_wakeup.notify());
+    let state = SourceState {
+        parts,
+        wakeup,
+        capacity,
+    };
+    (callback, state)
+}
+
+impl<Frame: Copy> SinkState<Frame> {
+    pub fn into_sink(self, stream: Stream<Frame>) -> OutputSink<Frame> {
+        OutputSink {
+            stream,
+            state: self,
+        }
+    }
+
+    fn start_send(&mut self, item: Vec<Frame>) {
+        debug_assert!(
+            self.pending.is_empty(),
+            "start_send called before poll_ready."
+        );
+        self.pending = item;
+        self.pending_position = 0;
+    }
+
+    /// Moves as much of the pending chunk as possible into the buffer. Ready once the whole chunk
+    /// was moved.
+    fn poll_pending(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        for attempt in 0..2 {
+            self.pending_position += self
+                .parts
+                .writer
+                .push_slice(&self.pending[self.pending_position..]);
+            if self.pending_position == self.pending.len() {
+                self.pending.clear();
+                self.pending_position = 0;
+                return Poll::Ready(());
+            }
+            // Wait before trying again, so that a callback running in-between is not missed.
+            if attempt == 0 {
+                self.wakeup.wait(cx.waker());
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl<Frame: sample::Frame> SourceState<Frame> {
+    pub fn into_source(self, stream: Stream<Frame>) -> InputSource<Frame> {
+        InputSource {
+            stream,
+            state: self,
+        }
+    }
+
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Vec<Frame>> {
+        for attempt in 0..2 {
+            let available = self.parts.reader.len().min(self.capacity);
+            if available > 0 {
+                let mut chunk = vec![Frame::equilibrium(); available];
+                let n_read = self.parts.reader.pop_slice(&mut chunk);
+                debug_assert_eq!(n_read, available);
+                return Poll::Ready(chunk);
+            }
+            if attempt == 0 {
+                self.wakeup.wait(cx.waker());
+            }
+        }
+        Poll::Pending
+    }
+}
+
+impl<Frame: Copy> OutputSink<Frame> {
+    /// The number of stream callbacks that did not have enough buffered frames.
+    pub fn underruns(&self) -> usize {
+        self.state.parts.underruns.load(Ordering::Relaxed)
+    }
+
+    pub fn start(&mut self) -> Result<()> {
+        self.stream.start()
+    }
+
+    /// The underlying stream.
+    pub fn stream(&mut self) -> &mut Stream<Frame> {
+        &mut self.stream
+    }
+}
+
+impl<Frame: sample::Frame + Unpin> futures::Sink<Vec<Frame>> for OutputSink<Frame> {
+    type Error = Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().state.poll_pending(cx).map(Ok)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Vec<Frame>) -> Result<()> {
+        self.get_mut().state.start_send(item);
+        Ok(())
+    }
+
+    /// Ready once every chunk sent so far is in the stream's buffer.
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.get_mut().state.poll_pending(cx).map(Ok)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+impl<Frame: Copy> InputSource<Frame> {
+    /// The number of stream callbacks whose frames did not entirely fit in the buffer.
+    pub fn overruns(&self) -> usize {
+        self.state.parts.overruns.load(Ordering::Relaxed)
+    }
+
+    pub fn start(&mut self) -> Result<()> {
+        self.stream.start()
+    }
+
+    /// The underlying stream.
+    pub fn stream(&mut self) -> &mut Stream<Frame> {
+        &mut self.stream
+    }
+}
+
+impl<Frame: sample::Frame + Unpin> futures::Stream for InputSource<Frame> {
+    type Item = Vec<Frame>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Vec<Frame>>> {
+        self.get_mut().state.poll_next(cx).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::task::{waker, ArcWake};
+    use std::sync::atomic::AtomicUsize;
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl ArcWake for CountingWaker {
+        fn wake_by_ref(arc_self: &Arc<Self>) {
+            arc_self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn sink_applies_backpressure() {
+        let (mut callback, mut state) = output_callback::<[f32; 1]>(4);
+        let wakes = Arc::new(CountingWaker::default());
+        let waker = waker(Arc::clone(&wakes));
+        let mut cx = Context::from_waker(&waker);
+
+        state.start_send(vec![[1.0]; 6]);
+        // Only 4 frames fit, so the sink must wait for the callback to make room.
+        assert_eq!(state.poll_pending(&mut cx), Poll::Pending);
+        callback(&mut [[0.0]; 3]);
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+        assert_eq!(state.poll_pending(&mut cx), Poll::Ready(()));
+        // The sink is not waiting anymore, so callbacks do not wake it.
+        callback(&mut [[0.0]; 3]);
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn source_yields_captured_frames() {
+        let (mut callback, mut state) = input_callback::<[f32; 1]>(8);
+        let wakes = Arc::new(CountingWaker::default());
+        let waker = waker(Arc::clone(&wakes));
+        let mut cx = Context::from_waker(&waker);
+
+        assert_eq!(state.poll_next(&mut cx), Poll::Pending);
+        callback(&mut [[1.0], [2.0]]);
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+        assert_eq!(state.poll_next(&mut cx), Poll::Ready(vec![[1.0], [2.0]]));
+        // Filling a buffer that is not empty does not wake the source.
+        callback(&mut [[3.0]]);
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+        assert_eq!(state.poll_next(&mut cx), Poll::Ready(vec![[3.0]]));
+        assert_eq!(state.poll_next(&mut cx), Poll::Pending);
+        callback(&mut [[4.0]]);
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 2);
+    }
+}
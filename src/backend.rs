@@ -1,11 +1,71 @@
 #[non_exhaustive]
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Backend {
     None,
+    /// JACK, through PortAudio. Requires the `jack` feature, which also enables the extensions of
+    /// the `jack` module.
     Jack,
+    /// ALSA, through PortAudio. Requires the `alsa` feature (enabled by default), which also
+    /// enables the extensions of the `alsa` module.
     Alsa,
     CoreAudio,
     Wasapi,
+    /// OSS (i.e. the `/dev/dsp*` device nodes), through PortAudio. Also works with OSS emulations
+    /// such as `osspd`. Requires the `oss` feature, which also enables the extensions of the `oss`
+    /// module.
     LinuxFallback,
+    /// PortAudio's skeleton host API, which has no devices. Requires the `skeleton` feature
+    /// (enabled by default).
     Dummy,
+    /// A pure-Rust backend with fake devices, that does not need any audio hardware. See
+    /// [`Host::with_null_backend`](crate::Host::with_null_backend).
+    Null,
+    /// A pure-Rust backend whose devices are WAV files. See
+    /// [`Host::with_file_backend`](crate::Host::with_file_backend).
+    File,
+    /// A pure-Rust backend whose output is looped back to its input. See
+    /// [`Host::with_loopback_backend`](crate::Host::with_loopback_backend).
+    Loopback,
+    /// Talks to the PulseAudio server natively, without going through PortAudio, with the
+    /// `native-pulseaudio` feature. See
+    /// [`Host::with_pulseaudio_backend`](crate::Host::with_pulseaudio_backend). Otherwise, goes
+    /// through PortAudio, which requires the `pulseaudio` feature.
+    PulseAudio,
+    /// Talks to the PipeWire daemon natively, without going through PortAudio. Requires the
+    /// `native-pipewire` feature. See
+    /// [`Host::with_pipewire_backend`](crate::Host::with_pipewire_backend).
+    PipeWire,
+}
+
+impl Backend {
+    /// Returns whether support for the backend was compiled, as selected by cargo features and the
+    /// target. Backends that were not compiled are always
+    /// [`Error::BackendUnavailable`](crate::Error::BackendUnavailable), while compiled ones may
+    /// still be unavailable at runtime (e.g. if no JACK server is running).
+    ///
+    /// # Examples
+    /// ```
+    /// # use audiohal::*;
+    /// assert!(Backend::Null.is_compiled());
+    /// assert!(!Backend::None.is_compiled());
+    /// ```
+    pub fn is_compiled(&self) -> bool {
+        use Backend::*;
+        match self {
+            None => false,
+            Jack => cfg!(feature = "jack"),
+            Alsa => cfg!(all(feature = "alsa", target_os = "linux")),
+            CoreAudio => cfg!(target_os = "macos"),
+            Wasapi => cfg!(windows),
+            LinuxFallback => cfg!(all(feature = "oss", unix, not(target_os = "macos"))),
+            Dummy => cfg!(feature = "skeleton"),
+            Null | File | Loopback => true,
+            PulseAudio => cfg!(any(
+                feature = "native-pulseaudio",
+                all(feature = "pulseaudio", target_os = "linux")
+            )),
+            PipeWire => cfg!(feature = "native-pipewire"),
+        }
+    }
 }
@@ -0,0 +1,210 @@
+//! Buffered stream front-ends, for applications that would rather push and pull frames from a
+//! regular thread than write a stream callback.
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::Result;
+use crate::ring_buffer;
+use crate::stream_options::Callback;
+use crate::Stream;
+
+/// The writing end of a buffered output stream.
+///
+/// Created by [`Device::open_outstream_buffered`](crate::Device::open_outstream_buffered). Frames
+/// written with [`write`](Producer::write) are played in order by the stream. Whenever the stream
+/// needs more frames than are buffered, the difference is filled with silence and counted as an
+/// underrun.
+pub struct Producer<Frame> {
+    stream: Stream<Frame>,
+    writer: ring_buffer::Writer<Frame>,
+    underruns: Arc<AtomicUsize>,
+}
+
+/// The reading end of a buffered input stream.
+///
+/// Created by [`Device::open_instream_buffered`](crate::Device::open_instream_buffered). Frames
+/// captured by the stream are read in order with [`read`](Consumer::read). Whenever the buffer is
+/// full, newly captured frames are dropped and counted as an overrun.
+pub struct Consumer<Frame> {
+    stream: Stream<Frame>,
+    reader: ring_buffer::Reader<Frame>,
+    overruns: Arc<AtomicUsize>,
+}
+
+impl<Frame: Copy> Producer<Frame> {
+    /// Writes as many frames as fit in the buffer, and returns how many were written. Never
+    /// blocks.
+    pub fn write(&mut self, frames: &[Frame]) -> usize {
+        self.writer.push_slice(frames)
+    }
+
+    /// The number of frames that can currently be written without blocking.
+    pub fn available(&self) -> usize {
+        self.writer.free_len()
+    }
+
+    /// The number of stream callbacks that did not have enough buffered frames.
+    pub fn underruns(&self) -> usize {
+        self.underruns.load(Ordering::Relaxed)
+    }
+
+    pub fn start(&mut self) -> Result<()> {
+        self.stream.start()
+    }
+
+    /// Returns the stream's latency, not including the frames waiting in the buffer.
+    pub fn latency(&self) -> Duration {
+        self.stream.latency()
+    }
+
+    /// The underlying stream.
+    pub fn stream(&mut self) -> &mut Stream<Frame> {
+        &mut self.stream
+    }
+}
+
+impl<Frame: Copy> Consumer<Frame> {
+    /// Reads as many buffered frames as are available into `frames`, and returns how many were
+    /// read. Never blocks.
+    pub fn read(&mut self, frames: &mut [Frame]) -> usize {
+        self.reader.pop_slice(frames)
+    }
+
+    /// The number of frames that can currently be read without blocking.
+    pub fn available(&self) -> usize {
+        self.reader.len()
+    }
+
+    /// The number of stream callbacks whose frames did not entirely fit in the buffer.
+    pub fn overruns(&self) -> usize {
+        self.overruns.load(Ordering::Relaxed)
+    }
+
+    pub fn start(&mut self) -> Result<()> {
+        self.stream.start()
+    }
+
+    /// Returns the stream's latency, not including the frames waiting in the buffer.
+    pub fn latency(&self) -> Duration {
+        self.stream.latency()
+    }
+
+    /// The underlying stream.
+    pub fn stream(&mut self) -> &mut Stream<Frame> {
+        &mut self.stream
+    }
+}
+
+/// The state shared by a [`Producer`] and its stream callback, before the stream is opened.
+pub struct ProducerParts<Frame> {
+    pub writer: ring_buffer::Writer<Frame>,
+    pub underruns: Arc<AtomicUsize>,
+}
+
+/// The state shared by a [`Consumer`] and its stream callback, before the stream is opened.
+pub struct ConsumerParts<Frame> {
+    pub reader: ring_buffer::Reader<Frame>,
+    pub overruns: Arc<AtomicUsize>,
+}
+
+impl<Frame> ProducerParts<Frame> {
+    pub fn into_producer(self, stream: Stream<Frame>) -> Producer<Frame> {
+        Producer {
+            stream,
+            writer: self.writer,
+            underruns: self.underruns,
+        }
+    }
+}
+
+impl<Frame> ConsumerParts<Frame> {
+    pub fn into_consumer(self, stream: Stream<Frame>) -> Consumer<Frame> {
+        Consumer {
+            stream,
+            reader: self.reader,
+            overruns: self.overruns,
+        }
+    }
+}
+
+/// Creates an output stream callback that drains a ring buffer of `capacity` frames.
+///
+/// `notify` is called from the audio thread after every callback, once frames were drained.
+pub fn output_callback<Frame, Notify>(
+    capacity: usize,
+    notify: Notify,
+) -> (Callback<Frame>, ProducerParts<Frame>)
+where
+    Frame: sample::Frame + Send + 'static,
+    Notify: Fn() + Send + 'static,
+{
+    let (writer, mut reader) = ring_buffer::new(capacity, Frame::equilibrium());
+    let underruns = Arc::new(AtomicUsize::new(0));
+    let callback_underruns = Arc::clone(&underruns);
+    let callback = Box::new(move |output: &mut [Frame]| {
+        let n_read = reader.pop_slice(output);
+        if n_read < output.len() {
+            for frame in &mut output[n_read..] {
+                *frame = Frame::equilibrium();
+            }
+            callback_underruns.fetch_add(1, Ordering::Relaxed);
+        }
+        notify();
+    });
+    (callback, ProducerParts { writer, underruns })
+}
+
+/// Creates an input stream callback that fills a ring buffer of `capacity` frames.
+///
+/// `notify` is called from the audio thread after every callback, once frames were buffered.
+pub fn input_callback<Frame, Notify>(
+    capacity: usize,
+    notify: Notify,
+) -> (Callback<Frame>, ConsumerParts<Frame>)
+where
+    Frame: sample::Frame + Send + 'static,
+    Notify: Fn() + Send + 'static,
+{
+    let (mut writer, reader) = ring_buffer::new(capacity, Frame::equilibrium());
+    let overruns = Arc::new(AtomicUsize::new(0));
+    let callback_overruns = Arc::clone(&overruns);
+    let callback = Box::new(move |input: &mut [Frame]| {
+        if writer.push_slice(input) < input.len() {
+            callback_overruns.fetch_add(1, Ordering::Relaxed);
+        }
+        notify();
+    });
+    (callback, ConsumerParts { reader, overruns })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_callback_fills_underruns_with_silence() {
+        let (mut callback, mut parts) = output_callback::<[i16; 1], _>(8, || ());
+        assert_eq!(parts.writer.push_slice(&[[1], [2], [3]]), 3);
+        let mut output = [[-1]; 5];
+        callback(&mut output);
+        assert_eq!(output, [[1], [2], [3], [0], [0]]);
+        assert_eq!(parts.underruns.load(Ordering::Relaxed), 1);
+        // Exactly enough frames is not an underrun.
+        parts.writer.push_slice(&[[4], [5]]);
+        callback(&mut output[..2]);
+        assert_eq!(parts.underruns.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn input_callback_counts_overruns() {
+        let (mut callback, mut parts) = input_callback::<[i16; 1], _>(4, || ());
+        callback(&mut [[1], [2], [3]]);
+        assert_eq!(parts.overruns.load(Ordering::Relaxed), 0);
+        callback(&mut [[4], [5]]);
+        assert_eq!(parts.overruns.load(Ordering::Relaxed), 1);
+        let mut input = [[0]; 8];
+        assert_eq!(parts.reader.pop_slice(&mut input), 4);
+        assert_eq!(&input[..4], &[[1], [2], [3], [4]]);
+    }
+}
@@ -0,0 +1,153 @@
+//! Conversion of raw interleaved samples between [`Format`]s.
+//!
+//! Samples are native-endian bytes, as exchanged with stream callbacks. Integer samples are
+//! scaled to and from `[-1.0, 1.0)`, so converting between integer formats keeps the most
+//! significant bits.
+use std::convert::TryInto as _;
+
+use crate::stream_options::Format;
+
+/// Decodes a single sample.
+pub fn decode(format: Format, sample: &[u8]) -> f64 {
+    match format {
+        Format::F32 => f64::from(f32::from_ne_bytes(sample.try_into().unwrap())),
+        Format::I32 => f64::from(i32::from_ne_bytes(sample.try_into().unwrap())) / 2_f64.powi(31),
+        Format::I24 => f64::from(decode_i24(sample)) / 2_f64.powi(23),
+        Format::I16 => f64::from(i16::from_ne_bytes(sample.try_into().unwrap())) / 2_f64.powi(15),
+        Format::I8 => f64::from(sample[0] as i8) / 2_f64.powi(7),
+        Format::U8 => (f64::from(sample[0]) - 128.0) / 2_f64.powi(7),
+    }
+}
+
+/// Encodes a single sample, clipping it to the range of `format`.
+pub fn encode(format: Format, value: f64, sample: &mut [u8]) {
+    /// Scales `value` to a signed integer of `bits` bits.
+    fn quantize(value: f64, bits: i32) -> i32 {
+        let scale = 2_f64.powi(bits - 1);
+        (value * scale).round().max(-scale).min(scale - 1.0) as i32
+    }
+    match format {
+        Format::F32 => sample.copy_from_slice(&(value as f32).to_ne_bytes()),
+        Format::I32 => sample.copy_from_slice(&quantize(value, 32).to_ne_bytes()),
+        Format::I24 => encode_i24(quantize(value, 24), sample),
+        Format::I16 => sample.copy_from_slice(&(quantize(value, 16) as i16).to_ne_bytes()),
+        Format::I8 => sample[0] = quantize(value, 8) as i8 as u8,
+        Format::U8 => sample[0] = (quantize(value, 8) + 128) as u8,
+    }
+}
+
+/// Converts the samples in `input` from format `from` into `output`, in format `to`. Both must
+/// hold the same number of samples.
+pub fn convert(from: Format, input: &[u8], to: Format, output: &mut [u8]) {
+    debug_assert_eq!(
+        input.len() / from.sample_size(),
+        output.len() / to.sample_size()
+    );
+    if from == to {
+        output.copy_from_slice(input);
+        return;
+    }
+    for (input, output) in input
+        .chunks_exact(from.sample_size())
+        .zip(output.chunks_exact_mut(to.sample_size()))
+    {
+        encode(to, decode(from, input), output);
+    }
+}
+
+/// Scales `samples`, interleaved frames of `n_channels` channels, by a gain going linearly from
+/// `from` on the first frame towards `to`.
+pub fn ramp(format: Format, n_channels: usize, samples: &mut [u8], from: f64, to: f64) {
+    let frame_size = format.sample_size() * n_channels;
+    let n_frames = samples.len() / frame_size;
+    for (i, frame) in samples.chunks_exact_mut(frame_size).enumerate() {
+        let gain = from + (to - from) * i as f64 / n_frames as f64;
+        for sample in frame.chunks_exact_mut(format.sample_size()) {
+            encode(format, decode(format, sample) * gain, sample);
+        }
+    }
+}
+
+/// Fills `samples` with silence.
+pub fn fill_silence(format: Format, samples: &mut [u8]) {
+    let fill = if format == Format::U8 { 0x80 } else { 0 };
+    for byte in samples.iter_mut() {
+        *byte = fill;
+    }
+}
+
+fn decode_i24(sample: &[u8]) -> i32 {
+    let mut bytes = [0; 4];
+    if cfg!(target_endian = "big") {
+        bytes[..3].copy_from_slice(sample);
+        i32::from_be_bytes(bytes) >> 8
+    } else {
+        bytes[1..].copy_from_slice(sample);
+        i32::from_le_bytes(bytes) >> 8
+    }
+}
+
+fn encode_i24(value: i32, sample: &mut [u8]) {
+    if cfg!(target_endian = "big") {
+        sample.copy_from_slice(&value.to_be_bytes()[1..]);
+    } else {
+        sample.copy_from_slice(&value.to_le_bytes()[..3]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert_one(from: Format, input: &[u8], to: Format) -> Vec<u8> {
+        let mut output = vec![0; to.sample_size()];
+        convert(from, input, to, &mut output);
+        output
+    }
+
+    #[test]
+    fn converts_between_integer_formats() {
+        let sample = (-0x1234_i16).to_ne_bytes();
+        assert_eq!(
+            convert_one(Format::I16, &sample, Format::I32),
+            (-0x1234_0000_i32).to_ne_bytes()
+        );
+        assert_eq!(
+            decode_i24(&convert_one(Format::I16, &sample, Format::I24)),
+            -0x12_3400
+        );
+        assert_eq!(convert_one(Format::I16, &sample, Format::I8), vec![0xee]);
+        assert_eq!(convert_one(Format::I16, &sample, Format::U8), vec![0x6e]);
+    }
+
+    #[test]
+    fn converts_to_and_from_float() {
+        assert_eq!(
+            convert_one(Format::F32, &0.5_f32.to_ne_bytes(), Format::I16),
+            0x4000_i16.to_ne_bytes()
+        );
+        assert_eq!(
+            convert_one(Format::U8, &[0], Format::F32),
+            (-1.0_f32).to_ne_bytes()
+        );
+        // Out of range samples are clipped.
+        assert_eq!(
+            convert_one(Format::F32, &2.0_f32.to_ne_bytes(), Format::I16),
+            i16::max_value().to_ne_bytes()
+        );
+    }
+
+    #[test]
+    fn ramps_gain() {
+        let mut samples: Vec<u8> = [0x4000_i16; 4]
+            .iter()
+            .flat_map(|s| s.to_ne_bytes().to_vec())
+            .collect();
+        ramp(Format::I16, 2, &mut samples, 1.0, 0.0);
+        let samples: Vec<i16> = samples
+            .chunks_exact(2)
+            .map(|sample| i16::from_ne_bytes([sample[0], sample[1]]))
+            .collect();
+        assert_eq!(samples, vec![0x4000, 0x4000, 0x2000, 0x2000]);
+    }
+}
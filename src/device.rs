@@ -0,0 +1,321 @@
+#[cfg(feature = "async")]
+use crate::async_stream::{self, InputSource, OutputSink};
+use crate::buffered::{self, Consumer, Producer};
+use crate::device_id::DeviceId;
+use crate::error::{Error, Result};
+use crate::follow;
+use crate::host::Host;
+use crate::null;
+#[cfg(feature = "native-pipewire")]
+use crate::pipewire;
+use crate::portaudio;
+#[cfg(feature = "native-pulseaudio")]
+use crate::pulseaudio;
+use crate::stream::{Stream, StreamImpl};
+use crate::stream_options::{Direction, Reconnect, StreamOptions};
+
+/// An audio device (e.g. speakers or a microphone) that streams can be opened on.
+pub struct Device(DeviceImpl);
+
+pub enum DeviceImpl {
+    PortAudio(portaudio::Device),
+    Null(null::Device),
+    #[cfg(feature = "native-pulseaudio")]
+    PulseAudio(pulseaudio::Device),
+    #[cfg(feature = "native-pipewire")]
+    PipeWire(pipewire::Device),
+}
+
+impl Device {
+    /// Returns the PortAudio device, for the extensions of its host APIs.
+    #[cfg(any(all(feature = "alsa", target_os = "linux"), feature = "oss"))]
+    pub(crate) fn as_portaudio(&self) -> Option<&portaudio::Device> {
+        match &self.0 {
+            DeviceImpl::PortAudio(device) => Some(device),
+            _ => None,
+        }
+    }
+
+    #[cfg(all(feature = "alsa", target_os = "linux"))]
+    pub(crate) fn as_portaudio_mut(&mut self) -> Option<&mut portaudio::Device> {
+        match &mut self.0 {
+            DeviceImpl::PortAudio(device) => Some(device),
+            _ => None,
+        }
+    }
+
+    /// The device's system name (e.g. "Built-in Output").
+    pub fn name(&self) -> &str {
+        match &self.0 {
+            DeviceImpl::PortAudio(device) => device.name(),
+            DeviceImpl::Null(device) => device.name(),
+            #[cfg(feature = "native-pulseaudio")]
+            DeviceImpl::PulseAudio(device) => device.name(),
+            #[cfg(feature = "native-pipewire")]
+            DeviceImpl::PipeWire(device) => device.name(),
+        }
+    }
+
+    /// Returns the device's identifier, to find it again later (e.g. after the application
+    /// restarts) with [`Host::device_by_id`](crate::Host::device_by_id).
+    pub fn id(&self) -> DeviceId {
+        match &self.0 {
+            DeviceImpl::PortAudio(device) => device.id(),
+            DeviceImpl::Null(device) => device.id(),
+            #[cfg(feature = "native-pulseaudio")]
+            DeviceImpl::PulseAudio(device) => device.id(),
+            #[cfg(feature = "native-pipewire")]
+            DeviceImpl::PipeWire(device) => device.id(),
+        }
+    }
+
+    /// Creates an output stream.
+    ///
+    /// `Frame` is the stream's frame type, and is inferred from the stream callback. It must be
+    /// `Send + 'static`, since frames are buffered on the backend's audio thread (e.g. when
+    /// [`StreamOptions::block_size`] is set).
+    ///
+    /// Output streams stream digital audio (in the form of frames) to a system's output device.
+    /// The callback in  [`StreamOptions`] is called multiple times per second (depending on how you
+    /// setup frames_per_buffer) in order to satisfy the requested sample-rate. See
+    /// [`Stream`] for more details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use audiohal::*;
+    /// fn callback(buffer: &mut [[f32; 2]]) {
+    ///     # buffer;
+    /// }
+    /// let mut device = Host::with_default_backend()?.default_output_device()?;
+    /// let stream = device.open_outstream(
+    ///     StreamOptions {
+    ///         callback: Box::new(callback),
+    ///         // The rest of the parameters will be set to device defaults.
+    ///         ..Default::default()
+    ///     });
+    /// assert!(stream.is_ok());
+    /// # Result::Ok(())
+    /// ```
+    pub fn open_outstream<Frame>(&mut self, options: StreamOptions<Frame>) -> Result<Stream<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        Ok(Stream::new(match &mut self.0 {
+            DeviceImpl::PortAudio(device) if options.reconnect != Reconnect::Never => {
+                StreamImpl::Following(follow::Stream::open(
+                    Host::from_portaudio(device.host()),
+                    follow::Target::Device(device.id()),
+                    Direction::Output,
+                    options,
+                )?)
+            }
+            DeviceImpl::PortAudio(device) => StreamImpl::PortAudio(device.open_outstream(options)?),
+            DeviceImpl::Null(device) => StreamImpl::Null(device.open_outstream(options)?),
+            #[cfg(feature = "native-pulseaudio")]
+            DeviceImpl::PulseAudio(device) => {
+                StreamImpl::PulseAudio(device.open_outstream(options)?)
+            }
+            #[cfg(feature = "native-pipewire")]
+            DeviceImpl::PipeWire(device) => StreamImpl::PipeWire(device.open_outstream(options)?),
+        }))
+    }
+
+    /// Creates an input stream.
+    ///
+    /// Input streams stream digital audio from a system's input device (e.g. a microphone). The
+    /// callback in [`StreamOptions`] is called with the captured frames.
+    pub fn open_instream<Frame>(&mut self, options: StreamOptions<Frame>) -> Result<Stream<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        Ok(Stream::new(match &mut self.0 {
+            DeviceImpl::PortAudio(device) if options.reconnect != Reconnect::Never => {
+                StreamImpl::Following(follow::Stream::open(
+                    Host::from_portaudio(device.host()),
+                    follow::Target::Device(device.id()),
+                    Direction::Input,
+                    options,
+                )?)
+            }
+            DeviceImpl::PortAudio(device) => StreamImpl::PortAudio(device.open_instream(options)?),
+            DeviceImpl::Null(device) => StreamImpl::Null(device.open_instream(options)?),
+            #[cfg(feature = "native-pulseaudio")]
+            DeviceImpl::PulseAudio(device) => {
+                StreamImpl::PulseAudio(device.open_instream(options)?)
+            }
+            #[cfg(feature = "native-pipewire")]
+            DeviceImpl::PipeWire(device) => StreamImpl::PipeWire(device.open_instream(options)?),
+        }))
+    }
+
+    /// Creates an output stream that plays frames written to the returned [`Producer`], which
+    /// buffers up to `capacity` frames.
+    ///
+    /// The callback in `options` is replaced by one that drains the buffer. Returns
+    /// [`Error::Invalid`] if `capacity` is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use audiohal::*;
+    /// let mut device = Host::with_default_backend()?.default_output_device()?;
+    /// let mut producer = device.open_outstream_buffered::<[f32; 2]>(Default::default(), 4096)?;
+    /// producer.write(&[[0.0, 0.0]; 1024]);
+    /// producer.start()?;
+    /// # Result::Ok(())
+    /// ```
+    pub fn open_outstream_buffered<Frame>(
+        &mut self,
+        options: StreamOptions<Frame>,
+        capacity: usize,
+    ) -> Result<Producer<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        check_capacity(capacity)?;
+        let (callback, parts) = buffered::output_callback(capacity, || ());
+        let stream = self.open_outstream(StreamOptions {
+            callback,
+            ..options
+        })?;
+        Ok(parts.into_producer(stream))
+    }
+
+    /// Creates an input stream whose frames are read from the returned [`Consumer`], which
+    /// buffers up to `capacity` frames.
+    ///
+    /// The callback in `options` is replaced by one that fills the buffer. Returns
+    /// [`Error::Invalid`] if `capacity` is zero.
+    pub fn open_instream_buffered<Frame>(
+        &mut self,
+        options: StreamOptions<Frame>,
+        capacity: usize,
+    ) -> Result<Consumer<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        check_capacity(capacity)?;
+        let (callback, parts) = buffered::input_callback(capacity, || ());
+        let stream = self.open_instream(StreamOptions {
+            callback,
+            ..options
+        })?;
+        Ok(parts.into_consumer(stream))
+    }
+
+    /// Creates an output stream that plays chunks sent to the returned [`OutputSink`], which
+    /// buffers up to `capacity` frames.
+    ///
+    /// The callback in `options` is replaced by one that drains the buffer. Returns
+    /// [`Error::Invalid`] if `capacity` is zero. Requires the `async` feature.
+    #[cfg(feature = "async")]
+    pub fn open_outstream_sink<Frame>(
+        &mut self,
+        options: StreamOptions<Frame>,
+        capacity: usize,
+    ) -> Result<OutputSink<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        check_capacity(capacity)?;
+        let (callback, state) = async_stream::output_callback(capacity);
+        let stream = self.open_outstream(StreamOptions {
+            callback,
+            ..options
+        })?;
+        Ok(state.into_sink(stream))
+    }
+
+    /// Creates an input stream whose frames are yielded by the returned [`InputSource`], which
+    /// buffers up to `capacity` frames.
+    ///
+    /// The callback in `options` is replaced by one that fills the buffer. Returns
+    /// [`Error::Invalid`] if `capacity` is zero. Requires the `async` feature.
+    #[cfg(feature = "async")]
+    pub fn open_instream_source<Frame>(
+        &mut self,
+        options: StreamOptions<Frame>,
+        capacity: usize,
+    ) -> Result<InputSource<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        check_capacity(capacity)?;
+        let (callback, state) = async_stream::input_callback(capacity);
+        let stream = self.open_instream(StreamOptions {
+            callback,
+            ..options
+        })?;
+        Ok(state.into_source(stream))
+    }
+}
+
+/// Buffers must hold at least one frame.
+fn check_capacity(capacity: usize) -> Result<()> {
+    if capacity == 0 {
+        return Err(Error::Invalid);
+    }
+    Ok(())
+}
+
+impl From<portaudio::Device> for Device {
+    fn from(device: portaudio::Device) -> Device {
+        Device(DeviceImpl::PortAudio(device))
+    }
+}
+
+impl From<null::Device> for Device {
+    fn from(device: null::Device) -> Device {
+        Device(DeviceImpl::Null(device))
+    }
+}
+
+#[cfg(feature = "native-pulseaudio")]
+impl From<pulseaudio::Device> for Device {
+    fn from(device: pulseaudio::Device) -> Device {
+        Device(DeviceImpl::PulseAudio(device))
+    }
+}
+
+#[cfg(feature = "native-pipewire")]
+impl From<pipewire::Device> for Device {
+    fn from(device: pipewire::Device) -> Device {
+        Device(DeviceImpl::PipeWire(device))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Backend;
+
+    #[test]
+    fn errors_if_buffer_capacity_is_zero() -> Result<()> {
+        let mut host = Host::with_backend(Backend::Null)?;
+        let producer = host
+            .default_output_device()?
+            .open_outstream_buffered::<[f32; 2]>(Default::default(), 0);
+        assert_eq!(producer.err(), Some(Error::Invalid));
+        let consumer = host
+            .default_input_device()?
+            .open_instream_buffered::<[f32; 2]>(Default::default(), 0);
+        assert_eq!(consumer.err(), Some(Error::Invalid));
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn errors_if_async_buffer_capacity_is_zero() -> Result<()> {
+        let mut host = Host::with_backend(Backend::Null)?;
+        let sink = host
+            .default_output_device()?
+            .open_outstream_sink::<[f32; 2]>(Default::default(), 0);
+        assert_eq!(sink.err(), Some(Error::Invalid));
+        let source = host
+            .default_input_device()?
+            .open_instream_source::<[f32; 2]>(Default::default(), 0);
+        assert_eq!(source.err(), Some(Error::Invalid));
+        Ok(())
+    }
+}
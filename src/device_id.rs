@@ -0,0 +1,253 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::backend::Backend;
+use crate::error::{Error, Result};
+use crate::stream_options::Direction;
+
+/// Identifies a device across launches of an application (e.g. to find the device a user chose
+/// in its settings again). See [`Device::id`](crate::Device::id) and
+/// [`Host::device_by_id`](crate::Host::device_by_id).
+///
+/// Unlike names, identifiers tell identical devices (e.g. two interfaces of the same model) apart,
+/// by the order the backend lists them in. Identifiers are serializable with the `serde` feature,
+/// and convert to and from strings (e.g. `Alsa/Output/0/USB Audio: - (hw:2,0)`).
+///
+/// # Examples
+/// ```
+/// # use audiohal::*;
+/// let id: DeviceId = "Null/Duplex/1/USB Headset".parse()?;
+/// assert_eq!(id.backend, Backend::Null);
+/// assert_eq!(id.name, "USB Headset");
+/// assert_eq!(id.occurrence, 1);
+/// assert_eq!(id.to_string(), "Null/Duplex/1/USB Headset");
+/// # Result::Ok(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceId {
+    pub backend: Backend,
+    /// The backend's name for the device. For the native PulseAudio and PipeWire backends, this is
+    /// the server's identifier, and not the description returned by
+    /// [`Device::name`](crate::Device::name).
+    pub name: String,
+    /// The number of devices of the host with the same name and direction listed before this one.
+    pub occurrence: usize,
+    /// The directions the device supports.
+    pub direction: Direction,
+}
+
+impl fmt::Display for DeviceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The name goes last, as it may contain separators.
+        write!(
+            f,
+            "{:?}/{:?}/{}/{}",
+            self.backend, self.direction, self.occurrence, self.name
+        )
+    }
+}
+
+impl FromStr for DeviceId {
+    type Err = Error;
+
+    /// Parses an identifier formatted by [`DeviceId`]'s `Display`. Returns [`Error::Invalid`] if
+    /// it is malformed.
+    fn from_str(id: &str) -> Result<DeviceId> {
+        let mut parts = id.splitn(4, '/');
+        let mut next = || parts.next().ok_or(Error::Invalid);
+        let backend = parse_backend(next()?)?;
+        let direction = match next()? {
+            "Input" => Direction::Input,
+            "Output" => Direction::Output,
+            "Duplex" => Direction::Duplex,
+            _ => return Err(Error::Invalid),
+        };
+        let occurrence = next()?.parse().or(Err(Error::Invalid))?;
+        let name = next()?.to_string();
+        Ok(DeviceId {
+            backend,
+            name,
+            occurrence,
+            direction,
+        })
+    }
+}
+
+fn parse_backend(name: &str) -> Result<Backend> {
+    use Backend::*;
+    [
+        None,
+        Jack,
+        Alsa,
+        CoreAudio,
+        Wasapi,
+        LinuxFallback,
+        Dummy,
+        Null,
+        File,
+        Loopback,
+        PulseAudio,
+        PipeWire,
+    ]
+    .iter()
+    .find(|backend| format!("{:?}", backend) == name)
+    .copied()
+    .ok_or(Error::Invalid)
+}
+
+/// Returns the index of the identifier of `candidates` that best matches `id`, if any matches.
+///
+/// From best to worst, matches are: the same identifier, the same device in another position
+/// (e.g. after an identical device was unplugged), a device with the same name and a compatible
+/// direction on any backend, and a device whose name only differs in case and in the card and
+/// device numbers of its ALSA `(hw:N,M)` suffix (e.g. a USB card numbered differently after a
+/// reboot).
+pub(crate) fn best_match(id: &DeviceId, candidates: &[DeviceId]) -> Option<usize> {
+    let fuzzy_name = fuzzy(&id.name);
+    let rules: [&dyn Fn(&DeviceId) -> bool; 4] = [
+        &|candidate| candidate == id,
+        &|candidate| {
+            candidate.backend == id.backend
+                && candidate.name == id.name
+                && candidate.direction == id.direction
+        },
+        &|candidate| candidate.name == id.name && candidate.direction.supports(id.direction),
+        &|candidate| {
+            fuzzy(&candidate.name) == fuzzy_name && candidate.direction.supports(id.direction)
+        },
+    ];
+    rules
+        .iter()
+        .find_map(|rule| candidates.iter().position(rule))
+}
+
+/// Normalizes a name for fuzzy matching: removes its ALSA `(hw:N,M)` suffix, if any, and ignores
+/// case. Other digits are kept, as they tell ports of the same card apart (e.g. `HDMI 0` and
+/// `HDMI 1`).
+fn fuzzy(name: &str) -> String {
+    let is_hw_suffix = |suffix: &str| {
+        let numbers = match suffix
+            .strip_prefix(" (hw:")
+            .and_then(|s| s.strip_suffix(')'))
+        {
+            Some(numbers) => numbers,
+            None => return false,
+        };
+        let mut numbers = numbers.split(',');
+        let is_number = |n: &str| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit());
+        matches!(
+            (numbers.next(), numbers.next(), numbers.next()),
+            (Some(card), Some(device), None) if is_number(card) && is_number(device)
+        )
+    };
+    let name = match name.rfind(" (hw:") {
+        Some(start) if is_hw_suffix(&name[start..]) => &name[..start],
+        _ => name,
+    };
+    name.to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Device, Host, NullDeviceOptions, NullHostOptions};
+
+    fn id(backend: Backend, name: &str, occurrence: usize, direction: Direction) -> DeviceId {
+        DeviceId {
+            backend,
+            name: name.to_string(),
+            occurrence,
+            direction,
+        }
+    }
+
+    #[test]
+    fn converts_to_and_from_strings() {
+        let original = id(
+            Backend::Alsa,
+            "USB Audio: - (hw:2,0)/1",
+            3,
+            Direction::Input,
+        );
+        assert_eq!(original.to_string().parse(), Ok(original));
+        for malformed in &[
+            "",
+            "Alsa",
+            "Alsa/Input/x/Mic",
+            "Alsa/Sideways/0/Mic",
+            "Foo/Input/0/Mic",
+        ] {
+            assert_eq!(malformed.parse::<DeviceId>(), Err(Error::Invalid));
+        }
+    }
+
+    #[test]
+    fn tells_identical_devices_apart() -> Result<()> {
+        let interface = NullDeviceOptions {
+            name: "Interface".to_string(),
+            ..Default::default()
+        };
+        let mut host = Host::with_null_backend(NullHostOptions {
+            devices: vec![interface.clone(), interface],
+            ..Default::default()
+        })?;
+        let ids: Vec<DeviceId> = host.devices()?.iter().map(Device::id).collect();
+        assert_eq!(ids[0].occurrence, 0);
+        assert_eq!(ids[1].occurrence, 1);
+        assert_eq!(ids[1].backend, Backend::Null);
+        assert_eq!(host.device_by_id(&ids[1])?.id(), ids[1]);
+        let unknown = id(Backend::Null, "Speakers", 0, Direction::Output);
+        assert_eq!(host.device_by_id(&unknown).err(), Some(Error::NoSuchDevice));
+        Ok(())
+    }
+
+    #[test]
+    fn matches_best_candidate() {
+        let headset = id(Backend::Alsa, "Headset (hw:1,0)", 1, Direction::Output);
+        let exact = vec![
+            id(Backend::Alsa, "Headset (hw:1,0)", 0, Direction::Output),
+            headset.clone(),
+        ];
+        assert_eq!(best_match(&headset, &exact), Some(1));
+        // The first headset was unplugged.
+        let moved = vec![id(Backend::Alsa, "Headset (hw:1,0)", 0, Direction::Output)];
+        assert_eq!(best_match(&headset, &moved), Some(0));
+        let duplex = vec![id(Backend::Jack, "Headset (hw:1,0)", 0, Direction::Duplex)];
+        assert_eq!(best_match(&headset, &duplex), Some(0));
+        let renumbered = vec![
+            id(Backend::Alsa, "Speakers (hw:0,0)", 0, Direction::Output),
+            id(Backend::Alsa, "HEADSET (hw:2,0)", 0, Direction::Output),
+        ];
+        assert_eq!(best_match(&headset, &renumbered), Some(1));
+        let input = vec![id(Backend::Alsa, "Headset (hw:1,0)", 0, Direction::Input)];
+        assert_eq!(best_match(&headset, &input), None);
+    }
+
+    #[test]
+    fn tells_ports_of_a_card_apart() {
+        let hdmi = id(
+            Backend::Alsa,
+            "HDA Intel PCH: HDMI 0 (hw:0,3)",
+            0,
+            Direction::Output,
+        );
+        let other_port = vec![id(
+            Backend::Alsa,
+            "HDA Intel PCH: HDMI 1 (hw:0,7)",
+            0,
+            Direction::Output,
+        )];
+        assert_eq!(best_match(&hdmi, &other_port), None);
+        let renumbered = vec![
+            other_port[0].clone(),
+            id(
+                Backend::Alsa,
+                "HDA Intel PCH: HDMI 0 (hw:1,3)",
+                0,
+                Direction::Output,
+            ),
+        ];
+        assert_eq!(best_match(&hdmi, &renumbered), Some(1));
+    }
+}
@@ -1,10 +1,10 @@
 use std::fmt;
 use std::result;
 
-use crate::Format;
+use crate::{Backend, Format};
 
 #[non_exhaustive]
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Error {
     /// An out-of-memory occurred while allocating in a C library.
     OutOfMemory,
@@ -12,6 +12,11 @@ pub enum Error {
     Unknown(&'static str),
     Invalid,
     InvalidFramesPerBuffer,
+    /// The requested [`StreamOptions::block_size`](crate::StreamOptions::block_size) is not
+    /// positive.
+    InvalidBlockSize,
+    /// A [`StreamFlags`](crate::StreamFlags) flag was set that is invalid for the stream.
+    InvalidFlags,
     /// The size of the frame type specified in the stream callback does not match the expected size
     /// of [`Format`].
     InvalidFrameSize {
@@ -20,16 +25,39 @@ pub enum Error {
     },
     /// The backend requested was either not compiled, or is uninitializable.
     BackendUnavailable,
+    /// A backend-specific extension (e.g. of the `jack` module) was used on a host or stream of
+    /// another backend.
+    WrongBackend,
     /// The requested device was unavailable.
     NoSuchDevice,
+    /// The device cannot open another stream in this direction, e.g. the loopback device while
+    /// another stream uses its output.
+    DeviceBusy,
     /// The requested format is not compatible with the device in-use.
     IncompatibleFormat(Format),
     /// The requested sample rate is not compatible with the device.
     IncompatibleSampleRate,
     /// The requested number of channels is not compatible with the device.
     IncompatibleNChannels,
+    /// The device cannot open streams in the requested direction, e.g. an input stream on an
+    /// output-only device.
+    IncompatibleDirection,
     /// ['Stream::start`] called on stream that has already started.
     StreamAlreadyStarted,
+    /// [`Host::refresh_devices`](crate::Host::refresh_devices) was called while streams of the
+    /// backend were open.
+    StreamsOpen,
+    /// The device stopped responding, e.g. because it was disconnected.
+    TimedOut,
+    /// A file backing a device of the file backend could not be accessed.
+    Io(std::io::ErrorKind),
+    /// The underlying audio API (e.g. ALSA or CoreAudio) failed unexpectedly, with its own error
+    /// code and description. Worth including in bug reports.
+    HostError {
+        backend: Backend,
+        code: i64,
+        text: String,
+    },
 }
 
 pub type Result<T> = result::Result<T, Error>;
@@ -0,0 +1,366 @@
+//! A backend whose devices are WAV files, for capturing or feeding an application's audio without
+//! audio hardware.
+//!
+//! Built on top of the null backend: streams are driven by a thread at the stream's sample rate
+//! (or faster, see [`Clock`]), and exchange their frames with a file instead of discarding them.
+use std::path::PathBuf;
+
+use crate::backend::Backend;
+use crate::error::Result;
+use crate::null::{self, open_wav, Clock, DeviceKind, HostState, NullDeviceOptions, VirtualDevice};
+
+/// Describes an output device of the file backend, that records everything played to a WAV file.
+///
+/// The file matches the stream's format, number of channels and sample rate. It is created (or
+/// truncated) when a stream is opened, and completed when the stream is closed. If the file cannot
+/// be written, the stream ends with [`StreamState::Failed`](crate::StreamState::Failed). If it
+/// cannot be completed, [`Stream::close`](crate::Stream::close) returns the error.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileSinkOptions {
+    pub name: String,
+    pub path: PathBuf,
+    /// The maximum number of channels of output streams.
+    pub max_channels: i32,
+    /// The sample rates that streams can be opened with.
+    pub sample_rates: Vec<i32>,
+    /// The sample rate used for [`SampleRate::DeviceDefault`](crate::SampleRate::DeviceDefault).
+    pub default_sample_rate: i32,
+}
+
+impl Default for FileSinkOptions {
+    fn default() -> FileSinkOptions {
+        FileSinkOptions {
+            name: "WAV File Output".to_string(),
+            path: PathBuf::from("audiohal.wav"),
+            max_channels: 2,
+            sample_rates: vec![44_100, 48_000],
+            default_sample_rate: 48_000,
+        }
+    }
+}
+
+/// Describes an input device of the file backend, that plays a WAV file into input streams.
+///
+/// The device has the file's number of channels and sample rate. Samples are converted to the
+/// stream's format. The file is opened when a stream is opened, so every stream starts from the
+/// beginning of the file. Once the end of the file is reached, the rest of the last buffer is
+/// silent, and the stream either starts over or ends (see
+/// [`Stream::is_active`](crate::Stream::is_active)). If the file cannot be read, the stream ends
+/// with [`StreamState::Failed`](crate::StreamState::Failed).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileSourceOptions {
+    pub name: String,
+    pub path: PathBuf,
+    /// Whether to loop the file instead of ending the stream.
+    pub looping: bool,
+}
+
+impl Default for FileSourceOptions {
+    fn default() -> FileSourceOptions {
+        FileSourceOptions {
+            name: "WAV File Input".to_string(),
+            path: PathBuf::new(),
+            looping: false,
+        }
+    }
+}
+
+/// Configures a host of the file backend.
+///
+/// The default options have a single stereo [`FileSinkOptions::default`] device, which records to
+/// `audiohal.wav` in the working directory, in real time, and no input devices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileHostOptions {
+    /// The host's output devices. The first one is the default output device.
+    pub sinks: Vec<FileSinkOptions>,
+    /// The host's input devices. The first one is the default input device.
+    pub sources: Vec<FileSourceOptions>,
+    pub clock: Clock,
+}
+
+impl Default for FileHostOptions {
+    fn default() -> FileHostOptions {
+        FileHostOptions {
+            sinks: vec![FileSinkOptions::default()],
+            sources: Vec::new(),
+            clock: Clock::default(),
+        }
+    }
+}
+
+pub fn new_host(options: FileHostOptions) -> Result<null::Host> {
+    options.clock.validate()?;
+    let mut devices = Vec::new();
+    for sink in options.sinks {
+        let device_options = NullDeviceOptions {
+            name: sink.name,
+            max_input_channels: 0,
+            max_output_channels: sink.max_channels,
+            sample_rates: sink.sample_rates,
+            default_sample_rate: sink.default_sample_rate,
+            ..Default::default()
+        };
+        device_options.validate()?;
+        devices.push(VirtualDevice {
+            options: device_options,
+            kind: DeviceKind::FileSink(sink.path),
+        });
+    }
+    for source in options.sources {
+        // Read the header now, so that the device reports the file's layout.
+        let spec = open_wav(&source.path)?.spec();
+        devices.push(VirtualDevice {
+            options: NullDeviceOptions {
+                name: source.name,
+                max_input_channels: i32::from(spec.n_channels),
+                max_output_channels: 0,
+                sample_rates: vec![spec.sample_rate as i32],
+                default_sample_rate: spec.sample_rate as i32,
+                ..Default::default()
+            },
+            kind: DeviceKind::FileSource {
+                path: source.path,
+                looping: source.looping,
+            },
+        });
+    }
+    Ok(null::Host::with_state(HostState {
+        name: "File",
+        backend: Backend::File,
+        clock: options.clock,
+        devices,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wav::{WavSpec, WavWriter};
+    use crate::{Error, Format, Host, SampleRate, StreamOptions, StreamState};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Arc};
+    use std::time::{Duration, Instant};
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("audiohal-{}-{}.wav", name, std::process::id()))
+    }
+
+    /// Writes a mono 8kHz file with `n_samples` samples counting up from 1.
+    fn write_ramp(name: &str, n_samples: i16) -> PathBuf {
+        let path = temp_path(name);
+        let file = std::fs::File::create(&path).unwrap();
+        let mut writer = WavWriter::new(
+            file,
+            WavSpec {
+                format: Format::I16,
+                n_channels: 1,
+                sample_rate: 8000,
+            },
+        )
+        .unwrap();
+        for sample in 1..=n_samples {
+            writer.write_samples(&sample.to_ne_bytes()).unwrap();
+        }
+        path
+    }
+
+    /// Captures the frames of a stream opened on a file source, until it ends or `max_frames`
+    /// frames were captured.
+    fn capture(path: &PathBuf, looping: bool, max_frames: usize) -> Result<(Vec<i32>, bool)> {
+        let mut host = Host::with_file_backend(FileHostOptions {
+            sources: vec![FileSourceOptions {
+                path: path.clone(),
+                looping,
+                ..Default::default()
+            }],
+            clock: Clock::Accelerated(100.0),
+            ..Default::default()
+        })?;
+        let (sender, receiver) = mpsc::channel();
+        let mut stream = host.default_input_device()?.open_instream(StreamOptions {
+            // The file's 16-bit samples are converted to floats.
+            callback: Box::new(move |buffer: &mut [[f32; 1]]| {
+                let _ = sender.send(buffer.to_vec());
+            }),
+            frames_per_buffer: Some(64),
+            ..Default::default()
+        })?;
+        stream.start()?;
+        let mut frames = Vec::new();
+        while frames.len() < max_frames {
+            match receiver.recv_timeout(Duration::from_secs(5)) {
+                Ok(buffer) => frames.extend(buffer.iter().map(|frame| (frame[0] * 32768.0) as i32)),
+                Err(_) => break,
+            }
+        }
+        Ok((frames, stream.is_active()))
+    }
+
+    #[test]
+    fn plays_file_into_input_stream() -> Result<()> {
+        let path = write_ramp("source", 100);
+        let (frames, is_active) = capture(&path, false, usize::max_value())?;
+        std::fs::remove_file(&path).unwrap();
+        // The end of the last buffer is silent, and the stream ends at the end of the file.
+        let mut expected: Vec<_> = (1..=100).collect();
+        expected.resize(128, 0);
+        assert_eq!(frames, expected);
+        assert!(!is_active);
+        Ok(())
+    }
+
+    #[test]
+    fn loops_file() -> Result<()> {
+        let path = write_ramp("looping", 100);
+        let (frames, is_active) = capture(&path, true, 256)?;
+        std::fs::remove_file(&path).unwrap();
+        let expected: Vec<_> = (1..=100).cycle().take(256).collect();
+        assert_eq!(frames, expected);
+        assert!(is_active);
+        Ok(())
+    }
+
+    #[test]
+    fn fails_if_file_cannot_be_read() -> Result<()> {
+        let path = write_ramp("truncated", 100);
+        // The header still announces 100 samples.
+        let file = std::fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(44 + 20).unwrap();
+        let mut host = Host::with_file_backend(FileHostOptions {
+            sources: vec![FileSourceOptions {
+                path: path.clone(),
+                ..Default::default()
+            }],
+            clock: Clock::Accelerated(100.0),
+            ..Default::default()
+        })?;
+        let mut stream = host
+            .default_input_device()?
+            .open_instream(StreamOptions::<[f32; 1]>::default())?;
+        stream.start()?;
+        let start = Instant::now();
+        while stream.is_active() {
+            assert_lt!(start.elapsed(), Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            stream.state(),
+            StreamState::Failed(Error::Io(std::io::ErrorKind::UnexpectedEof))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn source_reports_file_layout() -> Result<()> {
+        let path = write_ramp("layout", 10);
+        let mut host = Host::with_file_backend(FileHostOptions {
+            sources: vec![FileSourceOptions {
+                path: path.clone(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        })?;
+        let mut device = host.default_input_device()?;
+        let stereo = device.open_instream(StreamOptions::<[f32; 2]>::default());
+        let resampled = device.open_instream(StreamOptions::<[f32; 1]> {
+            sample_rate: SampleRate::Exact(44_100),
+            ..Default::default()
+        });
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(stereo.err(), Some(Error::IncompatibleNChannels));
+        assert_eq!(resampled.err(), Some(Error::IncompatibleSampleRate));
+        Ok(())
+    }
+
+    #[test]
+    fn enumerates_sinks() -> Result<()> {
+        let mut host = Host::with_file_backend(FileHostOptions {
+            sinks: vec![
+                FileSinkOptions {
+                    name: "First".to_string(),
+                    ..Default::default()
+                },
+                FileSinkOptions {
+                    name: "Second".to_string(),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        })?;
+        assert_eq!(host.name(), "File");
+        let names: Vec<_> = host
+            .devices()?
+            .iter()
+            .map(|device| device.name().to_string())
+            .collect();
+        assert_eq!(names, vec!["First", "Second"]);
+        assert_eq!(host.default_input_device().err(), Some(Error::NoSuchDevice));
+        Ok(())
+    }
+
+    #[test]
+    fn records_output_to_wav_file() -> Result<()> {
+        let path = temp_path("sink");
+        let mut host = Host::with_file_backend(FileHostOptions {
+            sinks: vec![FileSinkOptions {
+                path: path.clone(),
+                ..Default::default()
+            }],
+            clock: Clock::Accelerated(50.0),
+            ..Default::default()
+        })?;
+        let n_callbacks = Arc::new(AtomicUsize::new(0));
+        let callback_count = Arc::clone(&n_callbacks);
+        let mut stream = host
+            .default_output_device()?
+            .open_outstream(StreamOptions {
+                callback: Box::new(move |buffer: &mut [[i16; 2]]| {
+                    if callback_count.load(Ordering::SeqCst) < 4 {
+                        for frame in buffer.iter_mut() {
+                            *frame = [1000, -1000];
+                        }
+                        callback_count.fetch_add(1, Ordering::SeqCst);
+                    }
+                }),
+                format: Format::I16,
+                sample_rate: SampleRate::Exact(44_100),
+                frames_per_buffer: Some(100),
+                ..Default::default()
+            })?;
+        stream.start()?;
+        let start = Instant::now();
+        while n_callbacks.load(Ordering::SeqCst) < 4 {
+            assert_lt!(start.elapsed(), Duration::from_secs(5));
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        stream.close()?;
+
+        let file = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(&file[..4], b"RIFF");
+        assert_eq!(&file[22..24], &2_u16.to_le_bytes());
+        assert_eq!(&file[24..28], &44_100_u32.to_le_bytes());
+        let data_len = u32::from_le_bytes([file[40], file[41], file[42], file[43]]) as usize;
+        assert_eq!(data_len, file.len() - 44);
+        assert_eq!(data_len % 400, 0);
+        assert_eq!(&file[44..48], &[0xe8, 0x03, 0x18, 0xfc]);
+        Ok(())
+    }
+
+    #[test]
+    fn errors_if_file_cannot_be_created() -> Result<()> {
+        let mut host = Host::with_file_backend(FileHostOptions {
+            sinks: vec![FileSinkOptions {
+                path: temp_path("missing").join("output.wav"),
+                ..Default::default()
+            }],
+            ..Default::default()
+        })?;
+        let result = host
+            .default_output_device()?
+            .open_outstream(StreamOptions::<[f32; 2]>::default());
+        assert_eq!(result.err(), Some(Error::Io(std::io::ErrorKind::NotFound)));
+        Ok(())
+    }
+}
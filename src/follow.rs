@@ -0,0 +1,832 @@
+//! Streams that are reopened on another device: output streams that follow the default output
+//! device (see [`Host::open_default_outstream`]), and streams reconnecting once their device is
+//! disconnected (see [`Reconnect`]).
+//!
+//! PortAudio only reads the default device when it is initialized, and it can only be
+//! re-initialized once all streams are closed. A following stream therefore cannot notice that the
+//! default device changed while it plays: it is moved by [`Host::refresh_devices`] instead, which
+//! closes it, fading out, and reopens it on the default device once PortAudio is re-initialized,
+//! fading in. There is no crossfade, and the stream is silent while PortAudio is re-initialized.
+//!
+//! A single watcher thread reopens disconnected streams on a fallback device. Since PortAudio
+//! keeps listing a device that was unplugged until re-initialized, the devices are refreshed
+//! first: this is possible once the disconnected stream is closed, unless other streams are open.
+//!
+//! The user's callback is kept, along with its state. It is handed from one stream to the next
+//! without locking on the audio thread, and is not called while no stream is open.
+use lazy_static::lazy_static;
+use parking_lot::Mutex;
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, Instant};
+
+use crate::convert;
+use crate::device::Device;
+use crate::device_id::DeviceId;
+use crate::error::{Error, Result};
+use crate::host::Host;
+use crate::stream::{Stream as InnerStream, StreamState};
+use crate::stream_options::{
+    Callback, Direction, Format, Latency, Reconnect, SampleRate, StreamFlags, StreamOptions,
+};
+
+/// The length of the fades, in frames (about 40 ms at 48 kHz).
+const FADE_FRAMES: usize = 2048;
+/// How long to wait for a stream to fade out before closing it regardless.
+const FADE_OUT_TIMEOUT: Duration = Duration::from_millis(500);
+/// How often the watcher checks whether streams must be reconnected.
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+/// How often the devices are refreshed at most while a stream cannot be reconnected. Refreshing
+/// re-initializes PortAudio, which probes every device again.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    /// The open streams, to move on refresh and to poll.
+    static ref FOLLOWERS: Mutex<Followers> = Mutex::new(Followers {
+        followers: Vec::new(),
+        is_watched: false,
+    });
+    /// Held while moving streams, so that no stream is moved or reconnected while others are
+    /// moved on refresh (which would then fail, with a stream open).
+    static ref MOVING: Mutex<()> = Mutex::new(());
+}
+
+struct Followers {
+    followers: Vec<Weak<dyn Follow>>,
+    /// Whether the watcher thread is running. It stops once no stream is left.
+    is_watched: bool,
+}
+
+/// Runs `refresh`, which re-initializes PortAudio, with the following streams closed, then
+/// reopens them on the (possibly new) default output device.
+///
+/// Returns [`Error::StreamsOpen`] without closing them if `n_open_streams`, the number of open
+/// streams including those of following streams, shows that other streams are open.
+pub fn while_suspended(
+    n_open_streams: impl FnOnce() -> usize,
+    refresh: impl FnOnce() -> Result<()>,
+) -> Result<()> {
+    let _moving = MOVING.lock();
+    let followers = live_followers();
+    let n_following = followers
+        .iter()
+        .filter(|follower| follower.is_open())
+        .count();
+    if n_open_streams() > n_following {
+        return Err(Error::StreamsOpen);
+    }
+    for follower in &followers {
+        follower.suspend();
+    }
+    let result = refresh();
+    for follower in &followers {
+        follower.resume();
+    }
+    result
+}
+
+fn live_followers() -> Vec<Arc<dyn Follow>> {
+    let mut registry = FOLLOWERS.lock();
+    registry
+        .followers
+        .retain(|follower| follower.strong_count() > 0);
+    registry
+        .followers
+        .iter()
+        .filter_map(Weak::upgrade)
+        .collect()
+}
+
+/// Registers a stream, and starts the watcher thread unless it is running.
+fn register(follower: Weak<dyn Follow>) {
+    let mut registry = FOLLOWERS.lock();
+    registry.followers.push(follower);
+    if !registry.is_watched {
+        registry.is_watched = true;
+        std::thread::spawn(watch);
+    }
+}
+
+/// Polls the following streams, until none is left.
+fn watch() {
+    loop {
+        std::thread::sleep(WATCH_INTERVAL);
+        let _moving = MOVING.lock();
+        let followers = {
+            let mut registry = FOLLOWERS.lock();
+            registry
+                .followers
+                .retain(|follower| follower.strong_count() > 0);
+            if registry.followers.is_empty() {
+                registry.is_watched = false;
+                return;
+            }
+            registry
+                .followers
+                .iter()
+                .filter_map(Weak::upgrade)
+                .collect::<Vec<_>>()
+        };
+        for follower in followers {
+            follower.poll();
+        }
+    }
+}
+
+/// Type-erased stream, for the registry.
+trait Follow: Send + Sync {
+    /// Returns whether a stream is open.
+    fn is_open(&self) -> bool;
+    /// Fades out and closes the stream.
+    fn suspend(&self);
+    /// Reopens the stream on the first device available, and starts it if it was started.
+    fn resume(&self);
+    /// Reconnects the stream if it was disconnected.
+    fn poll(&self);
+}
+
+/// The device a stream is opened on.
+pub enum Target {
+    /// The host's default device of the stream's direction.
+    Default,
+    Device(DeviceId),
+}
+
+/// A stream that is reopened on another device, when the devices are refreshed or its device is
+/// disconnected.
+pub struct Stream<Frame>(Arc<Follower<Frame>>);
+
+struct Follower<Frame> {
+    host: Mutex<Host>,
+    target: Target,
+    direction: Direction,
+    template: Template,
+    mixer: Arc<Mixer<Frame>>,
+    state: Mutex<State<Frame>>,
+}
+
+struct State<Frame> {
+    /// None while suspended, or if it could not be reopened.
+    stream: Option<Opened<Frame>>,
+    /// The device the stream was disconnected from, until reconnected. Not reopened until the
+    /// devices are refreshed, since PortAudio may still list it.
+    lost: Option<DeviceId>,
+    /// When the devices were last refreshed to reconnect the stream.
+    refreshed_at: Option<Instant>,
+    started: bool,
+    /// Set once the following stream is closed, so that it is not reopened.
+    closed: bool,
+}
+
+/// A stream opened by a following stream.
+struct Opened<Frame> {
+    stream: InnerStream<Frame>,
+    /// Identifies the stream's callback to the mixer.
+    id: usize,
+    device: DeviceId,
+}
+
+impl<Frame> Stream<Frame>
+where
+    Frame: sample::Frame + Send + 'static,
+{
+    pub fn open(
+        host: Host,
+        target: Target,
+        direction: Direction,
+        options: StreamOptions<Frame>,
+    ) -> Result<Stream<Frame>> {
+        let (template, callback) = Template::split(options);
+        let mixer = Arc::new(Mixer::new(
+            callback,
+            direction,
+            template.format,
+            template.n_channels as usize,
+        ));
+        let follower = Arc::new(Follower {
+            host: Mutex::new(host),
+            target,
+            direction,
+            template,
+            mixer,
+            state: Mutex::new(State {
+                stream: None,
+                lost: None,
+                refreshed_at: None,
+                started: false,
+                closed: false,
+            }),
+        });
+        let mut device = follower.target_device()?;
+        let opened = follower.open_on(&mut device, false)?;
+        follower.mixer.acquire(opened.id);
+        follower.state.lock().stream = Some(opened);
+        let registered: Arc<dyn Follow> = follower.clone();
+        register(Arc::downgrade(&registered));
+        Ok(Stream(follower))
+    }
+}
+
+impl<Frame> Stream<Frame> {
+    pub fn start(&mut self) -> Result<()> {
+        let mut state = self.0.state.lock();
+        if let Some(opened) = &mut state.stream {
+            opened.stream.start()?;
+        }
+        state.started = true;
+        Ok(())
+    }
+
+    pub fn latency(&self) -> Duration {
+        self.0
+            .state
+            .lock()
+            .stream
+            .as_ref()
+            .map_or(Duration::default(), |opened| opened.stream.latency())
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.0
+            .state
+            .lock()
+            .stream
+            .as_ref()
+            .map_or(false, |opened| opened.stream.is_active())
+    }
+
+    pub fn state(&self) -> StreamState {
+        self.0
+            .state
+            .lock()
+            .stream
+            .as_ref()
+            .map_or(StreamState::Disconnected, |opened| opened.stream.state())
+    }
+
+    pub fn close(self) -> Result<()> {
+        let mut state = self.0.state.lock();
+        state.closed = true;
+        match state.stream.take() {
+            Some(opened) => opened.stream.close(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<Frame> Drop for Stream<Frame> {
+    fn drop(&mut self) {
+        self.0.state.lock().closed = true;
+    }
+}
+
+impl<Frame> Follower<Frame>
+where
+    Frame: sample::Frame + Send + 'static,
+{
+    fn is_disconnected(&self) -> bool {
+        self.state.lock().stream.as_ref().map_or(true, |opened| {
+            opened.stream.state() == StreamState::Disconnected
+        })
+    }
+
+    fn target_device(&self) -> Result<Device> {
+        let mut host = self.host.lock();
+        match (&self.target, self.direction) {
+            (Target::Default, Direction::Input) => host.default_input_device(),
+            (Target::Default, _) => host.default_output_device(),
+            (Target::Device(id), _) => host.device_by_id(id),
+        }
+    }
+
+    /// Returns whether the stream is reopened once disconnected. Streams following the default
+    /// device are reopened on the new default device.
+    fn reconnects(&self) -> bool {
+        matches!(self.target, Target::Default) || self.template.reconnect != Reconnect::Never
+    }
+
+    /// Returns the devices to try reopening the stream on, in order, without `lost`.
+    fn fallback_devices(&self, lost: Option<&DeviceId>) -> Vec<Device> {
+        let mut devices: Vec<Device> = self.target_device().into_iter().collect();
+        {
+            let mut host = self.host.lock();
+            match &self.template.reconnect {
+                Reconnect::ToDefault => devices.extend(match self.direction {
+                    Direction::Input => host.default_input_device(),
+                    _ => host.default_output_device(),
+                }),
+                Reconnect::ToDevices(ids) => {
+                    devices.extend(ids.iter().filter_map(|id| host.device_by_id(id).ok()))
+                }
+                _ => {}
+            }
+        }
+        let mut ids = Vec::new();
+        devices.retain(|device| {
+            let id = device.id();
+            let is_candidate = Some(&id) != lost && !ids.contains(&id);
+            ids.push(id);
+            is_candidate
+        });
+        devices
+    }
+
+    /// Opens the stream on the first fallback device available, without `state.lost`.
+    fn reopen(&self, state: &mut State<Frame>) {
+        // Without any device available, the stream stays closed until retried.
+        for mut device in self.fallback_devices(state.lost.as_ref()) {
+            let opened = self
+                .open_on(&mut device, true)
+                .and_then(|opened| self.take_over(opened, state.started));
+            if let Ok(opened) = opened {
+                state.stream = Some(opened);
+                state.lost = None;
+                state.refreshed_at = None;
+                return;
+            }
+        }
+    }
+
+    /// Closes the disconnected stream, refreshes the devices, and reopens the stream on a fallback
+    /// device.
+    fn reconnect(&self) {
+        let mut state = self.state.lock();
+        if state.closed {
+            return;
+        }
+        if let Some(opened) = state.stream.take() {
+            state.lost = Some(opened.device.clone());
+            self.close(opened);
+        }
+        let is_refresh_due = state.refreshed_at.map_or(true, |refreshed_at| {
+            refreshed_at.elapsed() >= REFRESH_INTERVAL
+        });
+        if is_refresh_due {
+            state.refreshed_at = Some(Instant::now());
+            // Fails if other streams are open, in which case the lost device is still skipped.
+            if self.host.lock().refresh_devices_if_idle().is_ok() {
+                state.lost = None;
+            }
+        }
+        self.reopen(&mut state);
+    }
+
+    /// Fades out the stream if active, closes it, and takes the user's callback back.
+    fn close(&self, opened: Opened<Frame>) {
+        if opened.stream.is_active() {
+            self.mixer.fade_out(opened.id);
+        }
+        let id = opened.id;
+        if let Err(error) = opened.stream.close() {
+            log::warn!("Could not close stream to reopen it: {}", error);
+        }
+        self.mixer.release(id);
+    }
+
+    fn open_on(&self, device: &mut Device, fade_in: bool) -> Result<Opened<Frame>> {
+        let id = self.mixer.next_id.fetch_add(1, Ordering::Relaxed);
+        let options = self.template.options(self.mixer.callback(id, fade_in));
+        let stream = match self.direction {
+            Direction::Input => device.open_instream(options),
+            Direction::Output => device.open_outstream(options),
+            _ => Err(Error::Invalid),
+        }?;
+        Ok(Opened {
+            stream,
+            id,
+            device: device.id(),
+        })
+    }
+
+    /// Hands the user's callback to a stream, and starts it if `started`.
+    fn take_over(&self, mut opened: Opened<Frame>, started: bool) -> Result<Opened<Frame>> {
+        self.mixer.acquire(opened.id);
+        if started {
+            if let Err(error) = opened.stream.start() {
+                let id = opened.id;
+                drop(opened);
+                self.mixer.release(id);
+                return Err(error);
+            }
+        }
+        Ok(opened)
+    }
+}
+
+impl<Frame> Follow for Follower<Frame>
+where
+    Frame: sample::Frame + Send + 'static,
+{
+    fn is_open(&self) -> bool {
+        self.state.lock().stream.is_some()
+    }
+
+    fn suspend(&self) {
+        let mut state = self.state.lock();
+        if let Some(opened) = state.stream.take() {
+            self.close(opened);
+        }
+    }
+
+    fn resume(&self) {
+        let mut state = self.state.lock();
+        if state.stream.is_some() || state.closed {
+            return;
+        }
+        // The devices were just refreshed.
+        state.lost = None;
+        self.reopen(&mut state);
+    }
+
+    fn poll(&self) {
+        if self.is_disconnected() && self.reconnects() {
+            self.reconnect();
+        }
+    }
+}
+
+/// A value used by the callback of one stream at a time, without locking on the audio thread.
+struct Exclusive<T> {
+    value: UnsafeCell<T>,
+    /// The identifier of the stream that may use the value (0 if none) shifted left once, with the
+    /// lowest bit set while the value is in use.
+    holder: AtomicUsize,
+}
+
+// Only the holder accesses the value.
+unsafe impl<T: Send> Sync for Exclusive<T> {}
+
+impl<T> Exclusive<T> {
+    fn new(value: T) -> Exclusive<T> {
+        Exclusive {
+            value: UnsafeCell::new(value),
+            holder: AtomicUsize::new(0),
+        }
+    }
+
+    /// Calls `f` with the value if stream `id` holds it. Never waits.
+    fn try_with<R>(&self, id: usize, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        let (free, in_use) = (id << 1, id << 1 | 1);
+        self.holder
+            .compare_exchange(free, in_use, Ordering::Acquire, Ordering::Relaxed)
+            .ok()?;
+        let result = f(unsafe { &mut *self.value.get() });
+        self.holder.store(free, Ordering::Release);
+        Some(result)
+    }
+
+    /// Hands the value from stream `from` over to stream `to`. Waits for `from` to be done with
+    /// it, for at most one callback.
+    fn hand_over(&self, from: usize, to: usize) {
+        let (free, in_use) = (from << 1, from << 1 | 1);
+        while let Err(holder) =
+            self.holder
+                .compare_exchange_weak(free, in_use, Ordering::Acquire, Ordering::Relaxed)
+        {
+            debug_assert_eq!(holder & !1, free, "The value is held by another stream.");
+            std::thread::yield_now();
+        }
+        self.holder.store(to << 1, Ordering::Release);
+    }
+}
+
+/// What the callbacks of the successive streams of a following stream share.
+struct Mixer<Frame> {
+    /// Held by the stream calling the user's callback.
+    callback: Exclusive<Callback<Frame>>,
+    /// The stream to fade out while it still calls the user's callback, or 0.
+    fading_out: AtomicUsize,
+    /// Set by the callback of the stream fading out, once silent.
+    faded_out: AtomicBool,
+    /// The identifier of the next stream opened.
+    next_id: AtomicUsize,
+    direction: Direction,
+    format: Format,
+    n_channels: usize,
+}
+
+impl<Frame> Mixer<Frame>
+where
+    Frame: sample::Frame + Send + 'static,
+{
+    fn new(
+        callback: Callback<Frame>,
+        direction: Direction,
+        format: Format,
+        n_channels: usize,
+    ) -> Mixer<Frame> {
+        Mixer {
+            callback: Exclusive::new(callback),
+            fading_out: AtomicUsize::new(0),
+            faded_out: AtomicBool::new(false),
+            next_id: AtomicUsize::new(1),
+            direction,
+            format,
+            n_channels,
+        }
+    }
+
+    /// Returns the callback of stream `id`. The stream calls the user's callback once handed it,
+    /// and is silent otherwise. Output is faded after the user's callback, and input before.
+    fn callback(self: &Arc<Self>, id: usize, fade_in: bool) -> Callback<Frame> {
+        let mixer = Arc::clone(self);
+        let mut faded_in = if fade_in { 0 } else { FADE_FRAMES };
+        let mut faded_out = 0;
+        Box::new(move |buffer: &mut [Frame]| {
+            if faded_out >= FADE_FRAMES {
+                mixer.silence(buffer);
+                return;
+            }
+            let fading_out = mixer.fading_out.load(Ordering::Acquire) == id;
+            let rendered = mixer.callback.try_with(id, |callback| {
+                if mixer.direction == Direction::Output {
+                    callback(buffer);
+                }
+                if faded_in < FADE_FRAMES {
+                    mixer.ramp(buffer, faded_in, true);
+                    faded_in += buffer.len();
+                }
+                if fading_out {
+                    mixer.ramp(buffer, faded_out, false);
+                    faded_out += buffer.len();
+                    if faded_out >= FADE_FRAMES {
+                        mixer.faded_out.store(true, Ordering::Release);
+                    }
+                }
+                if mixer.direction == Direction::Input {
+                    callback(buffer);
+                }
+            });
+            if rendered.is_none() {
+                mixer.silence(buffer);
+            }
+        })
+    }
+
+    /// Hands the user's callback to stream `id`, which no longer needs to be faded out.
+    fn acquire(&self, id: usize) {
+        self.callback.hand_over(0, id);
+    }
+
+    /// Takes the user's callback back from stream `id`, once it is closed.
+    fn release(&self, id: usize) {
+        self.callback.hand_over(id, 0);
+        self.fading_out.store(0, Ordering::Release);
+    }
+
+    /// Fades out stream `id`, which keeps calling the user's callback, and waits until it is
+    /// silent.
+    fn fade_out(&self, id: usize) {
+        self.faded_out.store(false, Ordering::Release);
+        self.fading_out.store(id, Ordering::Release);
+        let deadline = Instant::now() + FADE_OUT_TIMEOUT;
+        while !self.faded_out.load(Ordering::Acquire) && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn silence(&self, buffer: &mut [Frame]) {
+        if self.direction == Direction::Output {
+            buffer
+                .iter_mut()
+                .for_each(|frame| *frame = Frame::equilibrium());
+        }
+    }
+
+    /// Applies the part of a fade in or out that starts `done` frames into it to `buffer`.
+    fn ramp(&self, buffer: &mut [Frame], done: usize, fade_in: bool) {
+        let gain = |done: usize| {
+            let progress = done.min(FADE_FRAMES) as f64 / FADE_FRAMES as f64;
+            if fade_in {
+                progress
+            } else {
+                1.0 - progress
+            }
+        };
+        let (from, to) = (gain(done), gain(done + buffer.len()));
+        convert::ramp(self.format, self.n_channels, samples_mut(buffer), from, to);
+    }
+}
+
+/// Returns the samples of `frames`, whose size was checked against the format when the stream
+/// was opened.
+fn samples_mut<Frame>(frames: &mut [Frame]) -> &mut [u8] {
+    let n_bytes = frames.len() * std::mem::size_of::<Frame>();
+    unsafe { std::slice::from_raw_parts_mut(frames.as_mut_ptr() as *mut u8, n_bytes) }
+}
+
+/// The options to reopen a stream with, besides its callback.
+struct Template {
+    format: Format,
+    n_channels: i32,
+    frames_per_buffer: Option<i32>,
+    block_size: Option<i32>,
+    sample_rate: SampleRate,
+    latency: Latency,
+    flags: StreamFlags,
+    name: Option<String>,
+    properties: Vec<(String, String)>,
+    reconnect: Reconnect,
+}
+
+impl Template {
+    fn split<Frame>(options: StreamOptions<Frame>) -> (Template, Callback<Frame>) {
+        let template = Template {
+            format: options.format,
+            n_channels: options.n_channels,
+            frames_per_buffer: options.frames_per_buffer,
+            block_size: options.block_size,
+            sample_rate: options.sample_rate,
+            latency: options.latency,
+            flags: options.flags,
+            name: options.name,
+            properties: options.properties,
+            reconnect: options.reconnect,
+        };
+        (template, options.callback)
+    }
+
+    /// The streams opened are only reconnected by the following stream itself.
+    fn options<Frame>(&self, callback: Callback<Frame>) -> StreamOptions<Frame> {
+        StreamOptions {
+            format: self.format,
+            n_channels: self.n_channels,
+            frames_per_buffer: self.frames_per_buffer,
+            block_size: self.block_size,
+            sample_rate: self.sample_rate,
+            latency: self.latency,
+            flags: self.flags,
+            name: self.name.clone(),
+            properties: self.properties.clone(),
+            reconnect: Reconnect::Never,
+            callback,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::null::{Clock, NullDeviceOptions, NullHostOptions};
+
+    fn open(counter: Arc<AtomicUsize>, reconnect: Reconnect) -> Result<Stream<[f32; 2]>> {
+        let host = Host::with_null_backend(NullHostOptions {
+            clock: Clock::Accelerated(10.0),
+            ..Default::default()
+        })?;
+        let mut n_calls = 0;
+        Stream::open(
+            host,
+            Target::Default,
+            Direction::Output,
+            StreamOptions {
+                callback: Box::new(move |buffer: &mut [[f32; 2]]| {
+                    n_calls += 1;
+                    counter.store(n_calls, Ordering::SeqCst);
+                    buffer.iter_mut().for_each(|frame| *frame = [1.0, 1.0]);
+                }),
+                reconnect,
+                ..Default::default()
+            },
+        )
+    }
+
+    fn two_devices() -> NullHostOptions {
+        NullHostOptions {
+            devices: vec![
+                NullDeviceOptions {
+                    name: "Speakers".to_string(),
+                    ..Default::default()
+                },
+                NullDeviceOptions {
+                    name: "Headphones".to_string(),
+                    ..Default::default()
+                },
+            ],
+            clock: Clock::Accelerated(10.0),
+        }
+    }
+
+    fn mixer(level: f32) -> Arc<Mixer<[f32; 2]>> {
+        Arc::new(Mixer::new(
+            Box::new(move |buffer: &mut [[f32; 2]]| {
+                buffer.iter_mut().for_each(|frame| *frame = [level, level]);
+            }),
+            Direction::Output,
+            Format::F32,
+            2,
+        ))
+    }
+
+    #[test]
+    fn keeps_callback_state_across_refreshes() -> Result<()> {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let mut stream = open(counter.clone(), Reconnect::Never)?;
+        stream.start()?;
+        std::thread::sleep(Duration::from_millis(50));
+        while_suspended(
+            || 0,
+            || {
+                assert!(!stream.is_active());
+                Ok(())
+            },
+        )?;
+        let n_calls = counter.load(Ordering::SeqCst);
+        assert_gt!(n_calls, 0);
+        assert!(stream.is_active());
+        std::thread::sleep(Duration::from_millis(50));
+        // The same callback keeps counting, instead of starting over.
+        assert_gt!(counter.load(Ordering::SeqCst), n_calls);
+        Ok(())
+    }
+
+    #[test]
+    fn keeps_streams_open_if_others_are_open() -> Result<()> {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let mut stream = open(counter, Reconnect::Never)?;
+        stream.start()?;
+        assert_eq!(
+            while_suspended(|| usize::MAX, || panic!("Streams are open.")),
+            Err(Error::StreamsOpen)
+        );
+        assert!(stream.is_active());
+        Ok(())
+    }
+
+    /// Waits until `condition` holds, failing after a few seconds.
+    fn wait_until(mut condition: impl FnMut() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !condition() {
+            assert_lt!(Instant::now(), deadline, "Timed out.");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn reconnects_once_disconnected() -> Result<()> {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let mut stream = open(counter.clone(), Reconnect::ToDefault)?;
+        stream.start()?;
+        assert_eq!(stream.state(), StreamState::Active);
+        // Simulates a disconnection, as a failed reconnection would leave it.
+        stream.0.suspend();
+        assert_eq!(stream.state(), StreamState::Disconnected);
+        wait_until(|| stream.state() == StreamState::Active);
+        let n_calls = counter.load(Ordering::SeqCst);
+        wait_until(|| counter.load(Ordering::SeqCst) > n_calls);
+        Ok(())
+    }
+
+    #[test]
+    fn tries_each_fallback_device_once() -> Result<()> {
+        let host = Host::with_null_backend(two_devices())?;
+        let ids: Vec<DeviceId> = {
+            let mut host = Host::with_null_backend(two_devices())?;
+            host.devices()?.iter().map(Device::id).collect()
+        };
+        let stream = Stream::open(
+            host,
+            Target::Default,
+            Direction::Output,
+            StreamOptions {
+                callback: Box::new(|_: &mut [[f32; 2]]| {}),
+                reconnect: Reconnect::ToDevices(vec![ids[1].clone(), ids[0].clone()]),
+                ..Default::default()
+            },
+        )?;
+        let fallback_ids = |lost| -> Vec<DeviceId> {
+            stream
+                .0
+                .fallback_devices(lost)
+                .iter()
+                .map(Device::id)
+                .collect()
+        };
+        assert_eq!(fallback_ids(None), vec![ids[0].clone(), ids[1].clone()]);
+        assert_eq!(fallback_ids(Some(&ids[0])), vec![ids[1].clone()]);
+        Ok(())
+    }
+
+    #[test]
+    fn fades_in_after_resuming() {
+        let mixer = mixer(1.0);
+        let mut callback = mixer.callback(1, true);
+        mixer.acquire(1);
+        let mut buffer = vec![[0.0; 2]; FADE_FRAMES / 2];
+        callback(&mut buffer);
+        assert_eq!(buffer[0], [0.0, 0.0]);
+        assert_lt!(buffer[buffer.len() - 1][0], 0.5);
+        callback(&mut buffer);
+        assert_ge!(buffer[0][0], 0.5);
+        callback(&mut buffer);
+        assert_eq!(buffer[0], [1.0, 1.0]);
+
+        mixer.fading_out.store(1, Ordering::Release);
+        callback(&mut buffer);
+        callback(&mut buffer);
+        assert!(mixer.faded_out.load(Ordering::Acquire));
+        callback(&mut buffer);
+        assert_eq!(buffer[0], [0.0, 0.0]);
+    }
+}
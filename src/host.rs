@@ -0,0 +1,407 @@
+use std::time::Duration;
+
+use crate::backend::Backend;
+use crate::device::Device;
+use crate::device_id::{self, DeviceId};
+use crate::error::{Error, Result};
+use crate::file::{self, FileHostOptions};
+use crate::follow;
+use crate::loopback::{self, LoopbackOptions};
+use crate::null::{self, NullHostOptions};
+#[cfg(feature = "native-pipewire")]
+use crate::pipewire::{self, PipeWireOptions};
+use crate::portaudio;
+#[cfg(feature = "native-pulseaudio")]
+use crate::pulseaudio::{self, PulseAudioOptions};
+use crate::stream::{Stream, StreamImpl};
+use crate::stream_options::{Direction, StreamOptions};
+use crate::watch::{DeviceEvent, DeviceWatcher};
+
+/// A host is the entry point to an audio backend (e.g. ALSA or CoreAudio), and gives access to
+/// its devices.
+pub struct Host(HostImpl);
+
+#[derive(Clone)]
+pub enum HostImpl {
+    PortAudio(portaudio::Host),
+    Null(null::Host),
+    #[cfg(feature = "native-pulseaudio")]
+    PulseAudio(pulseaudio::Host),
+    #[cfg(feature = "native-pipewire")]
+    PipeWire(pipewire::Host),
+}
+
+impl Host {
+    /// Creates a host with the default system backend.
+    pub fn with_default_backend() -> Result<Host> {
+        Ok(Host(HostImpl::PortAudio(
+            portaudio::Host::with_default_backend()?,
+        )))
+    }
+
+    /// Creates a host with a specific backend.
+    ///
+    /// Will return [`Error::BackendUnavailable`](crate::Error::BackendUnavailable) if the backend
+    /// support was not compiled. [`Backend::Null`] is created with [`NullHostOptions::default`],
+    /// [`Backend::File`] with [`FileHostOptions::default`], and [`Backend::Loopback`] with
+    /// [`LoopbackOptions::default`].
+    ///
+    /// # Examples
+    /// ```
+    /// # use audiohal::*;
+    /// assert!(Host::with_backend(Backend::Dummy).is_ok(), "The dummy backend should always be available.");
+    /// assert!(Host::with_backend(Backend::Null).is_ok(), "The null backend should always be available.");
+    /// ```
+    pub fn with_backend(backend: Backend) -> Result<Host> {
+        match backend {
+            Backend::Null => Host::with_null_backend(NullHostOptions::default()),
+            Backend::File => Host::with_file_backend(FileHostOptions::default()),
+            Backend::Loopback => Host::with_loopback_backend(LoopbackOptions::default()),
+            #[cfg(feature = "native-pulseaudio")]
+            Backend::PulseAudio => Host::with_pulseaudio_backend(PulseAudioOptions::default()),
+            #[cfg(feature = "native-pipewire")]
+            Backend::PipeWire => Host::with_pipewire_backend(PipeWireOptions::default()),
+            backend => Ok(Host(HostImpl::PortAudio(portaudio::Host::with_backend(
+                backend,
+            )?))),
+        }
+    }
+
+    /// Creates a host of the null backend, whose devices are described by `options`.
+    ///
+    /// The null backend does not need any audio hardware, which makes it suitable for tests.
+    ///
+    /// # Examples
+    /// ```
+    /// # use audiohal::*;
+    /// let mut host = Host::with_null_backend(NullHostOptions {
+    ///     devices: vec![NullDeviceOptions {
+    ///         name: "USB Headset".to_string(),
+    ///         max_input_channels: 1,
+    ///         max_output_channels: 2,
+    ///         ..Default::default()
+    ///     }],
+    ///     clock: Clock::Accelerated(10.0),
+    /// })?;
+    /// assert_eq!(host.default_output_device()?.name(), "USB Headset");
+    /// # Result::Ok(())
+    /// ```
+    pub fn with_null_backend(options: NullHostOptions) -> Result<Host> {
+        Ok(Host(HostImpl::Null(null::Host::new(options)?)))
+    }
+
+    /// Creates a host of the file backend, whose output devices record to WAV files and whose input
+    /// devices play WAV files, as described by `options`.
+    ///
+    /// Like the null backend, the file backend does not need any audio hardware. This makes it
+    /// possible to capture the audio of an application session and compare it against a reference
+    /// recording, or to feed a recording pipeline deterministically.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use audiohal::*;
+    /// let mut host = Host::with_file_backend(FileHostOptions {
+    ///     sinks: vec![FileSinkOptions {
+    ///         path: "session.wav".into(),
+    ///         ..Default::default()
+    ///     }],
+    ///     clock: Clock::Accelerated(10.0),
+    ///     ..Default::default()
+    /// })?;
+    /// let mut stream = host.default_output_device()?.open_outstream(StreamOptions {
+    ///     callback: Box::new(|buffer: &mut [[f32; 2]]| {
+    ///         # buffer;
+    ///     }),
+    ///     ..Default::default()
+    /// })?;
+    /// stream.start()?;
+    /// # Result::Ok(())
+    /// ```
+    pub fn with_file_backend(options: FileHostOptions) -> Result<Host> {
+        Ok(Host(HostImpl::Null(file::new_host(options)?)))
+    }
+
+    /// Creates a host of the loopback backend, with a single device whose output is looped back to
+    /// its input, as described by `options`.
+    ///
+    /// This makes it possible to test round-trip latency and signal integrity without audio
+    /// hardware or cables.
+    ///
+    /// # Examples
+    /// ```
+    /// # use audiohal::*;
+    /// # use std::time::Duration;
+    /// let mut host = Host::with_loopback_backend(LoopbackOptions {
+    ///     delay: Duration::from_millis(20),
+    ///     noise_amplitude: 0.001,
+    ///     ..Default::default()
+    /// })?;
+    /// let mut output = host.default_output_device()?;
+    /// let mut input = host.default_input_device()?;
+    /// # Result::Ok(())
+    /// ```
+    pub fn with_loopback_backend(options: LoopbackOptions) -> Result<Host> {
+        Ok(Host(HostImpl::Null(loopback::new_host(options)?)))
+    }
+
+    /// Creates a host of the native PulseAudio backend, connected to the server described by
+    /// `options`. Requires the `native-pulseaudio` feature.
+    ///
+    /// Will return [`Error::BackendUnavailable`](crate::Error::BackendUnavailable) if the server
+    /// is unreachable.
+    #[cfg(feature = "native-pulseaudio")]
+    pub fn with_pulseaudio_backend(options: PulseAudioOptions) -> Result<Host> {
+        Ok(Host(HostImpl::PulseAudio(pulseaudio::Host::new(options)?)))
+    }
+
+    /// Creates a host of the native PipeWire backend, connected to the daemon described by
+    /// `options`. Requires the `native-pipewire` feature.
+    ///
+    /// Will return [`Error::BackendUnavailable`](crate::Error::BackendUnavailable) if the daemon
+    /// is unreachable.
+    #[cfg(feature = "native-pipewire")]
+    pub fn with_pipewire_backend(options: PipeWireOptions) -> Result<Host> {
+        Ok(Host(HostImpl::PipeWire(pipewire::Host::new(options)?)))
+    }
+
+    /// Returns the host API's descriptive name (e.g. "CoreAudio").
+    pub fn name(&self) -> &str {
+        match &self.0 {
+            HostImpl::PortAudio(host) => host.name(),
+            HostImpl::Null(host) => host.name(),
+            #[cfg(feature = "native-pulseaudio")]
+            HostImpl::PulseAudio(host) => host.name(),
+            #[cfg(feature = "native-pipewire")]
+            HostImpl::PipeWire(host) => host.name(),
+        }
+    }
+
+    /// Returns the host's backend.
+    pub fn backend(&self) -> Backend {
+        match &self.0 {
+            HostImpl::PortAudio(host) => host.backend(),
+            HostImpl::Null(host) => host.backend(),
+            #[cfg(feature = "native-pulseaudio")]
+            HostImpl::PulseAudio(_) => Backend::PulseAudio,
+            #[cfg(feature = "native-pipewire")]
+            HostImpl::PipeWire(_) => Backend::PipeWire,
+        }
+    }
+
+    /// Returns all the devices of this host.
+    pub fn devices(&mut self) -> Result<Vec<Device>> {
+        Ok(match &mut self.0 {
+            HostImpl::PortAudio(host) => host.devices()?.into_iter().map(Device::from).collect(),
+            HostImpl::Null(host) => host.devices().into_iter().map(Device::from).collect(),
+            #[cfg(feature = "native-pulseaudio")]
+            HostImpl::PulseAudio(host) => host.devices()?.into_iter().map(Device::from).collect(),
+            #[cfg(feature = "native-pipewire")]
+            HostImpl::PipeWire(host) => host.devices()?.into_iter().map(Device::from).collect(),
+        })
+    }
+
+    /// Returns the device identified by `id` (see [`Device::id`]).
+    ///
+    /// If no device has that exact identifier, falls back to the device that most likely is the
+    /// same (e.g. the same interface, now listed in another position, or an ALSA device whose card
+    /// number changed). Compare the device's identifier with `id` to tell whether it was an exact
+    /// match. Returns [`Error::NoSuchDevice`](crate::Error::NoSuchDevice) if no device matches.
+    ///
+    /// # Examples
+    /// ```
+    /// # use audiohal::*;
+    /// let mut host = Host::with_null_backend(NullHostOptions::default())?;
+    /// let saved = host.default_output_device()?.id().to_string();
+    /// // The next launch...
+    /// let device = host.device_by_id(&saved.parse()?)?;
+    /// assert_eq!(device.id().to_string(), saved);
+    /// # Result::Ok(())
+    /// ```
+    pub fn device_by_id(&mut self, id: &DeviceId) -> Result<Device> {
+        let mut devices = self.devices()?;
+        let ids: Vec<DeviceId> = devices.iter().map(Device::id).collect();
+        let index = device_id::best_match(id, &ids).ok_or(Error::NoSuchDevice)?;
+        Ok(devices.swap_remove(index))
+    }
+
+    /// Creates and returns the default output device for this host.
+    ///
+    /// This is the recommended device to use for audio playback.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut host = audiohal::Host::with_default_backend()?;
+    /// match host.default_output_device() {
+    ///     Ok(device) => println!("Default output device name is {}.", device.name()),
+    ///     Err(_) => println!("No devices available."),
+    /// };
+    /// # audiohal::Result::Ok(())
+    /// ```
+    ///
+    pub fn default_output_device(&mut self) -> Result<Device> {
+        Ok(match &mut self.0 {
+            HostImpl::PortAudio(host) => host.default_output_device()?.into(),
+            HostImpl::Null(host) => host.default_output_device()?.into(),
+            #[cfg(feature = "native-pulseaudio")]
+            HostImpl::PulseAudio(host) => host.default_output_device()?.into(),
+            #[cfg(feature = "native-pipewire")]
+            HostImpl::PipeWire(host) => host.default_output_device()?.into(),
+        })
+    }
+
+    /// Opens an output stream on the default output device, which moves to the new default output
+    /// device when it changes (e.g. when a headset is plugged in).
+    ///
+    /// On native PulseAudio and PipeWire backends, the sound server moves the stream itself, as
+    /// soon as the default device changes. PortAudio only reads the default device when
+    /// initialized, and cannot be re-initialized while the stream is open, so on PortAudio
+    /// backends the stream only moves when the devices are refreshed (see
+    /// [`Host::refresh_devices`]): refreshing closes the stream, fading it out, re-initializes
+    /// PortAudio, and reopens the stream on the default device with the same `options`, fading in.
+    /// The callback keeps its state, but is not called in between, so playback pauses while
+    /// PortAudio is re-initialized.
+    ///
+    /// Once its device is disconnected, the stream is reopened on the new default device, whatever
+    /// its [`Reconnect`](crate::Reconnect) policy. The default devices of the null, file and
+    /// loopback backends never change.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use audiohal::*;
+    /// let mut host = Host::with_default_backend()?;
+    /// let mut phase = 0.0_f32;
+    /// let mut stream = host.open_default_outstream(StreamOptions {
+    ///     callback: Box::new(move |buffer: &mut [[f32; 2]]| {
+    ///         for frame in buffer {
+    ///             *frame = [phase.sin() * 0.1; 2];
+    ///             phase += 0.05;
+    ///         }
+    ///     }),
+    ///     ..Default::default()
+    /// })?;
+    /// stream.start()?;
+    /// // The user picks another default output device in the system settings.
+    /// host.refresh_devices()?;
+    /// # Result::Ok(())
+    /// ```
+    pub fn open_default_outstream<Frame>(
+        &mut self,
+        options: StreamOptions<Frame>,
+    ) -> Result<Stream<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        match &self.0 {
+            HostImpl::PortAudio(_) => Ok(Stream::new(StreamImpl::Following(follow::Stream::open(
+                Host(self.0.clone()),
+                follow::Target::Default,
+                Direction::Output,
+                options,
+            )?))),
+            _ => self.default_output_device()?.open_outstream(options),
+        }
+    }
+
+    /// Creates and returns the default input device for this host.
+    ///
+    /// This is the recommended device to use for audio capture.
+    pub fn default_input_device(&mut self) -> Result<Device> {
+        Ok(match &mut self.0 {
+            HostImpl::PortAudio(host) => host.default_input_device()?.into(),
+            HostImpl::Null(host) => host.default_input_device()?.into(),
+            #[cfg(feature = "native-pulseaudio")]
+            HostImpl::PulseAudio(host) => host.default_input_device()?.into(),
+            #[cfg(feature = "native-pipewire")]
+            HostImpl::PipeWire(host) => host.default_input_device()?.into(),
+        })
+    }
+
+    /// Updates the devices of this host with those plugged in or unplugged since it was created, or
+    /// last refreshed.
+    ///
+    /// PortAudio lists devices once, when it is initialized: refreshing the host of a PortAudio
+    /// backend re-initializes it for all such hosts, which is only possible once all their streams
+    /// are closed. Devices obtained before keep working if they are still plugged in, and return
+    /// [`Error::NoSuchDevice`](crate::Error::NoSuchDevice) otherwise. Other backends always list
+    /// their current devices, so this does nothing.
+    ///
+    /// Streams opened with [`Host::open_default_outstream`] are closed while refreshing, and
+    /// reopened on the new default output device. So are streams with a
+    /// [`Reconnect`](crate::Reconnect) policy, on their device if still available. Returns
+    /// [`Error::StreamsOpen`](crate::Error::StreamsOpen) if another stream of a PortAudio backend
+    /// is open, without closing any stream.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use audiohal::*;
+    /// let mut host = Host::with_default_backend()?;
+    /// // Plug in a USB headset.
+    /// host.refresh_devices()?;
+    /// for device in host.devices()? {
+    ///     println!("{}", device.name());
+    /// }
+    /// # Result::Ok(())
+    /// ```
+    pub fn refresh_devices(&mut self) -> Result<()> {
+        match &mut self.0 {
+            HostImpl::PortAudio(host) => {
+                let streams = host.clone();
+                follow::while_suspended(|| streams.n_open_streams(), || host.refresh_devices())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Refreshes the devices like [`Host::refresh_devices`], unless any stream is open, including
+    /// those following the default output device.
+    pub(crate) fn refresh_devices_if_idle(&mut self) -> Result<()> {
+        match &mut self.0 {
+            HostImpl::PortAudio(host) => host.refresh_devices(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Polls the devices of this host every `interval` on a background thread, and calls
+    /// `callback` with the devices that were added or removed, and with changes of the default
+    /// devices. Watching stops when the returned [`DeviceWatcher`] is dropped.
+    ///
+    /// PortAudio backends only list the devices plugged or unplugged since they were last refreshed
+    /// (see [`Host::refresh_devices`]). Refreshing re-initializes PortAudio, which probes every
+    /// device again and, with JACK, registers its client anew, so the watcher only refreshes the
+    /// devices every 5 s at most, whatever the `interval`. While streams of a PortAudio backend
+    /// are open, the devices cannot be refreshed, so no change is reported until all are closed.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// # use audiohal::*;
+    /// # use std::time::Duration;
+    /// let host = Host::with_default_backend()?;
+    /// let _watcher = host.watch_devices(Duration::from_secs(1), |event| match event {
+    ///     DeviceEvent::Added(name) => println!("{} was plugged in.", name),
+    ///     DeviceEvent::Removed(name) => println!("{} was unplugged.", name),
+    ///     _ => {}
+    /// })?;
+    /// # Result::Ok(())
+    /// ```
+    pub fn watch_devices(
+        &self,
+        interval: Duration,
+        callback: impl FnMut(DeviceEvent) + Send + 'static,
+    ) -> Result<DeviceWatcher> {
+        DeviceWatcher::spawn(Host(self.0.clone()), interval, callback)
+    }
+
+    pub(crate) fn from_portaudio(host: portaudio::Host) -> Host {
+        Host(HostImpl::PortAudio(host))
+    }
+
+    /// Returns the PortAudio host, for the extensions of its host APIs.
+    #[cfg(feature = "jack")]
+    pub(crate) fn as_portaudio(&self) -> Option<&portaudio::Host> {
+        match &self.0 {
+            HostImpl::PortAudio(host) => Some(host),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Extensions of the JACK backend ([`Backend::Jack`](crate::Backend::Jack)). Requires the `jack`
+//! feature.
+//!
+//! PortAudio registers a single JACK client per process, with one port per channel of every
+//! stream. By default, it connects the ports of a stream to the ports of its device when the
+//! stream starts. With [`JackOptions::autoconnect`] unset, streams start unconnected instead, and
+//! can be patched to any other client with [`StreamExt`]. PortAudio still connects the ports while
+//! starting such a stream, and the connections are only undone once PortAudio returns: the stream
+//! is kept silent until then, so that nothing is played or captured through them.
+//!
+//! When a stream is closed, PortAudio disconnects all of its ports, including the connections made
+//! with [`StreamExt`].
+//!
+//! # Examples
+//! ```no_run
+//! use audiohal::jack::{HostExt as _, JackOptions, StreamExt as _};
+//! # use audiohal::*;
+//!
+//! let mut host = Host::with_jack_options(JackOptions {
+//!     client_name: "synth".to_string(),
+//!     autoconnect: false,
+//! })?;
+//! let mut stream = host
+//!     .default_output_device()?
+//!     .open_outstream(StreamOptions::<[f32; 2]>::default())?;
+//! stream.rename_jack_ports(&["left", "right"])?;
+//! stream.start()?;
+//! stream.connect_jack_port(0, "system:playback_1")?;
+//! stream.connect_jack_port(1, "system:playback_2")?;
+//! # Result::Ok(())
+//! ```
+use crate::error::{Error, Result};
+use crate::host::Host;
+use crate::portaudio;
+use crate::stream::Stream;
+
+/// Configures a host of the JACK backend.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JackOptions {
+    /// The name of the client to register. The server may append a suffix if the name is taken.
+    pub client_name: String,
+    /// Whether the ports of streams are connected to the ports of their device when they start.
+    /// Otherwise, the first callbacks of a stream may be skipped while PortAudio's connections
+    /// are undone.
+    pub autoconnect: bool,
+}
+
+impl Default for JackOptions {
+    fn default() -> JackOptions {
+        JackOptions {
+            client_name: "audiohal".to_string(),
+            autoconnect: true,
+        }
+    }
+}
+
+/// JACK-specific methods of [`Host`].
+pub trait HostExt: Sized {
+    /// Creates a host of the JACK backend, as described by `options`.
+    ///
+    /// All JACK hosts of the process share the same client, which is registered when the first
+    /// one is created: while any other host is alive, `options.client_name` must be the name it
+    /// was registered with, or [`Error::Invalid`] is returned. Returns
+    /// [`Error::BackendUnavailable`] if no JACK server is running.
+    fn with_jack_options(options: JackOptions) -> Result<Self>;
+
+    /// Returns the name the server gave the client.
+    fn jack_client_name(&self) -> Result<String>;
+}
+
+impl HostExt for Host {
+    fn with_jack_options(options: JackOptions) -> Result<Host> {
+        Ok(Host::from_portaudio(portaudio::Host::with_jack_options(
+            &options.client_name,
+            options.autoconnect,
+        )?))
+    }
+
+    fn jack_client_name(&self) -> Result<String> {
+        self.as_portaudio()
+            .ok_or(Error::WrongBackend)?
+            .jack_client_name()
+    }
+}
+
+/// JACK-specific methods of [`Stream`].
+///
+/// Channels are numbered from 0. Methods return [`Error::IncompatibleNChannels`] for channels the
+/// stream does not have, [`Error::NoSuchDevice`] for ports that do not exist, and
+/// [`Error::WrongBackend`] for streams of other backends.
+pub trait StreamExt {
+    /// Returns the full names (i.e. `client:port`) of the stream's ports, one per channel.
+    fn jack_ports(&self) -> Result<Vec<String>>;
+
+    /// Renames the stream's ports, one name per channel. Names are short names (i.e. without the
+    /// `client:` prefix).
+    fn rename_jack_ports(&mut self, names: &[&str]) -> Result<()>;
+
+    /// Returns the full names of the ports connected to the channel's port.
+    fn jack_connections(&self, channel: usize) -> Result<Vec<String>>;
+
+    /// Connects the channel's port to `port`, the full name of another client's port. Does nothing
+    /// if they are already connected.
+    ///
+    /// Connections made before the stream starts are kept, in addition to those made by
+    /// [`JackOptions::autoconnect`]. All connections are removed when the stream is closed.
+    fn connect_jack_port(&mut self, channel: usize, port: &str) -> Result<()>;
+
+    /// Disconnects the channel's port from `port`. Does nothing if they are not connected.
+    fn disconnect_jack_port(&mut self, channel: usize, port: &str) -> Result<()>;
+}
+
+impl<Frame> StreamExt for Stream<Frame> {
+    fn jack_ports(&self) -> Result<Vec<String>> {
+        Ok(self
+            .as_portaudio()
+            .ok_or(Error::WrongBackend)?
+            .jack_ports()?
+            .names()
+            .to_vec())
+    }
+
+    fn rename_jack_ports(&mut self, names: &[&str]) -> Result<()> {
+        self.as_portaudio_mut()
+            .ok_or(Error::WrongBackend)?
+            .jack_ports_mut()?
+            .rename(names)
+    }
+
+    fn jack_connections(&self, channel: usize) -> Result<Vec<String>> {
+        self.as_portaudio()
+            .ok_or(Error::WrongBackend)?
+            .jack_ports()?
+            .connections(channel)
+    }
+
+    fn connect_jack_port(&mut self, channel: usize, port: &str) -> Result<()> {
+        self.as_portaudio()
+            .ok_or(Error::WrongBackend)?
+            .jack_ports()?
+            .connect(channel, port)
+    }
+
+    fn disconnect_jack_port(&mut self, channel: usize, port: &str) -> Result<()> {
+        self.as_portaudio()
+            .ok_or(Error::WrongBackend)?
+            .jack_ports()?
+            .disconnect(channel, port)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NullHostOptions, StreamOptions};
+
+    #[test]
+    fn rejects_other_backends() -> Result<()> {
+        let mut host = Host::with_null_backend(NullHostOptions::default())?;
+        assert_eq!(host.jack_client_name(), Err(Error::WrongBackend));
+        let mut stream = host
+            .default_output_device()?
+            .open_outstream(StreamOptions::<[f32; 2]>::default())?;
+        assert_eq!(stream.jack_ports(), Err(Error::WrongBackend));
+        assert_eq!(
+            stream.connect_jack_port(0, "system:playback_1"),
+            Err(Error::WrongBackend)
+        );
+        Ok(())
+    }
+}
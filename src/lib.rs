@@ -11,18 +11,70 @@ extern crate more_asserts;
 #[macro_use]
 extern crate galvanic_assert;
 
+#[cfg(all(feature = "alsa", target_os = "linux"))]
+pub mod alsa;
+#[cfg(feature = "async")]
+mod async_stream;
 mod backend;
+mod buffered;
+mod convert;
+mod device;
+mod device_id;
 mod error;
+mod file;
+mod follow;
+mod host;
+#[cfg(feature = "jack")]
+pub mod jack;
+mod loopback;
+mod offline;
+#[cfg(feature = "oss")]
+pub mod oss;
+mod reblock;
+mod ring_buffer;
+mod stream;
 mod stream_options;
+#[cfg(all(
+    test,
+    any(
+        feature = "native-pulseaudio",
+        feature = "native-pipewire",
+        feature = "jack",
+        feature = "oss"
+    )
+))]
+mod test_daemon;
+mod version;
+mod watch;
+mod wav;
 
+mod null;
+#[cfg(feature = "native-pipewire")]
+mod pipewire;
 mod portaudio;
+#[cfg(feature = "native-pulseaudio")]
+mod pulseaudio;
 
 // Exporting public types.
+#[cfg(feature = "async")]
+pub use async_stream::{InputSource, OutputSink};
 pub use backend::Backend;
+pub use buffered::{Consumer, Producer};
+pub use device::Device;
+pub use device_id::DeviceId;
 pub use error::{Error, Result};
-pub use stream_options::{Callback, Format, SampleRate, StreamOptions};
-
-// Exporting backend types.
-pub use portaudio::Device;
-pub use portaudio::Host;
-pub use portaudio::Stream;
+pub use file::{FileHostOptions, FileSinkOptions, FileSourceOptions};
+pub use host::Host;
+pub use loopback::LoopbackOptions;
+pub use null::{Clock, NullDeviceOptions, NullHostOptions};
+pub use offline::OfflineStream;
+#[cfg(feature = "native-pipewire")]
+pub use pipewire::PipeWireOptions;
+#[cfg(feature = "native-pulseaudio")]
+pub use pulseaudio::PulseAudioOptions;
+pub use stream::{Stream, StreamState};
+pub use stream_options::{
+    Callback, Direction, Format, Latency, Reconnect, SampleRate, StreamFlags, StreamOptions,
+};
+pub use version::{library_version, LibraryVersion};
+pub use watch::{DeviceEvent, DeviceWatcher};
@@ -0,0 +1,492 @@
+//! A backend with a single virtual device, whose output is looped back to its input.
+//!
+//! Built on top of the null backend. Output streams write to a shared line, which input streams
+//! read from after a configurable delay. The line can be degraded with noise, dropouts and clock
+//! drift, to test how an application copes with a real (imperfect) signal path.
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::backend::Backend;
+use crate::convert;
+use crate::error::{Error, Result};
+use crate::null::{
+    self, Clock, DeviceKind, HostState, NullDeviceOptions, SampleSpec, VirtualDevice,
+};
+use crate::ring_buffer::{self, Reader, Writer};
+
+/// Configures a host of the loopback backend.
+///
+/// Output and input streams of the loopback device must be opened with the same sample rate. They
+/// may use different formats and numbers of channels: missing channels are silent, and extra
+/// channels are dropped. Only one output and one input stream can be open at a time; opening
+/// another one fails with [`Error::DeviceBusy`](crate::Error::DeviceBusy).
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopbackOptions {
+    /// The name of the loopback device.
+    pub name: String,
+    /// The number of channels of the line (i.e. the maximum number of channels of streams).
+    pub n_channels: i32,
+    pub sample_rate: i32,
+    /// The time between a frame being played by an output stream and it being captured by an
+    /// input stream, not including the streams' own buffering.
+    pub delay: Duration,
+    /// The peak amplitude of the white noise added to the line, in units of full scale.
+    pub noise_amplitude: f64,
+    /// The probability of any output buffer being lost (i.e. replaced with silence).
+    pub dropout_probability: f64,
+    /// How much faster the input device's clock runs than the output device's clock, in parts
+    /// per million. Negative values make it run slower.
+    pub clock_drift_ppm: f64,
+    /// Seeds the noise and dropouts, so that runs are reproducible.
+    pub seed: u64,
+    pub clock: Clock,
+}
+
+impl Default for LoopbackOptions {
+    fn default() -> LoopbackOptions {
+        LoopbackOptions {
+            name: "Loopback".to_string(),
+            n_channels: 2,
+            sample_rate: 48_000,
+            delay: Duration::default(),
+            noise_amplitude: 0.0,
+            dropout_probability: 0.0,
+            clock_drift_ppm: 0.0,
+            seed: 0,
+            clock: Clock::default(),
+        }
+    }
+}
+
+impl LoopbackOptions {
+    fn validate(&self) -> Result<()> {
+        self.clock.validate()?;
+        if self.n_channels <= 0
+            || self.sample_rate <= 0
+            || !(self.noise_amplitude >= 0.0 && self.noise_amplitude.is_finite())
+            || !(0.0..=1.0).contains(&self.dropout_probability)
+            || !self.clock_drift_ppm.is_finite()
+            || self.clock_drift_ppm <= -1e6
+        {
+            return Err(Error::Invalid);
+        }
+        Ok(())
+    }
+}
+
+pub fn new_host(options: LoopbackOptions) -> Result<null::Host> {
+    options.validate()?;
+    let device_options = NullDeviceOptions {
+        name: options.name.clone(),
+        max_input_channels: options.n_channels,
+        max_output_channels: options.n_channels,
+        sample_rates: vec![options.sample_rate],
+        default_sample_rate: options.sample_rate,
+        ..Default::default()
+    };
+    let clock = options.clock;
+    Ok(null::Host::with_state(HostState {
+        name: "Loopback",
+        backend: Backend::Loopback,
+        clock,
+        devices: vec![VirtualDevice {
+            options: device_options,
+            kind: DeviceKind::Loopback(Arc::new(Line::new(options))),
+        }],
+    }))
+}
+
+/// The signal path between the output and the input of the loopback device.
+///
+/// Samples travel through a lock-free ring buffer, whose halves are handed out to one output and
+/// one input stream at a time (see [`Line::output`] and [`Line::input`]), so that the streams'
+/// threads never wait on each other.
+pub struct Line {
+    options: LoopbackOptions,
+    writer: Mutex<Option<Writer<f32>>>,
+    reader: Mutex<Option<Reader<f32>>>,
+    /// Lets a new output stream ask the input side to discard what a previous output stream left
+    /// on the line (see [`Resync`]). Only set to `REQUESTED` with `reader` locked.
+    resync: AtomicU8,
+}
+
+/// The states of [`Line::resync`].
+struct Resync;
+
+impl Resync {
+    /// The output stream (if any) is writing to the line.
+    const WRITING: u8 = 0;
+    /// A new output stream waits for the line to be cleared, and drops its samples meanwhile.
+    const REQUESTED: u8 = 1;
+    /// The line was cleared for the new output stream.
+    const CLEARED: u8 = 2;
+}
+
+/// The output side of a [`Line`], owned by an output stream's endpoint.
+pub struct LineWriter {
+    line: Arc<Line>,
+    writer: Option<Writer<f32>>,
+    /// Whether the line was cleared and filled with the delay for this stream.
+    primed: bool,
+    random: XorShift,
+    /// Interleaved samples of the buffer being written, in the line's layout.
+    scratch: Vec<f32>,
+}
+
+/// The input side of a [`Line`], owned by an input stream's endpoint.
+pub struct LineReader {
+    line: Arc<Line>,
+    reader: Option<Reader<f32>>,
+    random: XorShift,
+    /// Interleaved samples of the buffer being read, in the line's layout.
+    scratch: Vec<f32>,
+}
+
+impl Line {
+    fn new(options: LoopbackOptions) -> Line {
+        // Keep at most a second on top of the delay, e.g. while no input stream reads the line.
+        let capacity =
+            (delay_frames(&options) + options.sample_rate as usize) * options.n_channels as usize;
+        let (writer, reader) = ring_buffer::new(capacity, 0.0);
+        Line {
+            options,
+            writer: Mutex::new(Some(writer)),
+            reader: Mutex::new(Some(reader)),
+            resync: AtomicU8::new(Resync::CLEARED),
+        }
+    }
+
+    /// How much faster the clock of input (or output) streams runs, relative to the sample rate.
+    pub fn clock_rate(&self, is_input: bool) -> f64 {
+        if is_input {
+            1.0 + self.options.clock_drift_ppm * 1e-6
+        } else {
+            1.0
+        }
+    }
+
+    fn n_channels(&self) -> usize {
+        self.options.n_channels as usize
+    }
+
+    /// Takes the output side of the line, for a new output stream. Returns
+    /// [`Error::DeviceBusy`] if another output stream holds it.
+    ///
+    /// What a previous output stream left on the line is discarded: right away if no input stream
+    /// is open, and by the input stream's next read otherwise.
+    pub fn output(self: &Arc<Line>) -> Result<LineWriter> {
+        let writer = self
+            .writer
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or(Error::DeviceBusy)?;
+        let mut reader = self.reader.lock().unwrap();
+        match reader.as_mut() {
+            Some(reader) => {
+                clear(reader);
+                self.resync.store(Resync::CLEARED, Ordering::Release);
+            }
+            None => self.resync.store(Resync::REQUESTED, Ordering::Release),
+        }
+        Ok(LineWriter {
+            line: Arc::clone(self),
+            writer: Some(writer),
+            primed: false,
+            random: XorShift::new(self.options.seed),
+            scratch: Vec::new(),
+        })
+    }
+
+    /// Takes the input side of the line, for a new input stream. Returns [`Error::DeviceBusy`]
+    /// if another input stream holds it.
+    pub fn input(self: &Arc<Line>) -> Result<LineReader> {
+        let reader = self
+            .reader
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or(Error::DeviceBusy)?;
+        Ok(LineReader {
+            line: Arc::clone(self),
+            reader: Some(reader),
+            // Use another sequence than the output side's.
+            random: XorShift::new(!self.options.seed),
+            scratch: Vec::new(),
+        })
+    }
+}
+
+fn delay_frames(options: &LoopbackOptions) -> usize {
+    (options.delay.as_secs_f64() * f64::from(options.sample_rate)).round() as usize
+}
+
+/// Discards everything on the line.
+fn clear(reader: &mut Reader<f32>) {
+    let mut discarded = [0.0; 64];
+    while reader.pop_slice(&mut discarded) > 0 {}
+}
+
+impl LineWriter {
+    /// Appends the output samples of a stream with `spec`.
+    ///
+    /// Until the line was cleared for this stream, samples are dropped. Then the line starts with
+    /// the configured delay of silence, from the first frame actually played.
+    pub fn write(&mut self, spec: SampleSpec, samples: &[u8]) {
+        let line = &self.line;
+        let writer = self.writer.as_mut().unwrap();
+        if !self.primed {
+            if line.resync.load(Ordering::Acquire) != Resync::CLEARED {
+                return;
+            }
+            line.resync.store(Resync::WRITING, Ordering::Relaxed);
+            self.primed = true;
+            let silence = [0.0; 64];
+            let mut n_silent = delay_frames(&line.options) * line.n_channels();
+            while n_silent > 0 {
+                n_silent -= writer.push_slice(&silence[..n_silent.min(silence.len())]);
+            }
+        }
+        let dropped = self.random.next_f64() < line.options.dropout_probability;
+        let sample_size = spec.format.sample_size();
+        self.scratch.clear();
+        for frame in samples.chunks_exact(sample_size * spec.n_channels as usize) {
+            for channel in 0..line.n_channels() {
+                let value = match frame.get(channel * sample_size..(channel + 1) * sample_size) {
+                    Some(sample) if !dropped => convert::decode(spec.format, sample) as f32,
+                    _ => 0.0,
+                };
+                self.scratch.push(value);
+            }
+        }
+        // Whatever does not fit is lost. Only write whole frames, so that channels stay aligned.
+        let n_samples =
+            (writer.free_len() / line.n_channels() * line.n_channels()).min(self.scratch.len());
+        writer.push_slice(&self.scratch[..n_samples]);
+    }
+}
+
+impl Drop for LineWriter {
+    fn drop(&mut self) {
+        *self.line.writer.lock().unwrap() = self.writer.take();
+    }
+}
+
+impl LineReader {
+    /// Reads the input samples of a stream with `spec`. Frames that were not written yet are
+    /// silent.
+    pub fn read(&mut self, spec: SampleSpec, samples: &mut [u8]) {
+        let line = &self.line;
+        let reader = self.reader.as_mut().unwrap();
+        if line.resync.load(Ordering::Acquire) == Resync::REQUESTED {
+            // The output stream does not write until the line was cleared.
+            clear(reader);
+            line.resync.store(Resync::CLEARED, Ordering::Release);
+        }
+        let n_channels = line.n_channels();
+        let sample_size = spec.format.sample_size();
+        let n_frames = samples.len() / (sample_size * spec.n_channels as usize);
+        // Only read whole frames.
+        let available = reader.len() / n_channels * n_channels;
+        self.scratch.resize(n_frames * n_channels, 0.0);
+        let n_read = reader.pop_slice(&mut self.scratch[..available.min(n_frames * n_channels)]);
+        for sample in &mut self.scratch[n_read..] {
+            *sample = 0.0;
+        }
+        let noise_amplitude = line.options.noise_amplitude;
+        let frames = samples.chunks_exact_mut(sample_size * spec.n_channels as usize);
+        for (frame, line_frame) in frames.zip(self.scratch.chunks_exact(n_channels)) {
+            for (channel, &line_sample) in line_frame.iter().enumerate() {
+                let mut value = f64::from(line_sample);
+                if noise_amplitude > 0.0 {
+                    value += (self.random.next_f64() * 2.0 - 1.0) * noise_amplitude;
+                }
+                if let Some(sample) =
+                    frame.get_mut(channel * sample_size..(channel + 1) * sample_size)
+                {
+                    convert::encode(spec.format, value, sample);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for LineReader {
+    fn drop(&mut self) {
+        let mut parked = self.line.reader.lock().unwrap();
+        let mut reader = self.reader.take().unwrap();
+        // Nobody else would clear the line for a waiting output stream.
+        if self.line.resync.load(Ordering::Acquire) == Resync::REQUESTED {
+            clear(&mut reader);
+            self.line.resync.store(Resync::CLEARED, Ordering::Release);
+        }
+        *parked = Some(reader);
+    }
+}
+
+/// A small deterministic pseudo-random number generator (xorshift64*).
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> XorShift {
+        // The state must never be zero.
+        XorShift((seed ^ 0x9e37_79b9_7f4a_7c15).max(1))
+    }
+
+    /// Returns a number in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let value = self.0.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        (value >> 11) as f64 / (1_u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Format, Host, StreamOptions};
+    use std::sync::mpsc;
+
+    const MONO_F32: SampleSpec = SampleSpec {
+        format: Format::F32,
+        n_channels: 1,
+        sample_rate: 1000,
+    };
+
+    fn line_with(options: LoopbackOptions) -> Arc<Line> {
+        Arc::new(Line::new(LoopbackOptions {
+            n_channels: 1,
+            sample_rate: 1000,
+            ..options
+        }))
+    }
+
+    fn write_f32(writer: &mut LineWriter, samples: &[f32]) {
+        let bytes: Vec<u8> = samples
+            .iter()
+            .flat_map(|s| s.to_ne_bytes().to_vec())
+            .collect();
+        writer.write(MONO_F32, &bytes);
+    }
+
+    fn read_f32(reader: &mut LineReader, n_samples: usize) -> Vec<f32> {
+        let mut bytes = vec![0; n_samples * 4];
+        reader.read(MONO_F32, &mut bytes);
+        bytes
+            .chunks_exact(4)
+            .map(|sample| f32::from_ne_bytes([sample[0], sample[1], sample[2], sample[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn delays_output() -> Result<()> {
+        let line = line_with(LoopbackOptions {
+            delay: Duration::from_millis(5),
+            ..Default::default()
+        });
+        let mut writer = line.output()?;
+        let mut reader = line.input()?;
+        write_f32(&mut writer, &[1.0, 0.5]);
+        assert_eq!(
+            read_f32(&mut reader, 8),
+            vec![0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.5, 0.0]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn discards_previous_output_stream() -> Result<()> {
+        let line = line_with(LoopbackOptions::default());
+        let mut reader = line.input()?;
+        let mut writer = line.output()?;
+        write_f32(&mut writer, &[1.0; 4]);
+        drop(writer);
+        let mut writer = line.output()?;
+        // Until the reader cleared the line, the new stream's output is dropped.
+        write_f32(&mut writer, &[0.5; 4]);
+        assert_eq!(read_f32(&mut reader, 2), vec![0.0; 2]);
+        write_f32(&mut writer, &[0.25; 2]);
+        assert_eq!(read_f32(&mut reader, 4), vec![0.25, 0.25, 0.0, 0.0]);
+        Ok(())
+    }
+
+    #[test]
+    fn lends_each_side_to_one_stream() -> Result<()> {
+        let line = line_with(LoopbackOptions::default());
+        let writer = line.output()?;
+        assert_eq!(line.output().err(), Some(Error::DeviceBusy));
+        drop(writer);
+        assert!(line.output().is_ok());
+        let _reader = line.input()?;
+        assert_eq!(line.input().err(), Some(Error::DeviceBusy));
+        Ok(())
+    }
+
+    #[test]
+    fn injects_noise() -> Result<()> {
+        let line = line_with(LoopbackOptions {
+            noise_amplitude: 0.1,
+            ..Default::default()
+        });
+        let samples = read_f32(&mut line.input()?, 100);
+        assert!(samples.iter().all(|sample| sample.abs() <= 0.1));
+        assert!(samples.iter().any(|&sample| sample != 0.0));
+        Ok(())
+    }
+
+    #[test]
+    fn drops_buffers() -> Result<()> {
+        let line = line_with(LoopbackOptions {
+            dropout_probability: 1.0,
+            ..Default::default()
+        });
+        let mut writer = line.output()?;
+        write_f32(&mut writer, &[1.0; 4]);
+        assert_eq!(read_f32(&mut line.input()?, 4), vec![0.0; 4]);
+        Ok(())
+    }
+
+    #[test]
+    fn drifts_input_clock() {
+        let line = line_with(LoopbackOptions {
+            clock_drift_ppm: 100.0,
+            ..Default::default()
+        });
+        assert_eq!(line.clock_rate(false), 1.0);
+        assert_lt!((line.clock_rate(true) - 1.0001).abs(), 1e-12);
+    }
+
+    #[test]
+    fn loops_output_stream_to_input_stream() -> Result<()> {
+        let mut host = Host::with_loopback_backend(LoopbackOptions {
+            clock: Clock::Accelerated(10.0),
+            ..Default::default()
+        })?;
+        let mut device = host.default_output_device()?;
+        let mut outstream = device.open_outstream(StreamOptions {
+            callback: Box::new(|buffer: &mut [[f32; 2]]| {
+                for frame in buffer.iter_mut() {
+                    *frame = [0.5, -0.5];
+                }
+            }),
+            ..Default::default()
+        })?;
+        let (sender, receiver) = mpsc::channel();
+        let mut instream = host.default_input_device()?.open_instream(StreamOptions {
+            callback: Box::new(move |buffer: &mut [[i16; 2]]| {
+                let _ = sender.send(buffer[buffer.len() - 1]);
+            }),
+            format: Format::I16,
+            ..Default::default()
+        })?;
+        outstream.start()?;
+        instream.start()?;
+        let looped_back = (0..100)
+            .any(|_| receiver.recv_timeout(Duration::from_secs(5)) == Ok([0x4000, -0x4000]));
+        assert!(looped_back);
+        Ok(())
+    }
+}
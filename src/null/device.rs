@@ -0,0 +1,250 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::null::endpoint::SampleSpec;
+use crate::null::host::HostState;
+use crate::null::stream::{Stream, StreamConfig};
+use crate::null::{NullDeviceOptions, DEFAULT_FRAMES_PER_BUFFER};
+use crate::reblock;
+use crate::stream_options::{check_frame_size, Direction, StreamOptions};
+use crate::{DeviceId, Latency, SampleRate};
+
+/// A fake device of the null backend.
+pub struct Device {
+    host: Arc<HostState>,
+    index: usize,
+}
+
+impl Device {
+    pub fn new(host: Arc<HostState>, index: usize) -> Device {
+        debug_assert_lt!(index, host.devices.len());
+        Device { host, index }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.options().name
+    }
+
+    pub fn id(&self) -> DeviceId {
+        let direction_of = |options: &NullDeviceOptions| {
+            Direction::of_device(options.max_input_channels, options.max_output_channels)
+        };
+        let options = self.options();
+        let direction = direction_of(options);
+        let occurrence = self.host.devices[..self.index]
+            .iter()
+            .filter(|other| {
+                other.options.name == options.name && direction_of(&other.options) == direction
+            })
+            .count();
+        DeviceId {
+            backend: self.host.backend,
+            name: options.name.clone(),
+            occurrence,
+            direction,
+        }
+    }
+
+    pub fn open_outstream<Frame>(&mut self, options: StreamOptions<Frame>) -> Result<Stream<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        self.open(options, Direction::Output)
+    }
+
+    pub fn open_instream<Frame>(&mut self, options: StreamOptions<Frame>) -> Result<Stream<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        self.open(options, Direction::Input)
+    }
+
+    fn options(&self) -> &NullDeviceOptions {
+        &self.host.devices[self.index].options
+    }
+
+    fn open<Frame>(
+        &self,
+        options: StreamOptions<Frame>,
+        direction: Direction,
+    ) -> Result<Stream<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        let config = self.stream_config(&options, direction)?;
+        let callback = reblock::wrap(options.callback, options.block_size, direction)?;
+        let endpoint = self.host.devices[self.index]
+            .kind
+            .open_endpoint(direction, config.spec)?;
+        Ok(Stream::new(callback, config, endpoint))
+    }
+
+    /// Validates `options` against the device, and resolves them to a stream configuration.
+    fn stream_config<Frame>(
+        &self,
+        options: &StreamOptions<Frame>,
+        direction: Direction,
+    ) -> Result<StreamConfig> {
+        let device = self.options();
+        options
+            .flags
+            .validate(direction, options.frames_per_buffer)?;
+        let max_channels = match direction {
+            Direction::Input => device.max_input_channels,
+            _ => device.max_output_channels,
+        };
+        if options.n_channels <= 0 || options.n_channels > max_channels {
+            return Err(Error::IncompatibleNChannels);
+        }
+        if !device.formats.contains(&options.format) {
+            return Err(Error::IncompatibleFormat(options.format));
+        }
+        check_frame_size::<Frame>(options.format, options.n_channels)?;
+        let sample_rate = match options.sample_rate {
+            SampleRate::Exact(rate) if device.sample_rates.contains(&rate) => rate,
+            SampleRate::Exact(_) => return Err(Error::IncompatibleSampleRate),
+            SampleRate::NearestTo(rate) => *device
+                .sample_rates
+                .iter()
+                .min_by_key(|&&supported| (i64::from(supported) - i64::from(rate)).abs())
+                .ok_or(Error::IncompatibleSampleRate)?,
+            SampleRate::DeviceDefault => device.default_sample_rate,
+            _ => panic!("Non-exhaustive sample rate."),
+        };
+        let frames_per_buffer = options
+            .frames_per_buffer
+            .unwrap_or(DEFAULT_FRAMES_PER_BUFFER);
+        if frames_per_buffer <= 0 {
+            return Err(Error::InvalidFramesPerBuffer);
+        }
+        let buffer_duration =
+            Duration::from_secs_f64(f64::from(frames_per_buffer) / f64::from(sample_rate));
+        let latency = match options.latency {
+            Latency::Low => buffer_duration,
+            Latency::High => buffer_duration * 4,
+            Latency::Exact(duration) => duration,
+            _ => panic!("Non-exhaustive latency."),
+        } + options
+            .block_size
+            .map_or(Duration::default(), |block_size| {
+                reblock::added_latency(block_size, sample_rate)
+            });
+        Ok(StreamConfig {
+            direction,
+            spec: SampleSpec {
+                format: options.format,
+                n_channels: options.n_channels,
+                sample_rate,
+            },
+            frames_per_buffer: frames_per_buffer as usize,
+            period: buffer_duration.div_f64(
+                self.host.clock.speed() * self.host.devices[self.index].kind.clock_rate(direction),
+            ),
+            latency,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::null::NullHostOptions;
+    use crate::Format;
+
+    fn null_host(options: NullHostOptions) -> Arc<HostState> {
+        Arc::new(HostState::null(options))
+    }
+
+    fn open_with(options: StreamOptions<[f32; 2]>) -> Result<Stream<[f32; 2]>> {
+        let mut device = Device::new(null_host(NullHostOptions::default()), 0);
+        device.open_outstream(options)
+    }
+
+    #[test]
+    fn errors_if_unsupported_n_channels() {
+        assert_eq!(
+            open_with(StreamOptions {
+                n_channels: 3,
+                ..Default::default()
+            })
+            .err(),
+            Some(Error::IncompatibleNChannels)
+        );
+    }
+
+    #[test]
+    fn errors_if_unsupported_format() {
+        let mut host = NullHostOptions::default();
+        host.devices[0].formats = vec![Format::I16];
+        let mut device = Device::new(null_host(host), 0);
+        assert_eq!(
+            device
+                .open_outstream(StreamOptions::<[f32; 2]>::default())
+                .err(),
+            Some(Error::IncompatibleFormat(Format::F32))
+        );
+    }
+
+    #[test]
+    fn errors_if_frame_size_mismatch() {
+        assert_eq!(
+            open_with(StreamOptions {
+                format: Format::I16,
+                ..Default::default()
+            })
+            .err(),
+            Some(Error::InvalidFrameSize {
+                expected: 4,
+                actual: 8
+            })
+        );
+    }
+
+    #[test]
+    fn resolves_sample_rates() -> Result<()> {
+        let device = Device::new(null_host(NullHostOptions::default()), 0);
+        let resolve = |sample_rate| {
+            device
+                .stream_config(
+                    &StreamOptions::<[f32; 2]> {
+                        sample_rate,
+                        frames_per_buffer: Some(48),
+                        latency: Latency::Low,
+                        ..Default::default()
+                    },
+                    Direction::Output,
+                )
+                .map(|config| config.latency)
+        };
+        assert_eq!(
+            resolve(SampleRate::Exact(48_000))?,
+            Duration::from_millis(1)
+        );
+        assert_eq!(
+            resolve(SampleRate::DeviceDefault)?,
+            Duration::from_millis(1)
+        );
+        assert_eq!(
+            resolve(SampleRate::NearestTo(47_000))?,
+            Duration::from_millis(1)
+        );
+        assert_eq!(
+            resolve(SampleRate::Exact(96_000)),
+            Err(Error::IncompatibleSampleRate)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn errors_if_invalid_frames_per_buffer() {
+        assert_eq!(
+            open_with(StreamOptions {
+                frames_per_buffer: Some(0),
+                ..Default::default()
+            })
+            .err(),
+            Some(Error::InvalidFramesPerBuffer)
+        );
+    }
+}
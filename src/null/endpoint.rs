@@ -0,0 +1,173 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::convert;
+use crate::error::{Error, Result};
+use crate::loopback::{Line, LineWriter};
+use crate::stream_options::{Direction, Format};
+use crate::wav::{WavReader, WavSpec, WavWriter};
+
+/// What the streams of a virtual device do with their samples.
+pub enum DeviceKind {
+    /// Output is discarded, and input is silent.
+    Null,
+    /// Output is recorded to a WAV file, which is created when the stream is opened.
+    FileSink(PathBuf),
+    /// Input is read from a WAV file, which is opened when the stream is opened.
+    FileSource {
+        path: PathBuf,
+        /// Whether to restart from the beginning of the file at its end, instead of ending the
+        /// stream.
+        looping: bool,
+    },
+    /// Output is written to a line, which input is read from.
+    Loopback(Arc<Line>),
+}
+
+/// The sample layout of a stream, as resolved by its device.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampleSpec {
+    pub format: Format,
+    pub n_channels: i32,
+    pub sample_rate: i32,
+}
+
+/// Exchanges the samples of a stream with the outside world, as interleaved native-endian bytes in
+/// the stream's format.
+pub enum Endpoint {
+    /// Output is discarded, and input is silent.
+    Null,
+    /// Receives every output buffer, once rendered by the stream callback.
+    Sink(Box<dyn SampleSink>),
+    /// Called to fill every input buffer, before it is passed to the stream callback. Returns
+    /// false once there is no more input, after which the stream ends. An error ends the stream
+    /// too.
+    Source(SampleSource),
+}
+
+/// Receives the output of a stream.
+pub trait SampleSink: Send {
+    /// Called with every output buffer. An error ends the stream.
+    fn write(&mut self, samples: &[u8]) -> Result<()>;
+
+    /// Called once when the stream is closed, after the last buffer.
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek + Send> SampleSink for WavWriter<W> {
+    fn write(&mut self, samples: &[u8]) -> Result<()> {
+        self.write_samples(samples)
+            .map_err(|error| Error::Io(error.kind()))
+    }
+
+    /// Completes the file's header, so that errors are reported instead of dropped.
+    fn finish(&mut self) -> Result<()> {
+        self.finalize().map_err(|error| Error::Io(error.kind()))
+    }
+}
+
+struct LoopbackSink {
+    writer: LineWriter,
+    spec: SampleSpec,
+}
+
+impl SampleSink for LoopbackSink {
+    fn write(&mut self, samples: &[u8]) -> Result<()> {
+        self.writer.write(self.spec, samples);
+        Ok(())
+    }
+}
+
+pub type SampleSource = Box<dyn FnMut(&mut [u8]) -> Result<bool> + Send>;
+
+impl DeviceKind {
+    pub fn open_endpoint(&self, direction: Direction, spec: SampleSpec) -> Result<Endpoint> {
+        Ok(match self {
+            DeviceKind::Null => Endpoint::Null,
+            DeviceKind::FileSink(path) => {
+                debug_assert!(direction == Direction::Output);
+                let file = File::create(path).map_err(|error| Error::Io(error.kind()))?;
+                let writer = WavWriter::new(BufWriter::new(file), spec.into())
+                    .map_err(|error| Error::Io(error.kind()))?;
+                Endpoint::Sink(Box::new(writer))
+            }
+            DeviceKind::FileSource { path, looping } => {
+                debug_assert!(direction == Direction::Input);
+                let mut reader = open_wav(path)?;
+                let file_format = reader.spec().format;
+                if i32::from(reader.spec().n_channels) != spec.n_channels {
+                    return Err(Error::IncompatibleNChannels);
+                }
+                let looping = *looping && !reader.is_empty();
+                let mut file_samples = Vec::new();
+                Endpoint::Source(Box::new(move |samples| {
+                    let n_samples = samples.len() / spec.format.sample_size();
+                    file_samples.resize(n_samples * file_format.sample_size(), 0);
+                    let mut n_read = 0;
+                    let mut more = true;
+                    while n_read < file_samples.len() && more {
+                        let len = reader
+                            .read_samples(&mut file_samples[n_read..])
+                            .map_err(|error| Error::Io(error.kind()))?;
+                        if len == 0 && looping {
+                            reader.rewind().map_err(|error| Error::Io(error.kind()))?;
+                        } else {
+                            more = len > 0;
+                            n_read += len;
+                        }
+                    }
+                    let n_converted =
+                        n_read / file_format.sample_size() * spec.format.sample_size();
+                    convert::convert(
+                        file_format,
+                        &file_samples[..n_read],
+                        spec.format,
+                        &mut samples[..n_converted],
+                    );
+                    convert::fill_silence(spec.format, &mut samples[n_converted..]);
+                    Ok(more)
+                }))
+            }
+            DeviceKind::Loopback(line) => {
+                if direction == Direction::Input {
+                    let mut reader = line.input()?;
+                    Endpoint::Source(Box::new(move |samples| {
+                        reader.read(spec, samples);
+                        Ok(true)
+                    }))
+                } else {
+                    let writer = line.output()?;
+                    Endpoint::Sink(Box::new(LoopbackSink { writer, spec }))
+                }
+            }
+        })
+    }
+
+    /// How much faster the clock of streams in `direction` runs, relative to their sample rate.
+    pub fn clock_rate(&self, direction: Direction) -> f64 {
+        match self {
+            DeviceKind::Loopback(line) => line.clock_rate(direction == Direction::Input),
+            _ => 1.0,
+        }
+    }
+}
+
+/// Opens a WAV file, and reads its header.
+pub fn open_wav(path: &Path) -> Result<WavReader<BufReader<File>>> {
+    let file = File::open(path).map_err(|error| Error::Io(error.kind()))?;
+    WavReader::new(BufReader::new(file)).map_err(|error| Error::Io(error.kind()))
+}
+
+impl From<SampleSpec> for WavSpec {
+    fn from(spec: SampleSpec) -> WavSpec {
+        WavSpec {
+            format: spec.format,
+            n_channels: spec.n_channels as u16,
+            sample_rate: spec.sample_rate as u32,
+        }
+    }
+}
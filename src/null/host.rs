@@ -0,0 +1,131 @@
+use std::sync::Arc;
+
+use crate::backend::Backend;
+use crate::error::{Error, Result};
+use crate::null::device::Device;
+use crate::null::endpoint::DeviceKind;
+use crate::null::{Clock, NullDeviceOptions, NullHostOptions};
+
+/// A host of the null backend, or of another backend built on top of it (e.g. the file backend).
+#[derive(Clone)]
+pub struct Host(Arc<HostState>);
+
+/// The devices of a virtual host, shared by the host and its devices.
+pub struct HostState {
+    pub name: &'static str,
+    pub backend: Backend,
+    pub clock: Clock,
+    pub devices: Vec<VirtualDevice>,
+}
+
+/// A device of a virtual host.
+pub struct VirtualDevice {
+    pub options: NullDeviceOptions,
+    pub kind: DeviceKind,
+}
+
+impl HostState {
+    pub fn null(options: NullHostOptions) -> HostState {
+        HostState {
+            name: "Null",
+            backend: Backend::Null,
+            clock: options.clock,
+            devices: options
+                .devices
+                .into_iter()
+                .map(|options| VirtualDevice {
+                    options,
+                    kind: DeviceKind::Null,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl Host {
+    pub fn new(options: NullHostOptions) -> Result<Host> {
+        options.validate()?;
+        Ok(Host::with_state(HostState::null(options)))
+    }
+
+    /// Creates a host from already validated devices.
+    pub fn with_state(state: HostState) -> Host {
+        Host(Arc::new(state))
+    }
+
+    pub fn name(&self) -> &str {
+        self.0.name
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.0.backend
+    }
+
+    pub fn devices(&self) -> Vec<Device> {
+        (0..self.0.devices.len())
+            .map(|index| Device::new(Arc::clone(&self.0), index))
+            .collect()
+    }
+
+    pub fn default_output_device(&self) -> Result<Device> {
+        self.first_device_where(|device| device.max_output_channels > 0)
+    }
+
+    pub fn default_input_device(&self) -> Result<Device> {
+        self.first_device_where(|device| device.max_input_channels > 0)
+    }
+
+    fn first_device_where(&self, predicate: impl Fn(&NullDeviceOptions) -> bool) -> Result<Device> {
+        let index = self
+            .0
+            .devices
+            .iter()
+            .position(|device| predicate(&device.options))
+            .ok_or(Error::NoSuchDevice)?;
+        Ok(Device::new(Arc::clone(&self.0), index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device_with_channels(name: &str, inputs: i32, outputs: i32) -> NullDeviceOptions {
+        NullDeviceOptions {
+            name: name.to_string(),
+            max_input_channels: inputs,
+            max_output_channels: outputs,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn enumerates_configured_devices() -> Result<()> {
+        let host = Host::new(NullHostOptions {
+            devices: vec![
+                device_with_channels("Microphone", 1, 0),
+                device_with_channels("Speakers", 0, 2),
+            ],
+            ..Default::default()
+        })?;
+        let names: Vec<_> = host
+            .devices()
+            .iter()
+            .map(|d| d.name().to_string())
+            .collect();
+        assert_eq!(names, vec!["Microphone", "Speakers"]);
+        assert_eq!(host.default_output_device()?.name(), "Speakers");
+        assert_eq!(host.default_input_device()?.name(), "Microphone");
+        Ok(())
+    }
+
+    #[test]
+    fn errors_if_no_default_device() -> Result<()> {
+        let host = Host::new(NullHostOptions {
+            devices: vec![device_with_channels("Speakers", 0, 2)],
+            ..Default::default()
+        })?;
+        assert_eq!(host.default_input_device().err(), Some(Error::NoSuchDevice));
+        Ok(())
+    }
+}
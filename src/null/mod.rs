@@ -0,0 +1,161 @@
+//! A pure-Rust backend with configurable fake devices, for running tests without audio hardware.
+//!
+//! Streams are driven by a thread that calls the stream callback at the stream's sample rate (or
+//! faster, see [`Clock`]). Output is discarded, and input is silent.
+use crate::error::{Error, Result};
+use crate::stream_options::Format;
+
+mod device;
+mod endpoint;
+mod host;
+mod stream;
+
+// Public API exports.
+pub use device::Device;
+pub use endpoint::{open_wav, DeviceKind, SampleSpec};
+pub use host::{Host, HostState, VirtualDevice};
+pub use stream::Stream;
+
+/// The number of frames per callback when `frames_per_buffer` is unspecified.
+const DEFAULT_FRAMES_PER_BUFFER: i32 = 256;
+
+/// How fast the streams of a null host call their callbacks.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Clock {
+    /// At the stream's sample rate, like a hardware device would.
+    RealTime,
+    /// The given factor faster than the stream's sample rate (e.g. `Accelerated(2.0)` calls the
+    /// callback twice as often as `RealTime`).
+    Accelerated(f64),
+}
+
+impl Default for Clock {
+    fn default() -> Clock {
+        Clock::RealTime
+    }
+}
+
+impl Clock {
+    fn speed(self) -> f64 {
+        match self {
+            Clock::RealTime => 1.0,
+            Clock::Accelerated(factor) => factor,
+        }
+    }
+
+    pub(crate) fn validate(self) -> Result<()> {
+        let speed = self.speed();
+        if !speed.is_finite() || speed <= 0.0 {
+            return Err(Error::Invalid);
+        }
+        Ok(())
+    }
+}
+
+/// Describes a fake device of the null backend.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NullDeviceOptions {
+    pub name: String,
+    /// The maximum number of channels of input streams. Zero if the device has no input.
+    pub max_input_channels: i32,
+    /// The maximum number of channels of output streams. Zero if the device has no output.
+    pub max_output_channels: i32,
+    /// The sample rates that streams can be opened with.
+    pub sample_rates: Vec<i32>,
+    /// The sample rate used for [`SampleRate::DeviceDefault`](crate::SampleRate::DeviceDefault).
+    pub default_sample_rate: i32,
+    /// The formats that streams can be opened with.
+    pub formats: Vec<Format>,
+}
+
+impl Default for NullDeviceOptions {
+    fn default() -> NullDeviceOptions {
+        NullDeviceOptions {
+            name: "Null Device".to_string(),
+            max_input_channels: 2,
+            max_output_channels: 2,
+            sample_rates: vec![44_100, 48_000],
+            default_sample_rate: 48_000,
+            formats: vec![
+                Format::F32,
+                Format::I32,
+                Format::I24,
+                Format::I16,
+                Format::I8,
+                Format::U8,
+            ],
+        }
+    }
+}
+
+/// Configures a host of the null backend.
+///
+/// The default options have a single stereo input and output [`NullDeviceOptions::default`]
+/// device, running in real time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NullHostOptions {
+    /// The host's devices. The first device with outputs (inputs) is the default output (input)
+    /// device.
+    pub devices: Vec<NullDeviceOptions>,
+    pub clock: Clock,
+}
+
+impl Default for NullHostOptions {
+    fn default() -> NullHostOptions {
+        NullHostOptions {
+            devices: vec![NullDeviceOptions::default()],
+            clock: Clock::default(),
+        }
+    }
+}
+
+impl NullDeviceOptions {
+    pub(crate) fn validate(&self) -> Result<()> {
+        if self.max_input_channels < 0
+            || self.max_output_channels < 0
+            || !self.sample_rates.contains(&self.default_sample_rate)
+        {
+            return Err(Error::Invalid);
+        }
+        Ok(())
+    }
+}
+
+impl NullHostOptions {
+    fn validate(&self) -> Result<()> {
+        self.clock.validate()?;
+        self.devices
+            .iter()
+            .try_for_each(NullDeviceOptions::validate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_host_options() {
+        assert_eq!(NullHostOptions::default().validate(), Ok(()));
+        assert_eq!(
+            NullHostOptions {
+                clock: Clock::Accelerated(0.0),
+                ..Default::default()
+            }
+            .validate(),
+            Err(Error::Invalid)
+        );
+        assert_eq!(
+            NullHostOptions {
+                devices: vec![NullDeviceOptions {
+                    default_sample_rate: 1234,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }
+            .validate(),
+            Err(Error::Invalid)
+        );
+    }
+}
@@ -0,0 +1,338 @@
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+use crate::null::endpoint::{Endpoint, SampleSpec};
+use crate::stream::StreamState;
+use crate::stream_options::{Callback, Direction};
+
+/// A stream configuration, resolved from [`StreamOptions`](crate::StreamOptions) by the device.
+pub struct StreamConfig {
+    pub direction: Direction,
+    pub spec: SampleSpec,
+    pub frames_per_buffer: usize,
+    /// The wall-clock time between two callbacks.
+    pub period: Duration,
+    pub latency: Duration,
+}
+
+/// A stream of the null backend, driven by its own thread.
+pub struct Stream<Frame> {
+    /// Processes a single buffer, and returns whether the stream should continue. Moved to the
+    /// stream's thread once started, together with the endpoint.
+    process: Option<Box<dyn FnMut(&mut Endpoint) -> Result<bool> + Send>>,
+    endpoint: Option<Endpoint>,
+    period: Duration,
+    latency: Duration,
+    running: Arc<AtomicBool>,
+    /// The error that ended the stream, if any.
+    error: Arc<Mutex<Option<Error>>>,
+    /// Returns the endpoint once the stream stopped.
+    thread: Option<JoinHandle<Endpoint>>,
+    _callback: PhantomData<Callback<Frame>>,
+}
+
+impl<Frame> Stream<Frame> {
+    pub fn new(
+        mut callback: Callback<Frame>,
+        config: StreamConfig,
+        endpoint: Endpoint,
+    ) -> Stream<Frame>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        let mut buffer = vec![Frame::equilibrium(); config.frames_per_buffer];
+        let direction = config.direction;
+        let process = move |endpoint: &mut Endpoint| {
+            let mut more = true;
+            match endpoint {
+                Endpoint::Source(source) => more = source(as_bytes_mut(&mut buffer))?,
+                // Input is silent. Reset it every time, in case the callback modified it.
+                _ if direction == Direction::Input => {
+                    for frame in buffer.iter_mut() {
+                        *frame = Frame::equilibrium();
+                    }
+                }
+                _ => (),
+            }
+            callback(&mut buffer);
+            if let Endpoint::Sink(sink) = endpoint {
+                sink.write(as_bytes(&buffer))?;
+            }
+            Ok(more)
+        };
+        Stream {
+            process: Some(Box::new(process)),
+            endpoint: Some(endpoint),
+            period: config.period,
+            latency: config.latency,
+            running: Arc::new(AtomicBool::new(false)),
+            error: Arc::new(Mutex::new(None)),
+            thread: None,
+            _callback: PhantomData,
+        }
+    }
+
+    /// Stream is inactive (i.e. no callback) until this method is called.
+    pub fn start(&mut self) -> Result<()> {
+        let mut process = self.process.take().ok_or(Error::StreamAlreadyStarted)?;
+        let mut endpoint = self.endpoint.take().unwrap();
+        let period = self.period;
+        let running = Arc::clone(&self.running);
+        let error = Arc::clone(&self.error);
+        running.store(true, Ordering::Release);
+        self.thread = Some(
+            thread::Builder::new()
+                .name("audiohal-null-stream".to_string())
+                .spawn(move || {
+                    let mut deadline = Instant::now();
+                    while running.load(Ordering::Acquire) {
+                        match process(&mut endpoint) {
+                            Ok(true) => (),
+                            result => {
+                                // Record the error before the stream is seen as stopped.
+                                *error.lock().unwrap() = result.err();
+                                running.store(false, Ordering::Release);
+                                break;
+                            }
+                        }
+                        deadline += period;
+                        let now = Instant::now();
+                        if deadline > now {
+                            thread::sleep(deadline - now);
+                        }
+                    }
+                    endpoint
+                })
+                .or(Err(Error::Unknown(
+                    "Could not spawn the null stream thread.",
+                )))?,
+        );
+        Ok(())
+    }
+
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.running.load(Ordering::Acquire)
+    }
+
+    pub fn state(&self) -> StreamState {
+        if self.is_active() {
+            StreamState::Active
+        } else if let Some(error) = self.error.lock().unwrap().clone() {
+            StreamState::Failed(error)
+        } else {
+            StreamState::Stopped
+        }
+    }
+
+    /// Stops the stream, and completes its endpoint, e.g. the header of a WAV file.
+    pub fn close(mut self) -> Result<()> {
+        self.running.store(false, Ordering::Release);
+        let endpoint = match self.thread.take() {
+            // A panicking callback dropped the endpoint while unwinding.
+            Some(thread) => thread.join().ok(),
+            None => self.endpoint.take(),
+        };
+        match endpoint {
+            Some(Endpoint::Sink(mut sink)) => sink.finish(),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Views frames as the raw bytes of their samples.
+fn as_bytes<Frame>(frames: &[Frame]) -> &[u8] {
+    // Frames are arrays of samples, whose size was checked against the stream's format.
+    unsafe {
+        std::slice::from_raw_parts(
+            frames.as_ptr() as *const u8,
+            frames.len() * std::mem::size_of::<Frame>(),
+        )
+    }
+}
+
+/// Views frames as the raw bytes of their samples, mutably.
+fn as_bytes_mut<Frame>(frames: &mut [Frame]) -> &mut [u8] {
+    unsafe {
+        std::slice::from_raw_parts_mut(
+            frames.as_mut_ptr() as *mut u8,
+            frames.len() * std::mem::size_of::<Frame>(),
+        )
+    }
+}
+
+impl<Frame> Drop for Stream<Frame> {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            // A panicking callback already reported its panic on the stream thread.
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::null::endpoint::SampleSink;
+    use crate::null::{Clock, NullHostOptions};
+    use crate::{Format, Host, SampleRate, StreamOptions};
+    use std::io;
+    use std::sync::mpsc;
+
+    fn null_host(clock: Clock) -> Host {
+        Host::with_null_backend(NullHostOptions {
+            clock,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn stream_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Stream<[f32; 2]>>();
+    }
+
+    #[test]
+    fn calls_back_with_frames_per_buffer() -> Result<()> {
+        let (sender, receiver) = mpsc::channel();
+        let mut stream = null_host(Clock::RealTime)
+            .default_output_device()?
+            .open_outstream(StreamOptions {
+                callback: Box::new(move |buffer: &mut [[f32; 2]]| {
+                    let _ = sender.send(buffer.len());
+                }),
+                frames_per_buffer: Some(128),
+                ..Default::default()
+            })?;
+        stream.start()?;
+        for _ in 0..4 {
+            assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), Ok(128));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn input_is_silent() -> Result<()> {
+        let (sender, receiver) = mpsc::channel();
+        let mut stream = null_host(Clock::RealTime)
+            .default_input_device()?
+            .open_instream(StreamOptions {
+                callback: Box::new(move |buffer: &mut [[i16; 1]]| {
+                    let _ = sender.send(buffer.iter().all(|frame| *frame == [0]));
+                    buffer[0] = [1];
+                }),
+                ..Default::default()
+            })?;
+        stream.start()?;
+        for _ in 0..2 {
+            assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), Ok(true));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn accelerated_clock_runs_faster_than_real_time() -> Result<()> {
+        let (sender, receiver) = mpsc::channel();
+        let mut stream = null_host(Clock::Accelerated(100.0))
+            .default_output_device()?
+            .open_outstream(StreamOptions {
+                callback: Box::new(move |buffer: &mut [[f32; 2]]| {
+                    let _ = sender.send(buffer.len());
+                }),
+                sample_rate: SampleRate::Exact(48_000),
+                frames_per_buffer: Some(4800),
+                ..Default::default()
+            })?;
+        let start = Instant::now();
+        stream.start()?;
+        // One simulated second.
+        for _ in 0..10 {
+            receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        }
+        assert_lt!(start.elapsed(), Duration::from_millis(500));
+        Ok(())
+    }
+
+    #[test]
+    fn stops_calling_back_once_closed() -> Result<()> {
+        let (sender, receiver) = mpsc::channel();
+        let mut stream = null_host(Clock::Accelerated(10.0))
+            .default_output_device()?
+            .open_outstream(StreamOptions {
+                callback: Box::new(move |_: &mut [[f32; 2]]| {
+                    let _ = sender.send(());
+                }),
+                ..Default::default()
+            })?;
+        stream.start()?;
+        receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        stream.close()?;
+        // The callback (and its sender) is dropped once the stream thread exits.
+        while receiver.recv_timeout(Duration::from_secs(5)).is_ok() {}
+        assert_eq!(receiver.try_recv(), Err(mpsc::TryRecvError::Disconnected));
+        Ok(())
+    }
+
+    #[test]
+    fn errors_if_started_twice() -> Result<()> {
+        let mut stream = null_host(Clock::RealTime)
+            .default_output_device()?
+            .open_outstream(StreamOptions::<[f32; 2]>::default())?;
+        stream.start()?;
+        assert_eq!(stream.start(), Err(Error::StreamAlreadyStarted));
+        Ok(())
+    }
+
+    /// Counts the frames written to it, and fails to finish.
+    struct FailingSink(Arc<Mutex<usize>>);
+
+    impl SampleSink for FailingSink {
+        fn write(&mut self, samples: &[u8]) -> Result<()> {
+            *self.0.lock().unwrap() += samples.len() / 8;
+            Ok(())
+        }
+
+        fn finish(&mut self) -> Result<()> {
+            Err(Error::Io(io::ErrorKind::WriteZero))
+        }
+    }
+
+    #[test]
+    fn close_reports_errors_of_the_sink() -> Result<()> {
+        let n_frames = Arc::new(Mutex::new(0));
+        let config = StreamConfig {
+            direction: Direction::Output,
+            spec: SampleSpec {
+                format: Format::F32,
+                n_channels: 2,
+                sample_rate: 48_000,
+            },
+            frames_per_buffer: 16,
+            period: Duration::from_millis(1),
+            latency: Duration::from_millis(1),
+        };
+        let sink = FailingSink(Arc::clone(&n_frames));
+        let mut stream = Stream::<[f32; 2]>::new(
+            Box::new(|_: &mut [[f32; 2]]| ()),
+            config,
+            Endpoint::Sink(Box::new(sink)),
+        );
+        stream.start()?;
+        let start = Instant::now();
+        while *n_frames.lock().unwrap() == 0 {
+            assert_lt!(start.elapsed(), Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(stream.close(), Err(Error::Io(io::ErrorKind::WriteZero)));
+        Ok(())
+    }
+}
@@ -0,0 +1,199 @@
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::reblock::Reblocker;
+use crate::stream_options::{check_frame_size, Callback, Direction, StreamOptions};
+use crate::SampleRate;
+
+/// The sample rate of offline streams opened with [`SampleRate::DeviceDefault`].
+const DEFAULT_SAMPLE_RATE: i32 = 48_000;
+
+/// An output stream that is rendered on demand, as fast as possible, instead of by a device's
+/// clock.
+///
+/// Offline streams take the same [`StreamOptions`] as device streams, so production callbacks can
+/// be used unmodified to render golden files or bounce audio to disk. The callback is called with
+/// `frames_per_buffer` frames (or `block_size` frames, if set), regardless of how many frames are
+/// rendered at a time. If neither is set, it is called with exactly the requested frames.
+///
+/// # Examples
+///
+/// ```
+/// # use audiohal::*;
+/// let mut stream = OfflineStream::new(StreamOptions {
+///     callback: Box::new(|buffer: &mut [[f32; 1]]| {
+///         for frame in buffer.iter_mut() {
+///             *frame = [0.5];
+///         }
+///     }),
+///     sample_rate: SampleRate::Exact(44_100),
+///     frames_per_buffer: Some(64),
+///     ..Default::default()
+/// })?;
+/// let rendered = stream.render(100);
+/// assert_eq!(rendered, vec![[0.5]; 100]);
+/// # Result::Ok(())
+/// ```
+pub struct OfflineStream<Frame> {
+    renderer: Renderer<Frame>,
+    sample_rate: i32,
+    frames_rendered: u64,
+}
+
+enum Renderer<Frame> {
+    /// The callback is called with each render's buffer.
+    Direct(Callback<Frame>),
+    /// The callback is called with fixed-size blocks.
+    Reblocked(Reblocker<Frame>),
+}
+
+impl<Frame> OfflineStream<Frame>
+where
+    Frame: sample::Frame + Send + 'static,
+{
+    /// Creates an offline stream. Unlike device streams, any positive sample rate and number of
+    /// channels is supported. [`SampleRate::DeviceDefault`] is 48kHz.
+    pub fn new(options: StreamOptions<Frame>) -> Result<OfflineStream<Frame>> {
+        options
+            .flags
+            .validate(Direction::Output, options.frames_per_buffer)?;
+        if options.n_channels <= 0 {
+            return Err(Error::IncompatibleNChannels);
+        }
+        check_frame_size::<Frame>(options.format, options.n_channels)?;
+        let sample_rate = match options.sample_rate {
+            SampleRate::Exact(rate) | SampleRate::NearestTo(rate) => rate,
+            SampleRate::DeviceDefault => DEFAULT_SAMPLE_RATE,
+            _ => panic!("Non-exhaustive sample rate."),
+        };
+        if sample_rate <= 0 {
+            return Err(Error::IncompatibleSampleRate);
+        }
+        if let Some(frames_per_buffer) = options.frames_per_buffer {
+            if frames_per_buffer <= 0 {
+                return Err(Error::InvalidFramesPerBuffer);
+            }
+        }
+        // A block size takes precedence over frames_per_buffer, as it would on a device.
+        let renderer = match (options.block_size, options.frames_per_buffer) {
+            (Some(block_size), _) if block_size <= 0 => return Err(Error::InvalidBlockSize),
+            (Some(size), _) | (None, Some(size)) => {
+                Renderer::Reblocked(Reblocker::for_output(options.callback, size as usize))
+            }
+            (None, None) => Renderer::Direct(options.callback),
+        };
+        Ok(OfflineStream {
+            renderer,
+            sample_rate,
+            frames_rendered: 0,
+        })
+    }
+
+    /// Renders the next `n_frames` frames.
+    pub fn render(&mut self, n_frames: usize) -> Vec<Frame> {
+        let mut output = vec![Frame::equilibrium(); n_frames];
+        self.render_into(&mut output);
+        output
+    }
+
+    /// Renders the next `output.len()` frames into `output`.
+    pub fn render_into(&mut self, output: &mut [Frame]) {
+        match &mut self.renderer {
+            Renderer::Direct(callback) => callback(output),
+            Renderer::Reblocked(reblocker) => reblocker.fill_output(output),
+        }
+        self.frames_rendered += output.len() as u64;
+    }
+
+    pub fn sample_rate(&self) -> i32 {
+        self.sample_rate
+    }
+
+    /// The stream time of the next rendered frame (i.e. the duration rendered so far).
+    pub fn position(&self) -> Duration {
+        Duration::from_secs_f64(self.frames_rendered as f64 / f64::from(self.sample_rate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// A callback that records the size of every buffer it is called with.
+    fn recording_callback(sizes: &Arc<Mutex<Vec<usize>>>) -> Callback<[f32; 2]> {
+        let sizes = Arc::clone(sizes);
+        Box::new(move |buffer: &mut [[f32; 2]]| sizes.lock().unwrap().push(buffer.len()))
+    }
+
+    #[test]
+    fn calls_back_with_requested_frames_by_default() -> Result<()> {
+        let sizes = Arc::default();
+        let mut stream = OfflineStream::new(StreamOptions {
+            callback: recording_callback(&sizes),
+            ..Default::default()
+        })?;
+        stream.render(100);
+        stream.render(7);
+        assert_eq!(*sizes.lock().unwrap(), vec![100, 7]);
+        Ok(())
+    }
+
+    #[test]
+    fn calls_back_with_frames_per_buffer() -> Result<()> {
+        let sizes = Arc::default();
+        let mut stream = OfflineStream::new(StreamOptions {
+            callback: recording_callback(&sizes),
+            frames_per_buffer: Some(32),
+            ..Default::default()
+        })?;
+        assert_eq!(stream.render(100).len(), 100);
+        assert_eq!(*sizes.lock().unwrap(), vec![32; 4]);
+        Ok(())
+    }
+
+    #[test]
+    fn renders_continuously() -> Result<()> {
+        let mut counter = 0.0;
+        let mut stream = OfflineStream::new(StreamOptions {
+            callback: Box::new(move |buffer: &mut [[f32; 1]]| {
+                for frame in buffer.iter_mut() {
+                    *frame = [counter];
+                    counter += 1.0;
+                }
+            }),
+            sample_rate: SampleRate::Exact(1000),
+            frames_per_buffer: Some(3),
+            ..Default::default()
+        })?;
+        let mut rendered = stream.render(5);
+        rendered.extend(stream.render(5));
+        let expected: Vec<_> = (0..10).map(|i| [i as f32]).collect();
+        assert_eq!(rendered, expected);
+        assert_eq!(stream.position(), Duration::from_millis(10));
+        Ok(())
+    }
+
+    #[test]
+    fn errors_if_invalid_options() {
+        assert_eq!(
+            OfflineStream::new(StreamOptions::<[f32; 2]> {
+                sample_rate: SampleRate::Exact(0),
+                ..Default::default()
+            })
+            .err(),
+            Some(Error::IncompatibleSampleRate)
+        );
+        assert_eq!(
+            OfflineStream::new(StreamOptions::<[f32; 2]> {
+                n_channels: 1,
+                ..Default::default()
+            })
+            .err(),
+            Some(Error::InvalidFrameSize {
+                expected: 4,
+                actual: 8
+            })
+        );
+    }
+}
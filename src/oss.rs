@@ -0,0 +1,86 @@
+//! Extensions of the OSS backend ([`Backend::LinuxFallback`](crate::Backend::LinuxFallback)).
+//! Requires the `oss` feature.
+//!
+//! OSS devices are the `/dev/dsp*` device nodes, which may be provided by a kernel driver or by
+//! an emulation such as `osspd` (which forwards them to ALSA or PulseAudio through CUSE).
+//!
+//! A stream's [`frames_per_buffer`](crate::StreamOptions::frames_per_buffer) sets the size of
+//! the device's fragments (i.e. the chunks the driver transfers at once). OSS fragments are a
+//! power of two bytes, so PortAudio rounds the size up and re-blocks: the callback is still
+//! called with exactly `frames_per_buffer` frames. The number of fragments follows from the
+//! requested [`Latency`](crate::Latency), with at least two.
+use std::path::{Path, PathBuf};
+
+use crate::device::Device;
+use crate::error::{Error, Result};
+
+/// Where OSS device nodes live.
+const DEVICE_DIR: &str = "/dev";
+
+/// Lists the `/dev/dsp*` device nodes (i.e. `/dev/dsp`, `/dev/dsp1`, ...), in numerical order.
+///
+/// PortAudio probes nodes in that order, and stops at the first one missing: nodes after a gap
+/// (e.g. `/dev/dsp2` without `/dev/dsp1`) are listed here but are not devices of the host.
+pub fn device_paths() -> Result<Vec<PathBuf>> {
+    device_paths_in(Path::new(DEVICE_DIR))
+}
+
+fn device_paths_in(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths: Vec<(u32, PathBuf)> = std::fs::read_dir(dir)
+        .map_err(|error| Error::Io(error.kind()))?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let name = entry.file_name();
+            let index = match name.to_str()?.strip_prefix("dsp")? {
+                "" => 0,
+                index => index.parse().ok().filter(|&index| index > 0)?,
+            };
+            Some((index, entry.path()))
+        })
+        .collect();
+    paths.sort();
+    Ok(paths.into_iter().map(|(_, path)| path).collect())
+}
+
+/// OSS-specific methods of [`Device`].
+pub trait DeviceExt {
+    /// Returns the path of the device's node (e.g. `/dev/dsp`). Returns [`Error::WrongBackend`]
+    /// for devices of other backends.
+    fn oss_path(&self) -> Result<PathBuf>;
+}
+
+impl DeviceExt for Device {
+    fn oss_path(&self) -> Result<PathBuf> {
+        self.as_portaudio().ok_or(Error::WrongBackend)?.oss_path()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Host, NullHostOptions};
+
+    #[test]
+    fn lists_device_nodes_in_order() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("audiohal-oss-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).map_err(|error| Error::Io(error.kind()))?;
+        for name in &["dsp10", "dsp", "dsp2", "dsp0", "dspx", "mixer", "adsp"] {
+            std::fs::write(dir.join(name), b"").map_err(|error| Error::Io(error.kind()))?;
+        }
+        let paths = device_paths_in(&dir);
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(
+            paths?,
+            vec![dir.join("dsp"), dir.join("dsp2"), dir.join("dsp10")]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn rejects_other_backends() -> Result<()> {
+        let mut host = Host::with_null_backend(NullHostOptions::default())?;
+        let device = host.default_output_device()?;
+        assert_eq!(device.oss_path(), Err(Error::WrongBackend));
+        Ok(())
+    }
+}
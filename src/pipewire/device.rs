@@ -0,0 +1,275 @@
+use std::ffi::CStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::pipewire::stream::Stream;
+use crate::pipewire::{ffi, format, PipeWireOptions};
+use crate::reblock;
+use crate::stream_options::{check_frame_size, Direction, StreamOptions};
+use crate::{Backend, DeviceId, Latency, SampleRate};
+
+/// The graph's rate, unless configured otherwise. Streams at other rates are resampled.
+const DEFAULT_SAMPLE_RATE: i32 = 48000;
+
+/// The quantum requested for `Latency::Low` and `Latency::High`, when `frames_per_buffer` is
+/// unspecified.
+const LOW_LATENCY: Duration = Duration::from_millis(10);
+const HIGH_LATENCY: Duration = Duration::from_millis(40);
+
+/// An audio node, as registered by the daemon.
+#[derive(Debug, Clone)]
+pub struct NodeInfo {
+    /// The daemon's identifier for the node (e.g. `alsa_output.pci-0000_00_1f.3.analog-stereo`).
+    pub name: String,
+    pub description: String,
+    pub direction: Direction,
+    pub n_channels: i32,
+    /// The node's `priority.session`, used by session managers to pick the default node.
+    pub priority: i32,
+}
+
+impl NodeInfo {
+    /// Parses the properties of a node. Returns `None` if it is not an audio sink or source.
+    pub fn from_props(props: &ffi::spa_dict) -> Option<NodeInfo> {
+        let lookup = |key: &str| -> Option<String> {
+            let items = unsafe { std::slice::from_raw_parts(props.items, props.n_items as usize) };
+            items
+                .iter()
+                .find(|item| unsafe { CStr::from_ptr(item.key) }.to_bytes() == key.as_bytes())
+                .filter(|item| !item.value.is_null())
+                .map(|item| {
+                    unsafe { CStr::from_ptr(item.value) }
+                        .to_string_lossy()
+                        .into_owned()
+                })
+        };
+        let direction = match lookup("media.class")?.as_str() {
+            "Audio/Sink" => Direction::Output,
+            "Audio/Source" | "Audio/Source/Virtual" => Direction::Input,
+            _ => return None,
+        };
+        let name = lookup("node.name")?;
+        Some(NodeInfo {
+            description: lookup("node.description")
+                .or_else(|| lookup("node.nick"))
+                .unwrap_or_else(|| name.clone()),
+            name,
+            direction,
+            n_channels: lookup("audio.channels")
+                .and_then(|channels| channels.parse().ok())
+                .unwrap_or(2),
+            priority: lookup("priority.session")
+                .and_then(|priority| priority.parse().ok())
+                .unwrap_or(0),
+        })
+    }
+}
+
+/// An audio node of the PipeWire backend.
+pub struct Device {
+    options: Arc<PipeWireOptions>,
+    info: NodeInfo,
+    /// Whether streams should be left for the session manager to route, instead of targeting
+    /// this node.
+    follows_default: bool,
+}
+
+impl Device {
+    pub fn new(options: Arc<PipeWireOptions>, info: NodeInfo, follows_default: bool) -> Device {
+        Device {
+            options,
+            info,
+            follows_default,
+        }
+    }
+
+    /// The node's description (e.g. "Built-in Audio Analog Stereo").
+    pub fn name(&self) -> &str {
+        &self.info.description
+    }
+
+    /// Server identifiers are unique, so the occurrence is always 0.
+    pub fn id(&self) -> DeviceId {
+        DeviceId {
+            backend: Backend::PipeWire,
+            name: self.info.name.clone(),
+            occurrence: 0,
+            direction: self.info.direction,
+        }
+    }
+
+    pub fn open_outstream<Frame>(&mut self, options: StreamOptions<Frame>) -> Result<Stream<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        self.open(options, Direction::Output)
+    }
+
+    pub fn open_instream<Frame>(&mut self, options: StreamOptions<Frame>) -> Result<Stream<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        self.open(options, Direction::Input)
+    }
+
+    fn open<Frame>(
+        &self,
+        options: StreamOptions<Frame>,
+        direction: Direction,
+    ) -> Result<Stream<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        if direction != self.info.direction {
+            return Err(Error::IncompatibleDirection);
+        }
+        options
+            .flags
+            .validate(direction, options.frames_per_buffer)?;
+        if options.n_channels <= 0 || options.n_channels > ffi::SPA_AUDIO_MAX_CHANNELS {
+            return Err(Error::IncompatibleNChannels);
+        }
+        check_frame_size::<Frame>(options.format, options.n_channels)?;
+        // The graph resamples streams to its own rate.
+        let sample_rate = match options.sample_rate {
+            SampleRate::Exact(rate) | SampleRate::NearestTo(rate) => rate,
+            SampleRate::DeviceDefault => DEFAULT_SAMPLE_RATE,
+            _ => panic!("Non-exhaustive sample rate."),
+        };
+        if sample_rate <= 0 {
+            return Err(Error::IncompatibleSampleRate);
+        }
+        // The requested quantum. The graph runs at the smallest quantum requested by its nodes,
+        // so callbacks may get fewer frames.
+        let frames_per_buffer = match (options.frames_per_buffer, options.latency) {
+            (Some(frames_per_buffer), _) => frames_per_buffer,
+            (None, Latency::Low) => to_frames(LOW_LATENCY, sample_rate),
+            (None, Latency::High) => to_frames(HIGH_LATENCY, sample_rate),
+            (None, Latency::Exact(latency)) => to_frames(latency, sample_rate),
+            _ => panic!("Non-exhaustive latency."),
+        };
+        if frames_per_buffer <= 0 {
+            return Err(Error::InvalidFramesPerBuffer);
+        }
+
+        // The stream connects on its own, with the host's connection properties.
+        let mut properties = self.options.connection_properties()?;
+        let category = match direction {
+            Direction::Input => "Capture",
+            _ => "Playback",
+        };
+        let name = options.name.as_deref().unwrap_or(category);
+        properties.set("media.type", "Audio")?;
+        properties.set("media.category", category)?;
+        properties.set("media.name", name)?;
+        properties.set(
+            "node.latency",
+            &format!("{}/{}", frames_per_buffer, sample_rate),
+        )?;
+        if !self.follows_default {
+            properties.set("target.object", &self.info.name)?;
+        }
+        // User properties take precedence.
+        for (key, value) in &options.properties {
+            properties.set(key, value)?;
+        }
+        let format = format::audio_format(
+            options.format,
+            sample_rate as u32,
+            options.n_channels as u32,
+        );
+
+        let callback = reblock::wrap(options.callback, options.block_size, direction)?;
+        let latency =
+            Duration::from_secs_f64(f64::from(frames_per_buffer) / f64::from(sample_rate))
+                + options
+                    .block_size
+                    .map_or(Duration::default(), |block_size| {
+                        reblock::added_latency(block_size, sample_rate)
+                    });
+        Stream::new(
+            name,
+            properties,
+            &format,
+            callback,
+            direction,
+            frames_per_buffer as usize,
+            latency,
+        )
+    }
+}
+
+fn to_frames(latency: Duration, sample_rate: i32) -> i32 {
+    (latency.as_secs_f64() * f64::from(sample_rate)).round() as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    fn dict_of(pairs: &[(&str, &str)]) -> (Vec<CString>, Vec<ffi::spa_dict_item>) {
+        let strings: Vec<_> = pairs
+            .iter()
+            .flat_map(|(key, value)| vec![CString::new(*key), CString::new(*value)])
+            .map(|string| string.unwrap())
+            .collect();
+        let items = strings
+            .chunks(2)
+            .map(|pair| ffi::spa_dict_item {
+                key: pair[0].as_ptr(),
+                value: pair[1].as_ptr(),
+            })
+            .collect();
+        (strings, items)
+    }
+
+    fn parse(pairs: &[(&str, &str)]) -> Option<NodeInfo> {
+        let (_strings, items) = dict_of(pairs);
+        NodeInfo::from_props(&ffi::spa_dict {
+            flags: 0,
+            n_items: items.len() as u32,
+            items: items.as_ptr(),
+        })
+    }
+
+    #[test]
+    fn parses_node_properties() {
+        let info = parse(&[
+            ("media.class", "Audio/Source"),
+            ("node.name", "alsa_input.usb"),
+            ("node.description", "USB Microphone"),
+            ("audio.channels", "1"),
+            ("priority.session", "2000"),
+        ])
+        .unwrap();
+        assert_eq!(info.name, "alsa_input.usb");
+        assert_eq!(info.description, "USB Microphone");
+        assert_eq!(info.direction, Direction::Input);
+        assert_eq!(info.n_channels, 1);
+        assert_eq!(info.priority, 2000);
+    }
+
+    #[test]
+    fn skips_non_audio_nodes() {
+        assert!(parse(&[("media.class", "Video/Source"), ("node.name", "v4l2")]).is_none());
+        assert!(parse(&[("node.name", "Dummy-Driver")]).is_none());
+    }
+
+    #[test]
+    fn errors_if_opened_in_wrong_direction() {
+        let info = parse(&[
+            ("media.class", "Audio/Sink"),
+            ("node.name", "sink"),
+            ("audio.channels", "2"),
+        ])
+        .unwrap();
+        let mut sink = Device::new(Arc::new(PipeWireOptions::default()), info, false);
+        assert_eq!(
+            sink.open_instream(StreamOptions::<[f32; 2]>::default())
+                .err(),
+            Some(Error::IncompatibleDirection)
+        );
+    }
+}
@@ -0,0 +1,381 @@
+//! The subset of the libpipewire C API used by the PipeWire backend.
+//!
+//! Interface methods (e.g. `pw_core_sync`) are inline functions in the C headers, that dispatch
+//! through the interface's method table. They are reimplemented here the same way.
+#![allow(non_camel_case_types)]
+use std::os::raw::{c_char, c_int, c_void};
+
+pub enum pw_main_loop {}
+pub enum pw_thread_loop {}
+pub enum pw_loop {}
+pub enum pw_context {}
+pub enum pw_core {}
+pub enum pw_registry {}
+pub enum pw_proxy {}
+pub enum pw_properties {}
+pub enum pw_stream {}
+pub enum spa_pod {}
+
+/// The id of the core object, used for round trips.
+pub const PW_ID_CORE: u32 = 0;
+/// Lets the session manager pick the target of a stream.
+pub const PW_ID_ANY: u32 = 0xffff_ffff;
+
+pub const PW_TYPE_INTERFACE_NODE: &[u8] = b"PipeWire:Interface:Node\0";
+pub const PW_VERSION_REGISTRY: u32 = 3;
+pub const PW_VERSION_CORE_EVENTS: u32 = 0;
+pub const PW_VERSION_REGISTRY_EVENTS: u32 = 0;
+pub const PW_VERSION_STREAM_EVENTS: u32 = 2;
+
+pub type spa_direction = u32;
+pub const SPA_DIRECTION_INPUT: spa_direction = 0;
+pub const SPA_DIRECTION_OUTPUT: spa_direction = 1;
+
+pub type pw_stream_flags = u32;
+pub const PW_STREAM_FLAG_AUTOCONNECT: pw_stream_flags = 1 << 0;
+pub const PW_STREAM_FLAG_INACTIVE: pw_stream_flags = 1 << 1;
+pub const PW_STREAM_FLAG_MAP_BUFFERS: pw_stream_flags = 1 << 2;
+pub const PW_STREAM_FLAG_RT_PROCESS: pw_stream_flags = 1 << 4;
+
+pub type pw_stream_state = c_int;
+pub const PW_STREAM_STATE_ERROR: pw_stream_state = -1;
+pub const PW_STREAM_STATE_UNCONNECTED: pw_stream_state = 0;
+pub const PW_STREAM_STATE_CONNECTING: pw_stream_state = 1;
+
+// Object and POD types, from spa/utils/type.h and spa/param/*.h.
+pub const SPA_TYPE_ID: u32 = 3;
+pub const SPA_TYPE_INT: u32 = 4;
+pub const SPA_TYPE_ARRAY: u32 = 13;
+pub const SPA_TYPE_OBJECT: u32 = 15;
+pub const SPA_TYPE_OBJECT_FORMAT: u32 = 0x40003;
+pub const SPA_PARAM_ENUM_FORMAT: u32 = 3;
+pub const SPA_FORMAT_MEDIA_TYPE: u32 = 1;
+pub const SPA_FORMAT_MEDIA_SUBTYPE: u32 = 2;
+pub const SPA_FORMAT_AUDIO_FORMAT: u32 = 0x10001;
+pub const SPA_FORMAT_AUDIO_RATE: u32 = 0x10003;
+pub const SPA_FORMAT_AUDIO_CHANNELS: u32 = 0x10004;
+pub const SPA_FORMAT_AUDIO_POSITION: u32 = 0x10005;
+pub const SPA_MEDIA_TYPE_AUDIO: u32 = 1;
+pub const SPA_MEDIA_SUBTYPE_RAW: u32 = 1;
+
+pub const SPA_AUDIO_CHANNEL_MONO: u32 = 2;
+pub const SPA_AUDIO_CHANNEL_FL: u32 = 3;
+pub const SPA_AUDIO_CHANNEL_FR: u32 = 4;
+
+pub type spa_audio_format = u32;
+pub const SPA_AUDIO_FORMAT_S8: spa_audio_format = 0x101;
+pub const SPA_AUDIO_FORMAT_U8: spa_audio_format = 0x102;
+pub const SPA_AUDIO_FORMAT_S16_LE: spa_audio_format = 0x103;
+pub const SPA_AUDIO_FORMAT_S16_BE: spa_audio_format = 0x104;
+pub const SPA_AUDIO_FORMAT_S32_LE: spa_audio_format = 0x10b;
+pub const SPA_AUDIO_FORMAT_S32_BE: spa_audio_format = 0x10c;
+pub const SPA_AUDIO_FORMAT_S24_LE: spa_audio_format = 0x10f;
+pub const SPA_AUDIO_FORMAT_S24_BE: spa_audio_format = 0x110;
+pub const SPA_AUDIO_FORMAT_F32_LE: spa_audio_format = 0x11b;
+pub const SPA_AUDIO_FORMAT_F32_BE: spa_audio_format = 0x11c;
+
+/// The maximum number of channels of a raw audio format.
+pub const SPA_AUDIO_MAX_CHANNELS: i32 = 64;
+
+#[repr(C)]
+pub struct spa_dict_item {
+    pub key: *const c_char,
+    pub value: *const c_char,
+}
+
+#[repr(C)]
+pub struct spa_dict {
+    pub flags: u32,
+    pub n_items: u32,
+    pub items: *const spa_dict_item,
+}
+
+#[repr(C)]
+pub struct spa_list {
+    pub next: *mut spa_list,
+    pub prev: *mut spa_list,
+}
+
+#[repr(C)]
+pub struct spa_callbacks {
+    pub funcs: *const c_void,
+    pub data: *mut c_void,
+}
+
+/// The header of every interface (e.g. `pw_core` and `pw_registry`).
+#[repr(C)]
+pub struct spa_interface {
+    pub type_: *const c_char,
+    pub version: u32,
+    pub cb: spa_callbacks,
+}
+
+/// A listener registration. Must stay in place until its object is destroyed.
+#[repr(C)]
+pub struct spa_hook {
+    pub link: spa_list,
+    pub cb: spa_callbacks,
+    pub removed: Option<extern "C" fn(hook: *mut spa_hook)>,
+    pub priv_: *mut c_void,
+}
+
+impl spa_hook {
+    pub fn zeroed() -> spa_hook {
+        spa_hook {
+            link: spa_list {
+                next: std::ptr::null_mut(),
+                prev: std::ptr::null_mut(),
+            },
+            cb: spa_callbacks {
+                funcs: std::ptr::null(),
+                data: std::ptr::null_mut(),
+            },
+            removed: None,
+            priv_: std::ptr::null_mut(),
+        }
+    }
+}
+
+#[repr(C)]
+pub struct spa_chunk {
+    pub offset: u32,
+    pub size: u32,
+    pub stride: i32,
+    pub flags: i32,
+}
+
+#[repr(C)]
+pub struct spa_data {
+    pub type_: u32,
+    pub flags: u32,
+    pub fd: i64,
+    pub mapoffset: u32,
+    pub maxsize: u32,
+    pub data: *mut c_void,
+    pub chunk: *mut spa_chunk,
+}
+
+#[repr(C)]
+pub struct spa_buffer {
+    pub n_metas: u32,
+    pub n_datas: u32,
+    pub metas: *mut c_void,
+    pub datas: *mut spa_data,
+}
+
+/// The leading fields of `pw_buffer`, which is only ever accessed through pointers provided by
+/// libpipewire.
+#[repr(C)]
+pub struct pw_buffer {
+    pub buffer: *mut spa_buffer,
+    pub user_data: *mut c_void,
+    pub size: u64,
+    /// The number of frames the graph wants for this cycle, or 0 if unknown (before 0.3.49).
+    pub requested: u64,
+}
+
+/// Methods of events that are not used are left unset.
+type unused_event = Option<extern "C" fn()>;
+
+#[repr(C)]
+pub struct pw_core_events {
+    pub version: u32,
+    pub info: unused_event,
+    pub done: Option<extern "C" fn(data: *mut c_void, id: u32, seq: c_int)>,
+    pub ping: unused_event,
+    pub error: Option<
+        extern "C" fn(data: *mut c_void, id: u32, seq: c_int, res: c_int, message: *const c_char),
+    >,
+    pub remove_id: unused_event,
+    pub bound_id: unused_event,
+    pub add_mem: unused_event,
+    pub remove_mem: unused_event,
+}
+
+#[repr(C)]
+pub struct pw_core_methods {
+    pub version: u32,
+    pub add_listener: extern "C" fn(
+        object: *mut c_void,
+        listener: *mut spa_hook,
+        events: *const pw_core_events,
+        data: *mut c_void,
+    ) -> c_int,
+    pub hello: *const c_void,
+    pub sync: extern "C" fn(object: *mut c_void, id: u32, seq: c_int) -> c_int,
+    pub pong: *const c_void,
+    pub error: *const c_void,
+    pub get_registry:
+        extern "C" fn(object: *mut c_void, version: u32, user_data_size: usize) -> *mut pw_registry,
+}
+
+#[repr(C)]
+pub struct pw_registry_events {
+    pub version: u32,
+    pub global: Option<
+        extern "C" fn(
+            data: *mut c_void,
+            id: u32,
+            permissions: u32,
+            type_: *const c_char,
+            version: u32,
+            props: *const spa_dict,
+        ),
+    >,
+    pub global_remove: unused_event,
+}
+
+#[repr(C)]
+pub struct pw_registry_methods {
+    pub version: u32,
+    pub add_listener: extern "C" fn(
+        object: *mut c_void,
+        listener: *mut spa_hook,
+        events: *const pw_registry_events,
+        data: *mut c_void,
+    ) -> c_int,
+}
+
+#[repr(C)]
+pub struct pw_stream_events {
+    pub version: u32,
+    pub destroy: unused_event,
+    pub state_changed: Option<
+        extern "C" fn(
+            data: *mut c_void,
+            old: pw_stream_state,
+            state: pw_stream_state,
+            error: *const c_char,
+        ),
+    >,
+    pub control_info: unused_event,
+    pub io_changed: unused_event,
+    pub param_changed: unused_event,
+    pub add_buffer: unused_event,
+    pub remove_buffer: unused_event,
+    pub process: Option<extern "C" fn(data: *mut c_void)>,
+    pub drained: unused_event,
+    pub command: unused_event,
+    pub trigger_done: unused_event,
+}
+
+/// Returns the method table and object of an interface.
+///
+/// # Safety
+/// `interface` must point to a live interface whose methods are of type `Methods`.
+unsafe fn methods<Methods>(interface: *mut c_void) -> (&'static Methods, *mut c_void) {
+    let interface = &*(interface as *mut spa_interface);
+    (&*(interface.cb.funcs as *const Methods), interface.cb.data)
+}
+
+pub unsafe fn pw_core_add_listener(
+    core: *mut pw_core,
+    listener: *mut spa_hook,
+    events: *const pw_core_events,
+    data: *mut c_void,
+) -> c_int {
+    let (methods, object) = methods::<pw_core_methods>(core as *mut c_void);
+    (methods.add_listener)(object, listener, events, data)
+}
+
+pub unsafe fn pw_core_sync(core: *mut pw_core, id: u32, seq: c_int) -> c_int {
+    let (methods, object) = methods::<pw_core_methods>(core as *mut c_void);
+    (methods.sync)(object, id, seq)
+}
+
+pub unsafe fn pw_core_get_registry(core: *mut pw_core) -> *mut pw_registry {
+    let (methods, object) = methods::<pw_core_methods>(core as *mut c_void);
+    (methods.get_registry)(object, PW_VERSION_REGISTRY, 0)
+}
+
+/// Unregisters a listener.
+pub unsafe fn spa_hook_remove(hook: *mut spa_hook) {
+    let link = &mut (*hook).link;
+    if !link.prev.is_null() {
+        (*link.prev).next = link.next;
+        (*link.next).prev = link.prev;
+    }
+    if let Some(removed) = (*hook).removed {
+        removed(hook);
+    }
+}
+
+pub unsafe fn pw_registry_add_listener(
+    registry: *mut pw_registry,
+    listener: *mut spa_hook,
+    events: *const pw_registry_events,
+    data: *mut c_void,
+) -> c_int {
+    let (methods, object) = methods::<pw_registry_methods>(registry as *mut c_void);
+    (methods.add_listener)(object, listener, events, data)
+}
+
+#[link(name = "pipewire-0.3")]
+extern "C" {
+    pub fn pw_init(argc: *mut c_int, argv: *mut *mut *mut c_char);
+
+    pub fn pw_properties_new(key: *const c_char, ...) -> *mut pw_properties;
+    pub fn pw_properties_set(
+        properties: *mut pw_properties,
+        key: *const c_char,
+        value: *const c_char,
+    ) -> c_int;
+    pub fn pw_properties_free(properties: *mut pw_properties);
+
+    pub fn pw_main_loop_new(props: *const spa_dict) -> *mut pw_main_loop;
+    pub fn pw_main_loop_get_loop(main_loop: *mut pw_main_loop) -> *mut pw_loop;
+    pub fn pw_main_loop_run(main_loop: *mut pw_main_loop) -> c_int;
+    pub fn pw_main_loop_quit(main_loop: *mut pw_main_loop) -> c_int;
+    pub fn pw_main_loop_destroy(main_loop: *mut pw_main_loop);
+
+    pub fn pw_thread_loop_new(name: *const c_char, props: *const spa_dict) -> *mut pw_thread_loop;
+    pub fn pw_thread_loop_get_loop(thread_loop: *mut pw_thread_loop) -> *mut pw_loop;
+    pub fn pw_thread_loop_start(thread_loop: *mut pw_thread_loop) -> c_int;
+    pub fn pw_thread_loop_stop(thread_loop: *mut pw_thread_loop);
+    pub fn pw_thread_loop_destroy(thread_loop: *mut pw_thread_loop);
+    pub fn pw_thread_loop_lock(thread_loop: *mut pw_thread_loop);
+    pub fn pw_thread_loop_unlock(thread_loop: *mut pw_thread_loop);
+    pub fn pw_thread_loop_signal(thread_loop: *mut pw_thread_loop, wait_for_accept: bool);
+    pub fn pw_thread_loop_timed_wait(
+        thread_loop: *mut pw_thread_loop,
+        wait_max_sec: c_int,
+    ) -> c_int;
+
+    pub fn pw_context_new(
+        main_loop: *mut pw_loop,
+        props: *mut pw_properties,
+        user_data_size: usize,
+    ) -> *mut pw_context;
+    pub fn pw_context_connect(
+        context: *mut pw_context,
+        props: *mut pw_properties,
+        user_data_size: usize,
+    ) -> *mut pw_core;
+    pub fn pw_context_destroy(context: *mut pw_context);
+    pub fn pw_core_disconnect(core: *mut pw_core) -> c_int;
+    pub fn pw_proxy_destroy(proxy: *mut pw_proxy);
+
+    pub fn pw_stream_new_simple(
+        main_loop: *mut pw_loop,
+        name: *const c_char,
+        props: *mut pw_properties,
+        events: *const pw_stream_events,
+        data: *mut c_void,
+    ) -> *mut pw_stream;
+    pub fn pw_stream_connect(
+        stream: *mut pw_stream,
+        direction: spa_direction,
+        target_id: u32,
+        flags: pw_stream_flags,
+        params: *mut *const spa_pod,
+        n_params: u32,
+    ) -> c_int;
+    pub fn pw_stream_get_state(
+        stream: *mut pw_stream,
+        error: *mut *const c_char,
+    ) -> pw_stream_state;
+    pub fn pw_stream_set_active(stream: *mut pw_stream, active: bool) -> c_int;
+    pub fn pw_stream_dequeue_buffer(stream: *mut pw_stream) -> *mut pw_buffer;
+    pub fn pw_stream_queue_buffer(stream: *mut pw_stream, buffer: *mut pw_buffer) -> c_int;
+    pub fn pw_stream_destroy(stream: *mut pw_stream);
+}
@@ -0,0 +1,144 @@
+//! Serialization of the stream's audio format into a SPA POD, PipeWire's binary format for
+//! parameters.
+//!
+//! PODs are sequences of 8-byte aligned values, each starting with its size and type. The C API
+//! builds them with inline helpers, so only the few types the format needs are reimplemented here.
+use crate::pipewire::ffi;
+use crate::Format;
+
+/// A serialized POD, stored in 8-byte words for alignment.
+pub struct Pod(Vec<u64>);
+
+impl Pod {
+    pub fn as_ptr(&self) -> *const ffi::spa_pod {
+        self.0.as_ptr() as *const ffi::spa_pod
+    }
+}
+
+/// Builds the `EnumFormat` parameter of a raw, interleaved audio stream.
+pub fn audio_format(format: Format, sample_rate: u32, n_channels: u32) -> Pod {
+    let mut builder = ObjectBuilder::new(ffi::SPA_TYPE_OBJECT_FORMAT, ffi::SPA_PARAM_ENUM_FORMAT);
+    builder.add_id(ffi::SPA_FORMAT_MEDIA_TYPE, ffi::SPA_MEDIA_TYPE_AUDIO);
+    builder.add_id(ffi::SPA_FORMAT_MEDIA_SUBTYPE, ffi::SPA_MEDIA_SUBTYPE_RAW);
+    builder.add_id(ffi::SPA_FORMAT_AUDIO_FORMAT, audio_sample_format(format));
+    builder.add_int(ffi::SPA_FORMAT_AUDIO_RATE, sample_rate);
+    builder.add_int(ffi::SPA_FORMAT_AUDIO_CHANNELS, n_channels);
+    // Other layouts are left unpositioned, and are routed channel by channel.
+    match n_channels {
+        1 => builder.add_id_array(
+            ffi::SPA_FORMAT_AUDIO_POSITION,
+            &[ffi::SPA_AUDIO_CHANNEL_MONO],
+        ),
+        2 => builder.add_id_array(
+            ffi::SPA_FORMAT_AUDIO_POSITION,
+            &[ffi::SPA_AUDIO_CHANNEL_FL, ffi::SPA_AUDIO_CHANNEL_FR],
+        ),
+        _ => (),
+    }
+    builder.build()
+}
+
+/// Maps `format` to the native-endian SPA sample format.
+fn audio_sample_format(format: Format) -> ffi::spa_audio_format {
+    let big_endian = cfg!(target_endian = "big");
+    match format {
+        Format::F32 if big_endian => ffi::SPA_AUDIO_FORMAT_F32_BE,
+        Format::F32 => ffi::SPA_AUDIO_FORMAT_F32_LE,
+        Format::I32 if big_endian => ffi::SPA_AUDIO_FORMAT_S32_BE,
+        Format::I32 => ffi::SPA_AUDIO_FORMAT_S32_LE,
+        Format::I24 if big_endian => ffi::SPA_AUDIO_FORMAT_S24_BE,
+        Format::I24 => ffi::SPA_AUDIO_FORMAT_S24_LE,
+        Format::I16 if big_endian => ffi::SPA_AUDIO_FORMAT_S16_BE,
+        Format::I16 => ffi::SPA_AUDIO_FORMAT_S16_LE,
+        Format::I8 => ffi::SPA_AUDIO_FORMAT_S8,
+        Format::U8 => ffi::SPA_AUDIO_FORMAT_U8,
+    }
+}
+
+/// Builds a POD object out of properties, in 4-byte words.
+struct ObjectBuilder {
+    words: Vec<u32>,
+}
+
+impl ObjectBuilder {
+    fn new(object_type: u32, id: u32) -> ObjectBuilder {
+        // The size is filled in by build().
+        ObjectBuilder {
+            words: vec![0, ffi::SPA_TYPE_OBJECT, object_type, id],
+        }
+    }
+
+    fn add_id(&mut self, key: u32, value: u32) {
+        self.add_scalar(key, ffi::SPA_TYPE_ID, value);
+    }
+
+    fn add_int(&mut self, key: u32, value: u32) {
+        self.add_scalar(key, ffi::SPA_TYPE_INT, value);
+    }
+
+    fn add_scalar(&mut self, key: u32, value_type: u32, value: u32) {
+        // Key, flags, then the value's size, type, and padded value.
+        self.words
+            .extend_from_slice(&[key, 0, 4, value_type, value, 0]);
+    }
+
+    fn add_id_array(&mut self, key: u32, values: &[u32]) {
+        // The array's body starts with the size and type of its children.
+        let size = 8 + 4 * values.len() as u32;
+        self.words
+            .extend_from_slice(&[key, 0, size, ffi::SPA_TYPE_ARRAY, 4, ffi::SPA_TYPE_ID]);
+        self.words.extend_from_slice(values);
+        if self.words.len() % 2 == 1 {
+            self.words.push(0);
+        }
+    }
+
+    fn build(mut self) -> Pod {
+        // The size excludes the object's own size and type.
+        self.words[0] = 4 * (self.words.len() as u32 - 2);
+        let mut pod = vec![0_u64; self.words.len() / 2];
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                self.words.as_ptr(),
+                pod.as_mut_ptr() as *mut u32,
+                self.words.len(),
+            );
+        }
+        Pod(pod)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(pod: &Pod) -> &[u32] {
+        unsafe { std::slice::from_raw_parts(pod.0.as_ptr() as *const u32, pod.0.len() * 2) }
+    }
+
+    #[test]
+    fn serializes_stereo_format() {
+        let pod = audio_format(Format::I16, 44100, 2);
+        let words = words(&pod);
+        assert_eq!(words.len() % 2, 0);
+        assert_eq!(words[0] as usize, 4 * (words.len() - 2));
+        assert_eq!(&words[1..4], &[15, 0x40003, 3]);
+        // The sample rate.
+        assert_eq!(&words[22..28], &[0x10003, 0, 4, 4, 44100, 0]);
+        // The channel positions.
+        assert_eq!(&words[34..], &[0x10005, 0, 16, 13, 4, 3, 3, 4]);
+    }
+
+    #[test]
+    fn pads_mono_positions() {
+        let pod = audio_format(Format::F32, 48000, 1);
+        let words = words(&pod);
+        assert_eq!(&words[34..], &[0x10005, 0, 12, 13, 4, 3, 2, 0]);
+    }
+
+    #[test]
+    fn leaves_surround_unpositioned() {
+        let pod = audio_format(Format::F32, 48000, 6);
+        assert_eq!(words(&pod).len(), 34);
+    }
+}
@@ -0,0 +1,241 @@
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::pipewire::device::{Device, NodeInfo};
+use crate::pipewire::{ffi, init, PipeWireOptions};
+use crate::stream_options::Direction;
+
+/// A host of the PipeWire backend.
+///
+/// The host does not keep a connection to the daemon: every query connects anew, so that results
+/// always reflect the daemon's current graph.
+#[derive(Clone)]
+pub struct Host(Arc<PipeWireOptions>);
+
+impl Host {
+    /// Creates a host, checking that the daemon is reachable.
+    pub fn new(options: PipeWireOptions) -> Result<Host> {
+        Connection::new(&options)?;
+        Ok(Host(Arc::new(options)))
+    }
+
+    pub fn name(&self) -> &str {
+        "PipeWire"
+    }
+
+    /// Returns the daemon's audio sinks and sources, in registration order.
+    pub fn devices(&self) -> Result<Vec<Device>> {
+        Ok(Connection::new(&self.0)?
+            .nodes()?
+            .into_iter()
+            .map(|info| Device::new(Arc::clone(&self.0), info, false))
+            .collect())
+    }
+
+    pub fn default_output_device(&self) -> Result<Device> {
+        self.default_device(Direction::Output)
+    }
+
+    pub fn default_input_device(&self) -> Result<Device> {
+        self.default_device(Direction::Input)
+    }
+
+    /// Returns the node the session manager would pick by default, i.e. the one with the highest
+    /// session priority. Streams opened on it are left for the session manager to route.
+    fn default_device(&self, direction: Direction) -> Result<Device> {
+        let info = Connection::new(&self.0)?
+            .nodes()?
+            .into_iter()
+            .filter(|info| info.direction == direction)
+            .fold(None, |best: Option<NodeInfo>, info| match best {
+                Some(best) if best.priority >= info.priority => Some(best),
+                _ => Some(info),
+            })
+            .ok_or(Error::NoSuchDevice)?;
+        Ok(Device::new(Arc::clone(&self.0), info, true))
+    }
+}
+
+/// A short-lived connection to the daemon, for queries.
+struct Connection {
+    main_loop: *mut ffi::pw_main_loop,
+    context: *mut ffi::pw_context,
+    core: *mut ffi::pw_core,
+}
+
+/// The state of a registry query, shared with its callbacks.
+struct Query {
+    main_loop: *mut ffi::pw_main_loop,
+    pending: c_int,
+    failed: bool,
+    nodes: Vec<NodeInfo>,
+}
+
+impl Connection {
+    fn new(options: &PipeWireOptions) -> Result<Connection> {
+        init();
+        let properties = options.connection_properties()?;
+        let main_loop = unsafe { ffi::pw_main_loop_new(ptr::null()) };
+        if main_loop.is_null() {
+            return Err(Error::OutOfMemory);
+        }
+        let context = unsafe {
+            ffi::pw_context_new(ffi::pw_main_loop_get_loop(main_loop), ptr::null_mut(), 0)
+        };
+        // From now on, Drop cleans up.
+        let mut connection = Connection {
+            main_loop,
+            context,
+            core: ptr::null_mut(),
+        };
+        if context.is_null() {
+            return Err(Error::OutOfMemory);
+        }
+        connection.core = unsafe { ffi::pw_context_connect(context, properties.into_raw(), 0) };
+        if connection.core.is_null() {
+            return Err(Error::BackendUnavailable);
+        }
+        Ok(connection)
+    }
+
+    /// Lists the audio nodes, by doing a round trip to the daemon while listening to the
+    /// registry.
+    fn nodes(&self) -> Result<Vec<NodeInfo>> {
+        extern "C" fn global(
+            data: *mut c_void,
+            _id: u32,
+            _permissions: u32,
+            type_: *const c_char,
+            _version: u32,
+            props: *const ffi::spa_dict,
+        ) {
+            let query = unsafe { &mut *(data as *mut Query) };
+            let is_node =
+                unsafe { CStr::from_ptr(type_) }.to_bytes_with_nul() == ffi::PW_TYPE_INTERFACE_NODE;
+            if let (true, Some(props)) = (is_node, unsafe { props.as_ref() }) {
+                query.nodes.extend(NodeInfo::from_props(props));
+            }
+        }
+        extern "C" fn done(data: *mut c_void, id: u32, seq: c_int) {
+            let query = unsafe { &mut *(data as *mut Query) };
+            if id == ffi::PW_ID_CORE && seq == query.pending {
+                unsafe { ffi::pw_main_loop_quit(query.main_loop) };
+            }
+        }
+        extern "C" fn error(
+            data: *mut c_void,
+            id: u32,
+            _seq: c_int,
+            _res: c_int,
+            _message: *const c_char,
+        ) {
+            let query = unsafe { &mut *(data as *mut Query) };
+            if id == ffi::PW_ID_CORE {
+                query.failed = true;
+                unsafe { ffi::pw_main_loop_quit(query.main_loop) };
+            }
+        }
+        let registry_events = ffi::pw_registry_events {
+            version: ffi::PW_VERSION_REGISTRY_EVENTS,
+            global: Some(global),
+            global_remove: None,
+        };
+        let core_events = ffi::pw_core_events {
+            version: ffi::PW_VERSION_CORE_EVENTS,
+            info: None,
+            done: Some(done),
+            ping: None,
+            error: Some(error),
+            remove_id: None,
+            bound_id: None,
+            add_mem: None,
+            remove_mem: None,
+        };
+
+        let registry = unsafe { ffi::pw_core_get_registry(self.core) };
+        if registry.is_null() {
+            return Err(Error::OutOfMemory);
+        }
+        let mut query = Query {
+            main_loop: self.main_loop,
+            pending: 0,
+            failed: false,
+            nodes: Vec::new(),
+        };
+        let data = &mut query as *mut Query as *mut c_void;
+        let mut registry_hook = ffi::spa_hook::zeroed();
+        let mut core_hook = ffi::spa_hook::zeroed();
+        unsafe {
+            ffi::pw_registry_add_listener(registry, &mut registry_hook, &registry_events, data);
+            ffi::pw_core_add_listener(self.core, &mut core_hook, &core_events, data);
+            query.pending = ffi::pw_core_sync(self.core, ffi::PW_ID_CORE, 0);
+            ffi::pw_main_loop_run(self.main_loop);
+            // The hooks live on this stack frame, which is gone once the server confirms that the
+            // registry was destroyed.
+            ffi::spa_hook_remove(&mut registry_hook);
+            ffi::spa_hook_remove(&mut core_hook);
+            ffi::pw_proxy_destroy(registry as *mut ffi::pw_proxy);
+        }
+        if query.failed {
+            return Err(Error::BackendUnavailable);
+        }
+        Ok(query.nodes)
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.core.is_null() {
+                ffi::pw_core_disconnect(self.core);
+            }
+            if !self.context.is_null() {
+                ffi::pw_context_destroy(self.context);
+            }
+            ffi::pw_main_loop_destroy(self.main_loop);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipewire::test_daemon::{TestDaemon, SINK_DESCRIPTION};
+
+    #[test]
+    fn errors_if_daemon_unavailable() {
+        let result = Host::new(PipeWireOptions {
+            remote: Some("/nonexistent/audiohal/pipewire-0".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(result.err(), Some(Error::BackendUnavailable));
+    }
+
+    #[test]
+    #[ignore = "needs pipewire"]
+    fn enumerates_nodes() -> Result<()> {
+        let daemon = TestDaemon::spawn("enumerate");
+        let host = Host::new(daemon.options())?;
+        let names: Vec<_> = host
+            .devices()?
+            .iter()
+            .map(|device| device.name().to_string())
+            .collect();
+        // The dummy driver is not an audio node.
+        assert_eq!(names, vec![SINK_DESCRIPTION.to_string()]);
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs pipewire"]
+    fn picks_default_sink() -> Result<()> {
+        let daemon = TestDaemon::spawn("default");
+        let host = Host::new(daemon.options())?;
+        assert_eq!(host.default_output_device()?.name(), SINK_DESCRIPTION);
+        assert_eq!(host.default_input_device().err(), Some(Error::NoSuchDevice));
+        Ok(())
+    }
+}
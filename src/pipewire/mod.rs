@@ -0,0 +1,178 @@
+//! A native PipeWire backend, talking to the PipeWire daemon through libpipewire instead of going
+//! through PortAudio. Requires the `native-pipewire` feature.
+//!
+//! Devices are the daemon's audio nodes: sinks are outputs, and sources are inputs. Linking
+//! streams to nodes is left to the session manager (e.g. WirePlumber), which also moves streams
+//! opened on the default devices whenever the defaults change.
+use std::ffi::CString;
+use std::sync::Once;
+
+use crate::error::{Error, Result};
+
+mod device;
+mod ffi;
+mod format;
+mod host;
+mod stream;
+
+// Public API exports.
+pub use device::Device;
+pub use host::Host;
+pub use stream::Stream;
+
+/// Configures a host of the PipeWire backend.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PipeWireOptions {
+    /// The name the daemon shows for the application (e.g. in patchbays and volume controls).
+    pub application_name: String,
+    /// The daemon to connect to, either a socket name in the runtime directory (e.g.
+    /// `pipewire-0`) or an absolute socket path. If `None`, the default daemon is used.
+    pub remote: Option<String>,
+}
+
+impl Default for PipeWireOptions {
+    fn default() -> PipeWireOptions {
+        PipeWireOptions {
+            application_name: "audiohal".to_string(),
+            remote: None,
+        }
+    }
+}
+
+impl PipeWireOptions {
+    /// Returns the properties of a connection to the daemon.
+    fn connection_properties(&self) -> Result<Properties> {
+        let mut properties = Properties::new()?;
+        properties.set("application.name", &self.application_name)?;
+        if let Some(remote) = &self.remote {
+            properties.set("remote.name", remote)?;
+        }
+        Ok(properties)
+    }
+}
+
+/// Initializes libpipewire, once per process.
+fn init() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| unsafe { ffi::pw_init(std::ptr::null_mut(), std::ptr::null_mut()) });
+}
+
+fn to_c_string(string: &str) -> Result<CString> {
+    CString::new(string).or(Err(Error::Invalid))
+}
+
+/// An owned `pw_properties`, until it is handed over to libpipewire.
+pub struct Properties(*mut ffi::pw_properties);
+
+impl Properties {
+    fn new() -> Result<Properties> {
+        let properties = unsafe { ffi::pw_properties_new(std::ptr::null()) };
+        if properties.is_null() {
+            return Err(Error::OutOfMemory);
+        }
+        Ok(Properties(properties))
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let key = to_c_string(key)?;
+        let value = to_c_string(value)?;
+        unsafe { ffi::pw_properties_set(self.0, key.as_ptr(), value.as_ptr()) };
+        Ok(())
+    }
+
+    /// Releases ownership, to a libpipewire function that takes it.
+    fn into_raw(self) -> *mut ffi::pw_properties {
+        let properties = self.0;
+        std::mem::forget(self);
+        properties
+    }
+}
+
+impl Drop for Properties {
+    fn drop(&mut self) {
+        unsafe { ffi::pw_properties_free(self.0) };
+    }
+}
+
+/// A private PipeWire daemon with a dummy driver and a single null sink, for tests.
+#[cfg(test)]
+pub mod test_daemon {
+    use super::PipeWireOptions;
+    use crate::test_daemon::Daemon;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    pub const SINK_DESCRIPTION: &str = "Test-Sink";
+
+    /// Loads only what streams need, and creates a dummy driver so that the graph runs without
+    /// audio hardware.
+    const CONFIG: &str = r"
+context.properties = {
+    support.dbus = false
+    default.clock.rate = 48000
+}
+context.spa-libs = {
+    audio.convert.* = audioconvert/libspa-audioconvert
+    support.*       = support/libspa-support
+}
+context.modules = [
+    { name = libpipewire-module-protocol-native }
+    { name = libpipewire-module-client-node }
+    { name = libpipewire-module-adapter }
+    { name = libpipewire-module-spa-node-factory }
+]
+context.objects = [
+    { factory = spa-node-factory
+        args = {
+            factory.name    = support.node.driver
+            node.name       = Dummy-Driver
+            priority.driver = 20000
+        }
+    }
+    { factory = adapter
+        args = {
+            factory.name     = support.null-audio-sink
+            node.name        = audiohal_test
+            node.description = Test-Sink
+            media.class      = Audio/Sink
+            audio.position   = [ FL FR ]
+        }
+    }
+]
+";
+
+    pub struct TestDaemon {
+        runtime_dir: PathBuf,
+        _daemon: Daemon,
+    }
+
+    impl TestDaemon {
+        /// Spawns `pipewire` with a minimal configuration.
+        pub fn spawn(name: &str) -> TestDaemon {
+            let runtime_dir = Daemon::runtime_dir("pipewire", name);
+            let config = runtime_dir.join("pipewire.conf");
+            std::fs::write(&config, CONFIG).unwrap();
+            let socket = runtime_dir.join("pipewire-0");
+            let daemon = Daemon::spawn(
+                Command::new("pipewire")
+                    .arg("-c")
+                    .arg(&config)
+                    .env("PIPEWIRE_RUNTIME_DIR", &runtime_dir)
+                    .env("XDG_RUNTIME_DIR", &runtime_dir),
+                Some(&runtime_dir),
+                || socket.exists(),
+            );
+            TestDaemon {
+                runtime_dir,
+                _daemon: daemon,
+            }
+        }
+
+        pub fn options(&self) -> PipeWireOptions {
+            PipeWireOptions {
+                remote: Some(self.runtime_dir.join("pipewire-0").display().to_string()),
+                ..Default::default()
+            }
+        }
+    }
+}
@@ -0,0 +1,366 @@
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::pipewire::format::Pod;
+use crate::pipewire::{ffi, init, to_c_string, Properties};
+use crate::stream::StreamState;
+use crate::stream_options::{Callback, Direction};
+
+/// How long to wait for the daemon to create the stream's node.
+const CONNECT_TIMEOUT_SECS: i32 = 5;
+
+/// The state shared with the stream's callbacks, which run on the loop thread.
+struct StreamData<Frame> {
+    thread_loop: *mut ffi::pw_thread_loop,
+    stream: *mut ffi::pw_stream,
+    callback: Callback<Frame>,
+    direction: Direction,
+    /// The requested quantum, used when the graph does not tell how many frames it wants.
+    frames_per_buffer: usize,
+    /// Frames are copied through this buffer, as mapped buffers are not guaranteed to be aligned.
+    buffer: Vec<Frame>,
+}
+
+impl<Frame> StreamData<Frame>
+where
+    Frame: sample::Frame,
+{
+    fn process(&mut self, data: &mut ffi::spa_data, requested: u64) {
+        let frame_size = std::mem::size_of::<Frame>();
+        let max_frames = data.maxsize as usize / frame_size;
+        let chunk = unsafe { &mut *data.chunk };
+        let n_frames = if self.direction == Direction::Input {
+            let offset = (chunk.offset as usize).min(data.maxsize as usize);
+            let size = (chunk.size as usize).min(data.maxsize as usize - offset);
+            let n_frames = size / frame_size;
+            self.resize(n_frames);
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    (data.data as *const u8).add(offset),
+                    self.buffer.as_mut_ptr() as *mut u8,
+                    n_frames * frame_size,
+                );
+            }
+            (self.callback)(&mut self.buffer[..n_frames]);
+            n_frames
+        } else {
+            let n_frames = match requested {
+                0 => self.frames_per_buffer,
+                requested => requested as usize,
+            }
+            .min(max_frames);
+            self.resize(n_frames);
+            (self.callback)(&mut self.buffer[..n_frames]);
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    self.buffer.as_ptr() as *const u8,
+                    data.data as *mut u8,
+                    n_frames * frame_size,
+                );
+            }
+            chunk.offset = 0;
+            chunk.stride = frame_size as i32;
+            chunk.size = (n_frames * frame_size) as u32;
+            n_frames
+        };
+        debug_assert!(n_frames <= max_frames);
+    }
+
+    /// Only allocates if the graph's quantum grew.
+    fn resize(&mut self, n_frames: usize) {
+        if self.buffer.len() < n_frames {
+            self.buffer.resize(n_frames, Frame::equilibrium());
+        }
+    }
+}
+
+extern "C" fn on_state_changed<Frame>(
+    data: *mut c_void,
+    _old: ffi::pw_stream_state,
+    _state: ffi::pw_stream_state,
+    _error: *const c_char,
+) {
+    let data = unsafe { &*(data as *const StreamData<Frame>) };
+    // Wakes up Stream::new, if it is waiting for the connection.
+    unsafe { ffi::pw_thread_loop_signal(data.thread_loop, false) };
+}
+
+extern "C" fn on_process<Frame>(data: *mut c_void)
+where
+    Frame: sample::Frame,
+{
+    let data = unsafe { &mut *(data as *mut StreamData<Frame>) };
+    let buffer = unsafe { ffi::pw_stream_dequeue_buffer(data.stream) };
+    if buffer.is_null() {
+        return;
+    }
+    unsafe {
+        let spa_buffer = &*(*buffer).buffer;
+        if spa_buffer.n_datas > 0 && !(*spa_buffer.datas).data.is_null() {
+            data.process(&mut *spa_buffer.datas, (*buffer).requested);
+        }
+        ffi::pw_stream_queue_buffer(data.stream, buffer);
+    }
+}
+
+/// A stream of the PipeWire backend, processed on its own loop thread.
+pub struct Stream<Frame> {
+    thread_loop: *mut ffi::pw_thread_loop,
+    stream: *mut ffi::pw_stream,
+    // Both are referenced by the stream, and must outlive it.
+    events: Box<ffi::pw_stream_events>,
+    data: Box<StreamData<Frame>>,
+    latency: Duration,
+    started: bool,
+}
+
+// The stream is only accessed with the loop's lock held.
+unsafe impl<Frame: Send> Send for Stream<Frame> {}
+
+impl<Frame> Stream<Frame> {
+    /// Creates the stream's node, inactive until started.
+    pub fn new(
+        name: &str,
+        properties: Properties,
+        format: &Pod,
+        callback: Callback<Frame>,
+        direction: Direction,
+        frames_per_buffer: usize,
+        latency: Duration,
+    ) -> Result<Stream<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        init();
+        let name = to_c_string(name)?;
+        let thread_loop = unsafe {
+            ffi::pw_thread_loop_new(
+                b"audiohal-pipewire\0".as_ptr() as *const c_char,
+                ptr::null(),
+            )
+        };
+        if thread_loop.is_null() {
+            return Err(Error::OutOfMemory);
+        }
+        let mut stream = Stream {
+            thread_loop,
+            stream: ptr::null_mut(),
+            events: Box::new(ffi::pw_stream_events {
+                version: ffi::PW_VERSION_STREAM_EVENTS,
+                destroy: None,
+                state_changed: Some(on_state_changed::<Frame>),
+                control_info: None,
+                io_changed: None,
+                param_changed: None,
+                add_buffer: None,
+                remove_buffer: None,
+                process: Some(on_process::<Frame>),
+                drained: None,
+                command: None,
+                trigger_done: None,
+            }),
+            data: Box::new(StreamData {
+                thread_loop,
+                stream: ptr::null_mut(),
+                callback,
+                direction,
+                frames_per_buffer,
+                buffer: Vec::with_capacity(frames_per_buffer),
+            }),
+            latency,
+            started: false,
+        };
+        // From now on, Drop cleans up.
+        if unsafe { ffi::pw_thread_loop_start(thread_loop) } < 0 {
+            return Err(Error::Unknown("Could not start the PipeWire loop thread."));
+        }
+        unsafe { ffi::pw_thread_loop_lock(thread_loop) };
+        let result = stream.connect(&name, properties, format);
+        unsafe { ffi::pw_thread_loop_unlock(thread_loop) };
+        result?;
+        Ok(stream)
+    }
+
+    /// Connects the stream, and waits for its node to be created. Must be called with the loop's
+    /// lock held.
+    fn connect(&mut self, name: &CStr, properties: Properties, format: &Pod) -> Result<()> {
+        let data = &mut *self.data as *mut StreamData<Frame> as *mut c_void;
+        self.stream = unsafe {
+            ffi::pw_stream_new_simple(
+                ffi::pw_thread_loop_get_loop(self.thread_loop),
+                name.as_ptr(),
+                properties.into_raw(),
+                &*self.events,
+                data,
+            )
+        };
+        if self.stream.is_null() {
+            return Err(Error::BackendUnavailable);
+        }
+        self.data.stream = self.stream;
+        let direction = match self.data.direction {
+            Direction::Input => ffi::SPA_DIRECTION_INPUT,
+            _ => ffi::SPA_DIRECTION_OUTPUT,
+        };
+        let mut params = [format.as_ptr()];
+        let result = unsafe {
+            ffi::pw_stream_connect(
+                self.stream,
+                direction,
+                ffi::PW_ID_ANY,
+                ffi::PW_STREAM_FLAG_AUTOCONNECT
+                    | ffi::PW_STREAM_FLAG_INACTIVE
+                    | ffi::PW_STREAM_FLAG_MAP_BUFFERS
+                    | ffi::PW_STREAM_FLAG_RT_PROCESS,
+                params.as_mut_ptr(),
+                params.len() as u32,
+            )
+        };
+        if result < 0 {
+            return Err(Error::Unknown("Could not connect the PipeWire stream."));
+        }
+        loop {
+            match unsafe { ffi::pw_stream_get_state(self.stream, ptr::null_mut()) } {
+                ffi::PW_STREAM_STATE_ERROR => {
+                    return Err(Error::Unknown("The PipeWire daemon rejected the stream."))
+                }
+                ffi::PW_STREAM_STATE_UNCONNECTED => return Err(Error::BackendUnavailable),
+                ffi::PW_STREAM_STATE_CONNECTING => {
+                    if unsafe {
+                        ffi::pw_thread_loop_timed_wait(self.thread_loop, CONNECT_TIMEOUT_SECS)
+                    } != 0
+                    {
+                        return Err(Error::BackendUnavailable);
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    /// Stream is inactive (i.e. no callback) until this method is called.
+    pub fn start(&mut self) -> Result<()> {
+        if self.started {
+            return Err(Error::StreamAlreadyStarted);
+        }
+        let result = unsafe {
+            ffi::pw_thread_loop_lock(self.thread_loop);
+            let result = ffi::pw_stream_set_active(self.stream, true);
+            ffi::pw_thread_loop_unlock(self.thread_loop);
+            result
+        };
+        if result < 0 {
+            return Err(Error::Unknown("Could not activate the PipeWire stream."));
+        }
+        self.started = true;
+        Ok(())
+    }
+
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    pub fn is_active(&self) -> bool {
+        if !self.started {
+            return false;
+        }
+        let state = unsafe {
+            ffi::pw_thread_loop_lock(self.thread_loop);
+            let state = ffi::pw_stream_get_state(self.stream, ptr::null_mut());
+            ffi::pw_thread_loop_unlock(self.thread_loop);
+            state
+        };
+        state != ffi::PW_STREAM_STATE_ERROR && state != ffi::PW_STREAM_STATE_UNCONNECTED
+    }
+
+    pub fn state(&self) -> StreamState {
+        if !self.started {
+            StreamState::Stopped
+        } else if self.is_active() {
+            StreamState::Active
+        } else {
+            StreamState::Disconnected
+        }
+    }
+
+    pub fn close(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<Frame> Drop for Stream<Frame> {
+    fn drop(&mut self) {
+        unsafe {
+            // Once the loop thread is stopped, callbacks cannot run anymore.
+            ffi::pw_thread_loop_stop(self.thread_loop);
+            if !self.stream.is_null() {
+                ffi::pw_stream_destroy(self.stream);
+            }
+            ffi::pw_thread_loop_destroy(self.thread_loop);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pipewire::test_daemon::TestDaemon;
+    use crate::pipewire::Host;
+    use crate::StreamOptions;
+    use std::sync::mpsc;
+
+    /// Without a session manager, nothing links test streams to the sink. Always-process nodes
+    /// are scheduled by the dummy driver regardless.
+    fn always_process() -> Vec<(String, String)> {
+        vec![("node.always-process".to_string(), "true".to_string())]
+    }
+
+    #[test]
+    fn stream_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Stream<[f32; 2]>>();
+    }
+
+    #[test]
+    #[ignore = "needs pipewire"]
+    fn plays_with_requested_quantum() -> Result<()> {
+        let daemon = TestDaemon::spawn("play");
+        let (sender, receiver) = mpsc::channel();
+        let mut stream = Host::new(daemon.options())?
+            .default_output_device()?
+            .open_outstream(StreamOptions {
+                callback: Box::new(move |buffer: &mut [[f32; 2]]| {
+                    let _ = sender.send(buffer.len());
+                }),
+                frames_per_buffer: Some(256),
+                name: Some("audiohal test".to_string()),
+                properties: always_process(),
+                ..Default::default()
+            })?;
+        assert!(!stream.is_active());
+        stream.start()?;
+        for _ in 0..4 {
+            assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), Ok(256));
+        }
+        assert!(stream.is_active());
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs pipewire"]
+    fn reports_quantum_latency() -> Result<()> {
+        let daemon = TestDaemon::spawn("latency");
+        let stream = Host::new(daemon.options())?
+            .default_output_device()?
+            .open_outstream(StreamOptions::<[i16; 2]> {
+                frames_per_buffer: Some(480),
+                properties: always_process(),
+                ..Default::default()
+            })?;
+        assert_eq!(stream.latency(), Duration::from_millis(10));
+        Ok(())
+    }
+}
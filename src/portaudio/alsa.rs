@@ -0,0 +1,137 @@
+//! ALSA-specific extensions of the PortAudio ALSA host API.
+use libportaudio_sys as ffi;
+use std::os::raw::c_int;
+
+use crate::alsa::AlsaDeviceKind;
+use crate::error::{Error, Result};
+use crate::portaudio::error::PaErrorAsResult as _;
+use crate::portaudio::LockGuard;
+use crate::stream_options::Direction;
+
+/// PortAudio's number of periods, unless set otherwise.
+pub const DEFAULT_N_PERIODS: i32 = 4;
+
+/// Classifies a device from the name PortAudio gives it. Hardware devices are named after their
+/// card, followed by their `hw:` name (e.g. "HDA Intel PCH: ALC892 Analog (hw:0,0)"), while
+/// plugins are named after their PCM (e.g. "dmix").
+pub fn device_kind(name: &str) -> AlsaDeviceKind {
+    let hardware = name
+        .rfind("(hw:")
+        .filter(|_| name.ends_with(')'))
+        .and_then(|start| {
+            let mut numbers = name[start + 4..name.len() - 1].split(',');
+            let card = numbers.next()?.parse().ok()?;
+            let device = numbers.next()?.parse().ok()?;
+            match numbers.next() {
+                None => Some(AlsaDeviceKind::Hardware { card, device }),
+                Some(_) => None,
+            }
+        });
+    hardware.unwrap_or_else(|| AlsaDeviceKind::Plugin {
+        name: name.to_string(),
+    })
+}
+
+/// Sets the number of periods of the streams opened next.
+pub fn set_n_periods(n_periods: i32, _guard: &LockGuard) -> Result<()> {
+    unsafe { ffi::alsa::PaAlsa_SetNumPeriods(n_periods) }
+        .as_result()
+        .and(Ok(()))
+}
+
+/// Expects the stream to be borrowed mutably, so that no other call is made on it.
+pub fn enable_realtime_scheduling(stream: *mut ffi::PaStream, enable: bool) {
+    unsafe { ffi::alsa::PaAlsa_EnableRealtimeScheduling(stream, c_int::from(enable)) };
+}
+
+pub fn card(stream: *mut ffi::PaStream, direction: Direction) -> Result<i32> {
+    let mut card: c_int = -1;
+    match direction {
+        Direction::Input => unsafe { ffi::alsa::PaAlsa_GetStreamInputCard(stream, &mut card) },
+        _ => unsafe { ffi::alsa::PaAlsa_GetStreamOutputCard(stream, &mut card) },
+    }
+    .as_result()?;
+    // Plugins that are not backed by a single card (e.g. "null") have none.
+    if card < 0 {
+        return Err(Error::NoSuchDevice);
+    }
+    Ok(card)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alsa::{DeviceExt as _, StreamExt as _};
+    use crate::portaudio::test_prelude::*;
+    use crate::Backend;
+
+    #[test]
+    fn classifies_hardware_devices() {
+        assert_eq!(
+            device_kind("HDA Intel PCH: ALC892 Analog (hw:0,0)"),
+            AlsaDeviceKind::Hardware { card: 0, device: 0 }
+        );
+        assert_eq!(
+            device_kind("USB Audio (Interface): - (hw:2,1)"),
+            AlsaDeviceKind::Hardware { card: 2, device: 1 }
+        );
+    }
+
+    #[test]
+    fn classifies_plugins() {
+        for name in &[
+            "default",
+            "dmix",
+            "sysdefault",
+            "pulse",
+            "front",
+            "(hw:0)",
+            "(hw:a,b)",
+        ] {
+            assert_eq!(
+                device_kind(name),
+                AlsaDeviceKind::Plugin {
+                    name: name.to_string()
+                }
+            );
+        }
+    }
+
+    #[test]
+    #[ignore = "needs an ALSA hardware output device"]
+    fn reports_stream_card() -> Result<()> {
+        begin!();
+        let mut host = crate::Host::with_backend(Backend::Alsa)?;
+        for mut device in host.devices()? {
+            let card = match device.alsa_kind()? {
+                AlsaDeviceKind::Hardware { card, .. } => card,
+                _ => continue,
+            };
+            device.set_alsa_n_periods(2)?;
+            // Skips input-only and busy devices.
+            let mut stream = match device.open_outstream(StreamOptions::<[f32; 2]>::default()) {
+                Ok(stream) => stream,
+                Err(_) => continue,
+            };
+            assert_eq!(stream.alsa_card()?, card);
+            stream.enable_alsa_realtime_scheduling(true)?;
+            stream.start()?;
+            assert_eq!(
+                stream.enable_alsa_realtime_scheduling(false),
+                Err(Error::StreamAlreadyStarted)
+            );
+            return Ok(());
+        }
+        panic!("No ALSA hardware output device.");
+    }
+
+    #[test]
+    #[ignore = "needs ALSA"]
+    fn rejects_invalid_n_periods() -> Result<()> {
+        begin!();
+        let mut host = crate::Host::with_backend(Backend::Alsa)?;
+        let mut device = host.default_output_device()?;
+        assert_eq!(device.set_alsa_n_periods(1), Err(Error::Invalid));
+        Ok(())
+    }
+}
@@ -1,14 +1,25 @@
 use std::sync::Arc;
 
+#[cfg(all(feature = "alsa", target_os = "linux"))]
+use crate::alsa::AlsaDeviceKind;
+#[cfg(any(all(feature = "alsa", target_os = "linux"), feature = "oss"))]
+use crate::error::Error;
 use crate::error::Result;
-use crate::portaudio::host::HostHandle;
+#[cfg(all(feature = "alsa", target_os = "linux"))]
+use crate::portaudio::alsa;
+use crate::portaudio::host::{Host, HostHandle};
+use crate::portaudio::stream::Stream;
 use crate::portaudio::LockGuard;
 use crate::stream_options::StreamOptions;
-use crate::Stream;
+use crate::DeviceId;
+#[cfg(any(all(feature = "alsa", target_os = "linux"), feature = "oss"))]
+use libportaudio_sys as ffi;
 
 use crate::portaudio::internal::device as internal;
 
 pub type DeviceHandle = Arc<internal::Device>;
+
+/// A Portaudio device.
 pub struct Device(DeviceHandle);
 
 impl Device {
@@ -17,38 +28,70 @@ impl Device {
         &self.0.name
     }
 
+    pub fn id(&self) -> DeviceId {
+        self.0.id()
+    }
+
+    /// Returns the device's host.
+    pub fn host(&self) -> Host {
+        Host::from(Arc::clone(self.0.host()))
+    }
+
     /// Creates an output stream.
-    ///
-    /// `Frame` is the stream's frame type, and is inferred from the stream callback.
-    ///
-    /// Output streams stream digital audio (in the form of frames) to a system's output device.
-    /// The callback in  [`StreamOptions`] is called multiple times per second (depending on how you
-    /// setup frames_per_buffer) in order to satisfy the requested sample-rate. See
-    /// [`Stream`] for more details.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// # use audiohal::*;
-    /// fn callback(buffer: &mut [[f32; 2]]) {
-    ///     # buffer;
-    /// }
-    /// let mut device = Host::with_default_backend()?.default_output_device()?;
-    /// let stream = device.open_outstream(
-    ///     StreamOptions {
-    ///         callback: Box::new(callback),
-    ///         // The rest of the parameters will be set to device defaults.
-    ///         ..Default::default()
-    ///     });
-    /// assert!(stream.is_ok());
-    /// # Result::Ok(())
-    /// ```
-    pub fn open_outstream<Frame>(
-        &mut self,
-        options: StreamOptions<Frame>,
-    ) -> Result<Stream<Frame>> {
+    pub fn open_outstream<Frame>(&mut self, options: StreamOptions<Frame>) -> Result<Stream<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
         self.0.open_outstream(options, Arc::clone(&self.0))
     }
+
+    /// Creates an input stream.
+    pub fn open_instream<Frame>(&mut self, options: StreamOptions<Frame>) -> Result<Stream<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        self.0.open_instream(options, Arc::clone(&self.0))
+    }
+
+    /// Classifies the device as a hardware device or a plugin. Returns [`Error::WrongBackend`] if
+    /// the device does not belong to the ALSA host API.
+    #[cfg(all(feature = "alsa", target_os = "linux"))]
+    pub fn alsa_kind(&self) -> Result<AlsaDeviceKind> {
+        self.check_alsa()?;
+        Ok(alsa::device_kind(&self.0.name))
+    }
+
+    /// Sets the number of periods of the buffers of streams opened afterwards.
+    #[cfg(all(feature = "alsa", target_os = "linux"))]
+    pub fn set_alsa_n_periods(&mut self, n_periods: i32) -> Result<()> {
+        self.check_alsa()?;
+        // The buffer must at least be double-buffered.
+        if n_periods < 2 {
+            return Err(Error::Invalid);
+        }
+        self.0.set_alsa_n_periods(n_periods);
+        Ok(())
+    }
+
+    /// Returns the path of the device's node. Returns [`Error::WrongBackend`] if the device does
+    /// not belong to the OSS host API.
+    #[cfg(feature = "oss")]
+    pub fn oss_path(&self) -> Result<std::path::PathBuf> {
+        if !self.0.host().is(ffi::PaHostApiTypeId::paOSS) {
+            return Err(Error::WrongBackend);
+        }
+        // PortAudio names OSS devices after their node.
+        Ok(self.0.name.clone().into())
+    }
+
+    #[cfg(all(feature = "alsa", target_os = "linux"))]
+    fn check_alsa(&self) -> Result<()> {
+        if self.0.host().is(ffi::PaHostApiTypeId::paALSA) {
+            Ok(())
+        } else {
+            Err(Error::WrongBackend)
+        }
+    }
 }
 
 pub fn from_device_index(
@@ -64,6 +107,7 @@ pub fn from_device_index(
 #[cfg(test)]
 mod tests {
     use crate::portaudio::test_prelude::*;
+    use crate::portaudio::{Device, Host};
 
     #[test]
     fn device_is_send() {
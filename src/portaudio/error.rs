@@ -1,7 +1,9 @@
 use libportaudio_sys as ffi;
-use std::os::raw::c_int;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
 
 use crate::error::{Error, Result};
+use crate::portaudio::global_lock;
 
 impl From<ffi::PaErrorCode> for Error {
     fn from(error: ffi::PaErrorCode) -> Error {
@@ -15,10 +17,33 @@ impl From<ffi::PaErrorCode> for Error {
             paHostApiNotFound => BackendUnavailable,
             paInvalidSampleRate => IncompatibleSampleRate,
             paInvalidChannelCount => IncompatibleNChannels,
+            paInvalidFlag => InvalidFlags,
+            paInvalidDevice | paDeviceUnavailable => NoSuchDevice,
+            paIncompatibleStreamHostApi | paIncompatibleHostApiSpecificStreamInfo => WrongBackend,
+            paBadIODeviceCombination => Invalid,
+            paBufferTooBig | paBufferTooSmall => InvalidFramesPerBuffer,
+            paStreamIsNotStopped => StreamAlreadyStarted,
+            paTimedOut => TimedOut,
+            // Formats are checked before opening streams, so this should only happen if the device
+            // changed in the meantime.
+            paSampleFormatNotSupported => Unknown("Portaudio sample format not supported."),
             // Not actually sure how to handle paNotInitialized. Should never happen
             // under normal circumstances.
             paNotInitialized => Unknown("Portaudio not initialized."),
-            _ => panic!("Figure out error mapping for {:?}", error),
+            paUnanticipatedHostError => last_host_error(),
+            // Only returned by the blocking API, which is not used.
+            paInputOverflowed
+            | paOutputUnderflowed
+            | paCanNotReadFromACallbackStream
+            | paCanNotWriteToACallbackStream
+            | paCanNotReadFromAnOutputOnlyStream
+            | paCanNotWriteToAnInputOnlyStream => Unknown("Portaudio blocking API error."),
+            // Programming errors, e.g. a stream used after being closed.
+            paNullCallback | paBadStreamPtr | paBadBufferPtr | paStreamIsStopped | paNoError => {
+                Unknown("Portaudio misused.")
+            }
+            // Codes added by later versions of PortAudio.
+            _ => Unknown("Unknown Portaudio error."),
         }
     }
 }
@@ -35,3 +60,88 @@ impl PaErrorAsResult for ffi::PaError {
         }
     }
 }
+
+/// Returns the error the failing host API reported. Only meaningful right after a PortAudio call
+/// returned `paUnanticipatedHostError`.
+///
+/// PortAudio keeps the last error in a static buffer, which is copied under the global lock so
+/// that the calls it serializes do not write it meanwhile. The lock is reentrant, so this can be
+/// called while holding it.
+fn last_host_error() -> Error {
+    let _guard = global_lock();
+    host_error(unsafe { ffi::Pa_GetLastHostErrorInfo().as_ref() })
+}
+
+fn host_error(info: Option<&ffi::PaHostErrorInfo>) -> Error {
+    match info {
+        Some(info) => Error::HostError {
+            backend: info.hostApiType.into(),
+            code: i64::from(info.errorCode),
+            text: to_string(info.errorText),
+        },
+        None => Error::Unknown("Portaudio host error without error info."),
+    }
+}
+
+/// Copies a string owned by PortAudio, which may be null.
+pub fn to_string(text: *const c_char) -> String {
+    if text.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(text) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Backend;
+
+    #[test]
+    fn describes_host_errors() {
+        let info = ffi::PaHostErrorInfo {
+            hostApiType: ffi::PaHostApiTypeId::paALSA,
+            errorCode: -16,
+            errorText: b"Device or resource busy\0".as_ptr() as *const c_char,
+        };
+        assert_eq!(
+            host_error(Some(&info)),
+            Error::HostError {
+                backend: Backend::Alsa,
+                code: -16,
+                text: "Device or resource busy".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn maps_every_error_code() {
+        // Includes the codes that used to panic, e.g. once a device is unplugged.
+        for code in
+            ffi::PaErrorCode::paNotInitialized as c_int..=ffi::PaErrorCode::paBadBufferPtr as c_int
+        {
+            assert!(
+                ffi::PaError(code).as_result().is_err(),
+                "{} is an error.",
+                code
+            );
+        }
+        assert_eq!(
+            Error::from(ffi::PaErrorCode::paBadStreamPtr),
+            Error::Unknown("Portaudio misused.")
+        );
+        assert_eq!(
+            Error::from(ffi::PaErrorCode::paOutputUnderflowed),
+            Error::Unknown("Portaudio blocking API error.")
+        );
+    }
+
+    #[test]
+    fn accepts_missing_host_error_info() {
+        assert_eq!(
+            host_error(None),
+            Error::Unknown("Portaudio host error without error info.")
+        );
+    }
+}
@@ -1,21 +1,38 @@
+use lazy_static::lazy_static;
 use libportaudio_sys as ffi;
+use parking_lot::Mutex;
 use std::convert::{TryFrom, TryInto as _};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use crate::backend::Backend;
 use crate::error::{Error, Result};
 use crate::portaudio::device;
 use crate::portaudio::error::PaErrorAsResult as _;
-use crate::portaudio::{global_lock, LockGuard, RawPtr};
+#[cfg(feature = "jack")]
+use crate::portaudio::jack;
+use crate::portaudio::{global_lock, LockGuard};
 
 pub type HostHandle = std::sync::Arc<HostImpl>;
+
+/// A Portaudio host API.
+#[derive(Clone)]
 pub struct Host(HostHandle);
 
+impl From<HostHandle> for Host {
+    fn from(host: HostHandle) -> Host {
+        Host(host)
+    }
+}
+
 impl TryFrom<Backend> for ffi::PaHostApiTypeId {
     type Error = crate::error::Error;
 
     fn try_from(backend: Backend) -> Result<Self> {
         use ffi::PaHostApiTypeId::*;
         use Backend::*;
+        if !backend.is_compiled() {
+            return Err(Error::BackendUnavailable);
+        }
         match backend {
             Jack => Ok(paJACK),
             Alsa => Ok(paALSA),
@@ -23,23 +40,102 @@ impl TryFrom<Backend> for ffi::PaHostApiTypeId {
             Wasapi => Ok(paWASAPI),
             LinuxFallback => Ok(paOSS),
             Dummy => Ok(paInDevelopment),
-            _ => panic!("Backend pattern is not exhaustive."),
+            // Only reached without the native backend.
+            PulseAudio => Ok(paPulseAudio),
+            None | Null | File | Loopback | PipeWire => Err(Error::BackendUnavailable),
+        }
+    }
+}
+
+impl From<ffi::PaHostApiTypeId> for Backend {
+    fn from(pa_backend: ffi::PaHostApiTypeId) -> Backend {
+        use ffi::PaHostApiTypeId::*;
+        use Backend::*;
+        match pa_backend {
+            paJACK => Jack,
+            paALSA => Alsa,
+            paCoreAudio => CoreAudio,
+            paWASAPI => Wasapi,
+            paOSS => LinuxFallback,
+            paInDevelopment => Dummy,
+            paPulseAudio => PulseAudio,
+            // Host APIs that are never built.
+            _ => None,
         }
     }
 }
 
 pub struct HostImpl {
     name: String,
-    host_index: ffi::PaHostApiIndex,
-    host_info: RawPtr<ffi::PaHostApiInfo>,
+    /// The host API, whose index may change when PortAudio is re-initialized.
+    host_type: ffi::PaHostApiTypeId,
+    /// Whether PortAudio connects the JACK ports of streams when they start.
+    #[cfg(feature = "jack")]
+    jack_autoconnect: bool,
+}
+
+/// PortAudio is initialized once for all hosts, so that it can be re-initialized (see
+/// [`refresh_devices`]) without tracking how many times it was.
+struct Initialization {
+    /// The number of live hosts.
+    n_hosts: usize,
+    /// Whether PortAudio is initialized. Only unset while hosts are alive if re-initializing
+    /// failed.
+    is_initialized: bool,
+}
+
+lazy_static! {
+    static ref INITIALIZATION: Mutex<Initialization> = Mutex::new(Initialization {
+        n_hosts: 0,
+        is_initialized: false,
+    });
+}
+
+/// Incremented whenever PortAudio is re-initialized, which invalidates device indices and infos.
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of open streams, which must all be closed before PortAudio is re-initialized.
+static N_OPEN_STREAMS: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns how many times PortAudio was re-initialized.
+pub fn generation(_guard: &LockGuard) -> usize {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+/// Records that a stream was opened. Must be paired with [`on_stream_closed`].
+pub fn on_stream_opened(_guard: &LockGuard) {
+    N_OPEN_STREAMS.fetch_add(1, Ordering::SeqCst);
+}
+
+pub fn on_stream_closed(_guard: &LockGuard) {
+    N_OPEN_STREAMS.fetch_sub(1, Ordering::SeqCst);
+}
+
+/// Re-initializes PortAudio, so that it lists the devices plugged or unplugged since. Returns
+/// [`Error::StreamsOpen`] if any stream is open.
+///
+/// Device indices and infos change: devices look themselves up again (see
+/// [`generation`]).
+pub fn refresh_devices(_guard: &LockGuard) -> Result<()> {
+    if N_OPEN_STREAMS.load(Ordering::SeqCst) > 0 {
+        return Err(Error::StreamsOpen);
+    }
+    let mut initialization = INITIALIZATION.lock();
+    if initialization.is_initialized {
+        unsafe { ffi::Pa_Terminate() }.as_result()?;
+        initialization.is_initialized = false;
+    }
+    GENERATION.fetch_add(1, Ordering::SeqCst);
+    unsafe { ffi::Pa_Initialize() }.as_result()?;
+    initialization.is_initialized = true;
+    Ok(())
 }
 
 impl Host {
     /// Creates a host with the default system backend.
     pub fn with_default_backend() -> Result<Host> {
         let _guard = global_lock();
-        unsafe { ffi::Pa_Initialize() }.as_result()?;
-        let mut host = HostImpl::new();
+        let mut host = HostImpl::new(&_guard)?;
         // TODO: Expose default backend.
         let host_index = unsafe { ffi::Pa_GetDefaultHostApi() };
         if host_index < 0 {
@@ -53,56 +149,115 @@ impl Host {
     ///
     /// Will return [`Error::BackendUnavailable`] if the backend support was not
     /// compiled.
-    ///
-    /// # Examples
-    /// ```
-    /// # use audiohal::*;
-    /// assert!(Host::with_backend(Backend::Dummy).is_ok(), "The dummy backend should always be available.");
-    /// ```
     pub fn with_backend(backend: Backend) -> Result<Host> {
-        let _guard = global_lock();
-        // Initialize Pa.
-        unsafe { ffi::Pa_Initialize() }.as_result()?;
-        let mut host = HostImpl::new();
         let pa_backend = backend.try_into()?;
+        let _guard = global_lock();
+        let mut host = HostImpl::new(&_guard)?;
         host.init_with_pa_host_type(pa_backend, _guard)?;
         Ok(Host(HostHandle::new(host)))
     }
 
+    /// Creates a host with the JACK backend, whose client is called `client_name`.
+    ///
+    /// All hosts share PortAudio's client: while any Portaudio host is alive, `client_name` must
+    /// be the name the client was registered with, or [`Error::Invalid`] is returned.
+    #[cfg(feature = "jack")]
+    pub fn with_jack_options(client_name: &str, autoconnect: bool) -> Result<Host> {
+        let _guard = global_lock();
+        jack::set_client_name(client_name, &_guard)?;
+        let mut host = HostImpl::new(&_guard)?;
+        host.jack_autoconnect = autoconnect;
+        host.init_with_pa_host_type(ffi::PaHostApiTypeId::paJACK, _guard)?;
+        Ok(Host(HostHandle::new(host)))
+    }
+
+    /// Returns the name the JACK server gave PortAudio's client. Returns
+    /// [`Error::WrongBackend`] if this is not the JACK host API.
+    #[cfg(feature = "jack")]
+    pub fn jack_client_name(&self) -> Result<String> {
+        let guard = global_lock();
+        if !self.0.is(ffi::PaHostApiTypeId::paJACK) {
+            return Err(Error::WrongBackend);
+        }
+        jack::client_name(&guard)
+    }
+
     /// Returns the host API's descriptive name (e.g. "CoreAudio").
     pub fn name(&self) -> &str {
         &self.0.name
     }
 
+    /// Returns all the devices of this host API.
+    pub fn devices(&mut self) -> Result<Vec<device::Device>> {
+        let guard = global_lock();
+        self.0
+            .device_indices(&guard)?
+            .into_iter()
+            .map(|device_index| {
+                device::from_device_index(device_index, HostHandle::clone(&self.0), &guard)
+            })
+            .collect()
+    }
+
     /// Creates and returns the default output device for this host.
-    ///
-    /// This is the recommended device to use for audio playback.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// let mut host = audiohal::Host::with_default_backend()?;
-    /// match host.default_output_device() {
-    ///     Ok(device) => println!("Default output device name is {}.", device.name()),
-    ///     Err(_) => println!("No devices available."),
-    /// };
-    /// # audiohal::Result::Ok(())
-    /// ```
-    ///
     pub fn default_output_device(&mut self) -> Result<device::Device> {
         let guard = global_lock();
         let device_index = self.0.default_output_device_index(&guard)?;
         device::from_device_index(device_index, HostHandle::clone(&self.0), &guard)
     }
+
+    /// Creates and returns the default input device for this host.
+    pub fn default_input_device(&mut self) -> Result<device::Device> {
+        let guard = global_lock();
+        let device_index = self.0.default_input_device_index(&guard)?;
+        device::from_device_index(device_index, HostHandle::clone(&self.0), &guard)
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.0.backend()
+    }
+
+    /// Re-initializes PortAudio, for all hosts. See [`refresh_devices`].
+    pub fn refresh_devices(&mut self) -> Result<()> {
+        refresh_devices(&global_lock())
+    }
+
+    /// Returns the number of open streams, of all hosts.
+    pub fn n_open_streams(&self) -> usize {
+        N_OPEN_STREAMS.load(Ordering::SeqCst)
+    }
 }
 
 impl HostImpl {
-    fn new() -> HostImpl {
-        HostImpl {
-            name: String::new(),
-            host_index: -1,
-            host_info: RawPtr::dangling(),
+    /// Initializes PortAudio, unless another host already did.
+    fn new(_guard: &LockGuard) -> Result<HostImpl> {
+        let mut initialization = INITIALIZATION.lock();
+        if !initialization.is_initialized {
+            unsafe { ffi::Pa_Initialize() }.as_result()?;
+            initialization.is_initialized = true;
         }
+        initialization.n_hosts += 1;
+        Ok(HostImpl {
+            name: String::new(),
+            host_type: ffi::PaHostApiTypeId::paInDevelopment,
+            #[cfg(feature = "jack")]
+            jack_autoconnect: true,
+        })
+    }
+
+    /// Returns whether this is the given host API.
+    #[cfg(any(
+        all(feature = "alsa", target_os = "linux"),
+        feature = "jack",
+        feature = "oss"
+    ))]
+    pub fn is(&self, pa_backend: ffi::PaHostApiTypeId) -> bool {
+        self.host_type == pa_backend
+    }
+
+    #[cfg(feature = "jack")]
+    pub fn jack_autoconnect(&self) -> bool {
+        self.jack_autoconnect
     }
 
     /// Expects Pa_Initialize() to have already been called.
@@ -121,24 +276,58 @@ impl HostImpl {
     /// Expects Pa_Initialize() to have already been called.
     fn init_with_pa_host_index(&mut self, host_index: i32, _guard: LockGuard) -> Result<()> {
         debug_assert!(host_index >= 0);
-        self.host_index = host_index;
-        self.host_info = RawPtr::new(unsafe { ffi::Pa_GetHostApiInfo(host_index) })
+        let host_info = unsafe { ffi::Pa_GetHostApiInfo(host_index).as_ref() }
             .ok_or(Error::BackendUnavailable)?;
-        self.name = unsafe { std::ffi::CStr::from_ptr(self.host_info.as_ref().unwrap().name) }
+        self.host_type = host_info.type_;
+        self.name = unsafe { std::ffi::CStr::from_ptr(host_info.name) }
             .to_str()
             .or(Err(Error::Unknown("Could not convert host name to UTF-8.")))?
             .to_string();
         Ok(())
     }
 
-    fn default_output_device_index(&self, _guard: &LockGuard) -> Result<i32> {
-        let host_device_index = unsafe { self.host_info.as_ref().unwrap() }.defaultOutputDevice;
+    /// Returns the host API's current index and info. Returns [`Error::BackendUnavailable`] if it
+    /// disappeared when PortAudio was re-initialized.
+    fn info<'a>(&self, _guard: &'a LockGuard) -> Result<(i32, &'a ffi::PaHostApiInfo)> {
+        let host_index = unsafe { ffi::Pa_HostApiTypeIdToHostApiIndex(self.host_type) };
+        if host_index < 0 {
+            return Err(Error::BackendUnavailable);
+        }
+        let host_info = unsafe { ffi::Pa_GetHostApiInfo(host_index).as_ref() }
+            .ok_or(Error::BackendUnavailable)?;
+        Ok((host_index, host_info))
+    }
+
+    fn default_output_device_index(&self, guard: &LockGuard) -> Result<i32> {
+        let host_device_index = self.info(guard)?.1.defaultOutputDevice;
+        self.to_device_index(host_device_index, guard)
+    }
+
+    fn default_input_device_index(&self, guard: &LockGuard) -> Result<i32> {
+        let host_device_index = self.info(guard)?.1.defaultInputDevice;
+        self.to_device_index(host_device_index, guard)
+    }
+
+    /// Returns the global indices of the host API's devices.
+    pub fn device_indices(&self, guard: &LockGuard) -> Result<Vec<i32>> {
+        (0..self.info(guard)?.1.deviceCount)
+            .map(|host_device_index| self.to_device_index(host_device_index, guard))
+            .collect()
+    }
+
+    pub fn backend(&self) -> Backend {
+        self.host_type.into()
+    }
+
+    /// Converts a host-specific device index to a global Portaudio device index.
+    fn to_device_index(&self, host_device_index: i32, guard: &LockGuard) -> Result<i32> {
         if host_device_index == ffi::paNoDevice {
             return Err(Error::NoSuchDevice);
         }
         assert!(host_device_index >= 0);
-        let device_index =
-            unsafe { ffi::Pa_HostApiDeviceIndexToDeviceIndex(self.host_index, host_device_index) };
+        let device_index = unsafe {
+            ffi::Pa_HostApiDeviceIndexToDeviceIndex(self.info(guard)?.0, host_device_index)
+        };
         if device_index < 0 {
             return Err(ffi::PaError::from(device_index).as_result().unwrap_err());
         }
@@ -149,7 +338,14 @@ impl HostImpl {
 impl Drop for HostImpl {
     fn drop(&mut self) {
         let _guard = global_lock();
-        unsafe { ffi::Pa_Terminate() }.as_result().unwrap();
+        let mut initialization = INITIALIZATION.lock();
+        initialization.n_hosts -= 1;
+        if initialization.n_hosts == 0 && initialization.is_initialized {
+            if let Err(error) = unsafe { ffi::Pa_Terminate() }.as_result() {
+                log::error!("Could not terminate PortAudio: {}", error);
+            }
+            initialization.is_initialized = false;
+        }
     }
 }
 
@@ -157,6 +353,7 @@ impl Drop for HostImpl {
 mod tests {
     use super::*;
     use crate::portaudio::test_prelude::*;
+    use crate::portaudio::Host;
 
     #[test]
     fn host_is_send() {
@@ -185,12 +382,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn rejects_backends_that_were_not_compiled() {
+        begin!();
+        for backend in vec![Backend::None, Backend::Jack, Backend::LinuxFallback] {
+            if !backend.is_compiled() {
+                assert_eq!(
+                    Host::with_backend(backend).err(),
+                    Some(Error::BackendUnavailable)
+                );
+                assert!(!is_initialized());
+            }
+        }
+    }
+
+    #[test]
+    fn refreshes_devices_once_streams_are_closed() -> Result<()> {
+        begin!();
+        let mut host = Host::with_default_backend()?;
+        let mut device = host.default_output_device()?;
+        let stream = device.open_outstream(StreamOptions::<[f32; 2]>::default())?;
+        assert_eq!(host.refresh_devices(), Err(Error::StreamsOpen));
+        drop(stream);
+        host.refresh_devices()?;
+        assert!(is_initialized());
+        // The device is looked up again.
+        device.open_outstream(StreamOptions::<[f32; 2]>::default())?;
+        Ok(())
+    }
+
     #[test]
     fn internal_handles_invalid_host_index() {
         begin!();
         let _guard = global_lock();
-        unsafe { ffi::Pa_Initialize() }.as_result().unwrap();
-        let mut host = HostImpl::new();
+        let mut host = HostImpl::new(&_guard).unwrap();
         assert_eq!(
             host.init_with_pa_host_index(100_000, _guard),
             Err(Error::BackendUnavailable)
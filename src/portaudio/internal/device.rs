@@ -1,24 +1,41 @@
 use libportaudio_sys as ffi;
+use parking_lot::Mutex;
 use std::convert::TryInto;
+#[cfg(all(feature = "alsa", target_os = "linux"))]
+use std::sync::atomic::{AtomicI32, Ordering};
 
 use crate::error::{Error, Result};
+#[cfg(all(feature = "alsa", target_os = "linux"))]
+use crate::portaudio::alsa;
 use crate::portaudio::device::DeviceHandle;
-use crate::portaudio::host::HostHandle;
+use crate::portaudio::host::{self, HostHandle};
 use crate::portaudio::internal::stream::StreamOpenParams;
-use crate::portaudio::stream::{new_outstream, Stream};
-use crate::portaudio::{LockGuard, RawPtr};
-use crate::stream_options::StreamOptions;
-use crate::SampleRate;
+use crate::portaudio::stream::{new_instream, new_outstream, Stream};
+use crate::portaudio::{global_lock, LockGuard, RawPtr};
+use crate::stream_options::{Direction, StreamOptions};
+use crate::{DeviceId, Latency, SampleRate};
 
 pub struct Device {
     pub name: String,
-    info: RawPtr<ffi::PaDeviceInfo>,
-
-    index: i32,
+    direction: Direction,
+    /// The number of devices of the host API with the same name and direction listed before it.
+    occurrence: usize,
+    /// Where PortAudio currently lists the device, which changes when it is re-initialized.
+    location: Mutex<Location>,
+    /// The number of periods of the buffers of ALSA streams.
+    #[cfg(all(feature = "alsa", target_os = "linux"))]
+    alsa_n_periods: AtomicI32,
     /// Handle to parent host.
     _parent_host: HostHandle,
 }
 
+struct Location {
+    /// The generation of PortAudio the index and info belong to.
+    generation: usize,
+    index: i32,
+    info: RawPtr<ffi::PaDeviceInfo>,
+}
+
 impl Device {
     /// Creates a portaudio Device from a given device index.
     pub fn from_device_index(
@@ -27,33 +44,99 @@ impl Device {
         _guard: &LockGuard,
     ) -> Result<Device> {
         debug_assert_ge!(index, 0);
-        let device_info = unsafe {
-            ffi::Pa_GetDeviceInfo(index)
-                .as_ref()
-                .ok_or(Error::NoSuchDevice)?
-        };
-        let name = unsafe { std::ffi::CStr::from_ptr(device_info.name) }
-            .to_str()
-            .or(Err(Error::Unknown(
-                "Could not convert device name to UTF-8.",
-            )))?
-            .to_string();
+        let (name, direction, device_info) = describe(index, _guard)?;
+        // Identical devices (e.g. two interfaces of the same model) have the same name.
+        let mut occurrence = 0;
+        for other in host_handle.device_indices(_guard)? {
+            if other == index {
+                break;
+            }
+            let (other_name, other_direction, _) = describe(other, _guard)?;
+            if other_name == name && other_direction == direction {
+                occurrence += 1;
+            }
+        }
         Ok(Device {
             name,
-            info: RawPtr::new(device_info as *const _).unwrap(),
-            index,
+            direction,
+            occurrence,
+            location: Mutex::new(Location {
+                generation: host::generation(_guard),
+                index,
+                info: RawPtr::new(device_info as *const _).unwrap(),
+            }),
+            #[cfg(all(feature = "alsa", target_os = "linux"))]
+            alsa_n_periods: AtomicI32::new(alsa::DEFAULT_N_PERIODS),
             _parent_host: host_handle,
         })
     }
 
+    pub fn id(&self) -> DeviceId {
+        DeviceId {
+            backend: self._parent_host.backend(),
+            name: self.name.clone(),
+            occurrence: self.occurrence,
+            direction: self.direction,
+        }
+    }
+
+    /// Returns the device's current index and info, looking the device up again if PortAudio was
+    /// re-initialized since. Returns [`Error::NoSuchDevice`] if it was unplugged.
+    fn locate<'a>(&self, guard: &'a LockGuard) -> Result<(i32, &'a ffi::PaDeviceInfo)> {
+        let mut location = self.location.lock();
+        let generation = host::generation(guard);
+        if location.generation != generation {
+            let mut occurrence = 0;
+            let mut found = None;
+            for index in self._parent_host.device_indices(guard)? {
+                let (name, direction, info) = describe(index, guard)?;
+                if name == self.name && direction == self.direction {
+                    if occurrence == self.occurrence {
+                        found = Some((index, info));
+                        break;
+                    }
+                    occurrence += 1;
+                }
+            }
+            let (index, info) = found.ok_or(Error::NoSuchDevice)?;
+            *location = Location {
+                generation,
+                index,
+                info: RawPtr::new(info as *const _).unwrap(),
+            };
+        }
+        Ok((location.index, unsafe { location.info.as_ref() }.unwrap()))
+    }
+
+    pub fn host(&self) -> &HostHandle {
+        &self._parent_host
+    }
+
+    #[cfg(all(feature = "alsa", target_os = "linux"))]
+    pub fn alsa_n_periods(&self) -> i32 {
+        self.alsa_n_periods.load(Ordering::Relaxed)
+    }
+
+    #[cfg(all(feature = "alsa", target_os = "linux"))]
+    pub fn set_alsa_n_periods(&self, n_periods: i32) {
+        self.alsa_n_periods.store(n_periods, Ordering::Relaxed);
+    }
+
     pub fn open_outstream<Frame>(
         &self,
         options: StreamOptions<Frame>,
         device_handle: DeviceHandle,
-    ) -> Result<Stream<Frame>> {
+    ) -> Result<Stream<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
         // Early-out if the stream spec is not supported?
         // self.is_stream_spec_supported(&options, true, &global_lock())?;
-        let (params, sample_rate) = self.options_to_stream_params(&options)?;
+        // Holds the lock until the stream is open, so that PortAudio is not re-initialized in
+        // between.
+        let guard = global_lock();
+        let (params, sample_rate) =
+            self.options_to_stream_params(&options, Direction::Output, &guard)?;
         let open_params = StreamOpenParams {
             user_options: options,
             pa_params: params,
@@ -62,27 +145,54 @@ impl Device {
         new_outstream(open_params, device_handle)
     }
 
+    pub fn open_instream<Frame>(
+        &self,
+        options: StreamOptions<Frame>,
+        device_handle: DeviceHandle,
+    ) -> Result<Stream<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        let guard = global_lock();
+        let (params, sample_rate) =
+            self.options_to_stream_params(&options, Direction::Input, &guard)?;
+        let open_params = StreamOpenParams {
+            user_options: options,
+            pa_params: params,
+            sample_rate,
+        };
+        new_instream(open_params, device_handle)
+    }
+
     fn options_to_stream_params<F>(
         &self,
         options: &StreamOptions<F>,
+        direction: Direction,
+        guard: &LockGuard,
     ) -> Result<(ffi::PaStreamParameters, i32)> {
-        let info = unsafe { self.info.as_ref().unwrap() };
+        let (index, info) = self.locate(guard)?;
         let sample_rate = match options.sample_rate {
             SampleRate::Exact(rate) => rate,
             SampleRate::DeviceDefault | SampleRate::NearestTo(_) => info.defaultSampleRate as i32,
             _ => panic!("Non-exhaustive sample rate."),
         };
-        let latency = if let Some(frames_per_buffer) = options.frames_per_buffer {
+        if let Some(frames_per_buffer) = options.frames_per_buffer {
             if frames_per_buffer <= 0 {
                 return Err(Error::InvalidFramesPerBuffer);
             }
-            frames_per_buffer_to_latency(frames_per_buffer, sample_rate)
-        } else {
-            info.defaultHighOutputLatency
+        }
+        let is_output = direction == Direction::Output;
+        let latency = match options.latency {
+            Latency::Low if is_output => info.defaultLowOutputLatency,
+            Latency::Low => info.defaultLowInputLatency,
+            Latency::High if is_output => info.defaultHighOutputLatency,
+            Latency::High => info.defaultHighInputLatency,
+            Latency::Exact(duration) => duration.as_secs_f64(),
+            _ => panic!("Non-exhaustive latency."),
         };
         Ok((
             ffi::PaStreamParameters {
-                device: self.index,
+                device: index,
                 channelCount: options.n_channels,
                 sampleFormat: options.format.try_into()?,
                 suggestedLatency: latency,
@@ -93,6 +203,18 @@ impl Device {
     }
 }
 
-fn frames_per_buffer_to_latency(frames_per_buffer: i32, sample_rate: i32) -> f64 {
-    f64::from(frames_per_buffer) / f64::from(sample_rate)
+/// Returns the name, direction and info of the device at `index`.
+fn describe<'a>(
+    index: i32,
+    _guard: &'a LockGuard,
+) -> Result<(String, Direction, &'a ffi::PaDeviceInfo)> {
+    let info = unsafe { ffi::Pa_GetDeviceInfo(index).as_ref() }.ok_or(Error::NoSuchDevice)?;
+    let name = unsafe { std::ffi::CStr::from_ptr(info.name) }
+        .to_str()
+        .or(Err(Error::Unknown(
+            "Could not convert device name to UTF-8.",
+        )))?
+        .to_string();
+    let direction = Direction::of_device(info.maxInputChannels, info.maxOutputChannels);
+    Ok((name, direction, info))
 }
@@ -1,11 +1,20 @@
 use libportaudio_sys as ffi;
-use std::os::raw::{c_ulong, c_void};
+use std::os::raw::{c_int, c_ulong, c_void};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use crate::error::{Error, Result};
+#[cfg(all(feature = "alsa", target_os = "linux"))]
+use crate::portaudio::alsa;
 use crate::portaudio::device::DeviceHandle;
 use crate::portaudio::error::PaErrorAsResult as _;
+use crate::portaudio::host;
+#[cfg(feature = "jack")]
+use crate::portaudio::jack;
 use crate::portaudio::{global_lock, LockGuard, RawPtr};
-use crate::stream_options::{Callback, StreamOptions};
+use crate::reblock;
+use crate::stream::StreamState;
+use crate::stream_options::{Callback, Direction, StreamOptions};
 
 /// Convenience structure to collect data needed for stream creation.
 pub struct StreamOpenParams<Frame> {
@@ -15,10 +24,24 @@ pub struct StreamOpenParams<Frame> {
 }
 
 /// Internal stream implementation. Deals with the Portaudio boilerplate.
+///
+/// Streams are not `Sync` (their callback is not), so calls on a stream are never concurrent.
+/// Streams are independent of each other, and PortAudio cannot be terminated while any is open
+/// (see [`Host::refresh_devices`](crate::Host::refresh_devices)), so only the calls that change
+/// PortAudio's global state (opening and closing) take the global lock.
 pub struct StreamImpl<Frame> {
     pa_stream: RawPtr<ffi::PaStream>,
-    cb_wrapper: Box<CallbackWrapper<Frame>>,
+    /// Only None once leaked, see `close`.
+    cb_wrapper: Option<Box<CallbackWrapper<Frame>>>,
+    started: bool,
     _sample_rate: i32,
+    /// The stream's actual latency, as reported by Portaudio.
+    latency: Duration,
+    #[cfg(all(feature = "alsa", target_os = "linux"))]
+    direction: Direction,
+    /// The stream's ports, if it belongs to the JACK host API.
+    #[cfg(feature = "jack")]
+    jack_ports: Option<jack::StreamPorts>,
     /// Handle back to the parent device.
     _parent_device: DeviceHandle,
 }
@@ -27,36 +50,117 @@ impl<Frame> StreamImpl<Frame> {
     pub fn new_outstream(
         params: StreamOpenParams<Frame>,
         device: DeviceHandle,
-    ) -> Result<StreamImpl<Frame>> {
+    ) -> Result<StreamImpl<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        StreamImpl::open(params, device, Direction::Output)
+    }
+
+    pub fn new_instream(
+        params: StreamOpenParams<Frame>,
+        device: DeviceHandle,
+    ) -> Result<StreamImpl<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        StreamImpl::open(params, device, Direction::Input)
+    }
+
+    fn open(
+        params: StreamOpenParams<Frame>,
+        device: DeviceHandle,
+        direction: Direction,
+    ) -> Result<StreamImpl<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
         let _guard = global_lock();
+        let is_output = direction == Direction::Output;
         // Verify stream spec.
-        is_stream_spec_supported(&params, true, &_guard)?;
-        // Wrap the callback into a thin pointer.
-        let callback = Box::new(CallbackWrapper(params.user_options.callback));
+        params
+            .user_options
+            .flags
+            .validate(direction, params.user_options.frames_per_buffer)?;
+        is_stream_spec_supported(&params, is_output, &_guard)?;
+        // Re-block the callback if requested, then wrap it into a thin pointer.
+        let block_size = params.user_options.block_size;
+        let callback = reblock::wrap(params.user_options.callback, block_size, direction)?;
+        let callback = Box::new(CallbackWrapper {
+            callback,
+            finished: AtomicBool::new(false),
+            muted: AtomicBool::new(false),
+        });
         // Create the Portaudio stream.
         let mut stream = StreamImpl {
             pa_stream: RawPtr::dangling(),
             _sample_rate: 0,
-            cb_wrapper: callback,
+            latency: Duration::default(),
+            cb_wrapper: Some(callback),
+            started: false,
+            #[cfg(all(feature = "alsa", target_os = "linux"))]
+            direction,
+            #[cfg(feature = "jack")]
+            jack_ports: None,
             _parent_device: device,
         };
+        #[cfg(feature = "jack")]
+        let jack_tracker = jack::PortTracker::new(stream._parent_device.host(), &_guard)?;
+        // The number of periods is global, so it is set right before opening each stream.
+        #[cfg(all(feature = "alsa", target_os = "linux"))]
+        {
+            if stream
+                ._parent_device
+                .host()
+                .is(ffi::PaHostApiTypeId::paALSA)
+            {
+                alsa::set_n_periods(stream._parent_device.alsa_n_periods(), &_guard)?;
+            }
+        }
+        let (input_params, output_params, pa_callback): (_, _, ffi::PaStreamCallback) = if is_output
+        {
+            (
+                std::ptr::null(),
+                &params.pa_params as *const _,
+                Some(outstream_callback::<Frame>),
+            )
+        } else {
+            (
+                &params.pa_params as *const _,
+                std::ptr::null(),
+                Some(instream_callback::<Frame>),
+            )
+        };
         unsafe {
             ffi::Pa_OpenStream(
                 &mut stream.pa_stream as *const _ as *mut _,
-                std::ptr::null(),
-                &params.pa_params,
+                input_params,
+                output_params,
                 params.sample_rate.into(),
                 params
                     .user_options
                     .frames_per_buffer
                     .unwrap_or(ffi::paFramesPerBufferUnspecified as i32) as c_ulong,
-                ffi::PaStreamFlags::PaNoFlag, // No flags
-                Some(outstream_callback::<Frame>),
-                Box::as_ref(&stream.cb_wrapper) as *const _ as *mut _,
+                params.user_options.flags.into(),
+                pa_callback,
+                stream.callback_wrapper() as *const _ as *mut _,
             )
         }
         .as_result()?;
         debug_assert!(!stream.pa_stream.is_null());
+        host::on_stream_opened(&_guard);
+        unsafe {
+            ffi::Pa_SetStreamFinishedCallback(
+                stream.pa_stream.as_ptr_mut(),
+                Some(stream_finished::<Frame>),
+            )
+        }
+        .as_result()?;
+        #[cfg(feature = "jack")]
+        {
+            let autoconnect = stream._parent_device.host().jack_autoconnect();
+            stream.jack_ports = jack_tracker.stream_ports(direction, autoconnect)?;
+        }
         // Verify the frame size.
         is_frame_size_valid::<Frame>(
             params.pa_params.sampleFormat,
@@ -69,44 +173,174 @@ impl<Frame> StreamImpl<Frame> {
                 .ok_or(Error::Unknown("Could not get stream info after creation."))?);
         // TODO: Do something with this sample rate.
         stream._sample_rate = stream_info.sampleRate as i32;
+        let pa_latency = if is_output {
+            stream_info.outputLatency
+        } else {
+            stream_info.inputLatency
+        };
+        stream.latency = Duration::from_secs_f64(pa_latency.max(0.0))
+            + block_size.map_or(Duration::default(), |block_size| {
+                reblock::added_latency(block_size, stream._sample_rate)
+            });
         Ok(stream)
     }
 
     /// Stream is inactive (i.e. no callback) until this method is called.
     pub fn start(&mut self) -> Result<()> {
-        let _guard = global_lock();
         // Make sure the stream isn't actually running.
         match unsafe { ffi::Pa_IsStreamStopped(self.pa_stream.as_ptr() as *mut _) }.into() {
             Ok(0) => Err(Error::StreamAlreadyStarted),
             Ok(_) => Ok(()),
             Err(error) => Err(error.into()),
         }?;
+        #[cfg(feature = "jack")]
+        let jack_connections = match &self.jack_ports {
+            Some(ports) => {
+                self.callback_wrapper()
+                    .muted
+                    .store(!ports.autoconnects(), Ordering::Release);
+                ports.before_start()?
+            }
+            None => Vec::new(),
+        };
         // Now, open the stream.
-        unsafe { ffi::Pa_StartStream(self.pa_stream.as_ptr() as *mut _) }
-            .as_result()
-            .and(Ok(()))
+        unsafe { ffi::Pa_StartStream(self.pa_stream.as_ptr() as *mut _) }.as_result()?;
+        self.started = true;
+        #[cfg(feature = "jack")]
+        {
+            if let Some(ports) = &self.jack_ports {
+                ports.after_start(&jack_connections)?;
+                self.callback_wrapper()
+                    .muted
+                    .store(false, Ordering::Release);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    /// Enables realtime scheduling of the callback thread of an ALSA stream. Must be called
+    /// before the stream is started.
+    #[cfg(all(feature = "alsa", target_os = "linux"))]
+    pub fn enable_alsa_realtime_scheduling(&mut self, enable: bool) -> Result<()> {
+        self.check_alsa()?;
+        match unsafe { ffi::Pa_IsStreamStopped(self.pa_stream.as_ptr_mut()) }.as_result()? {
+            0 => Err(Error::StreamAlreadyStarted),
+            _ => {
+                alsa::enable_realtime_scheduling(self.pa_stream.as_ptr_mut(), enable);
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the ALSA card number of the stream's device.
+    #[cfg(all(feature = "alsa", target_os = "linux"))]
+    pub fn alsa_card(&self) -> Result<i32> {
+        self.check_alsa()?;
+        alsa::card(self.pa_stream.as_ptr_mut(), self.direction)
+    }
+
+    #[cfg(all(feature = "alsa", target_os = "linux"))]
+    fn check_alsa(&self) -> Result<()> {
+        if self._parent_device.host().is(ffi::PaHostApiTypeId::paALSA) {
+            Ok(())
+        } else {
+            Err(Error::WrongBackend)
+        }
+    }
+
+    #[cfg(feature = "jack")]
+    pub fn jack_ports(&self) -> Option<&jack::StreamPorts> {
+        self.jack_ports.as_ref()
+    }
+
+    #[cfg(feature = "jack")]
+    pub fn jack_ports_mut(&mut self) -> Option<&mut jack::StreamPorts> {
+        self.jack_ports.as_mut()
     }
 
-    /// Closes the stream and deallocates any associated data.
+    pub fn is_active(&self) -> bool {
+        // Errors (e.g. a stream that was never started) mean the stream is not active.
+        unsafe { ffi::Pa_IsStreamActive(self.pa_stream.as_ptr() as *mut _) }.as_result() == Ok(1)
+    }
+
+    pub fn state(&self) -> StreamState {
+        let finished = self.callback_wrapper().finished.load(Ordering::Acquire);
+        let is_active = unsafe { ffi::Pa_IsStreamActive(self.pa_stream.as_ptr_mut()) }.as_result();
+        state(finished, is_active, self.started)
+    }
+
+    /// Closes the stream and deallocates any associated data. Does nothing if the stream is
+    /// already closed, or failed to open.
+    ///
+    /// If PortAudio fails to close the stream (e.g. because its device was disconnected), the
+    /// stream is left to PortAudio, which closes it when terminated. Its callback is leaked, since
+    /// PortAudio may still call it.
     pub fn close(&mut self) -> Result<()> {
         let _guard = global_lock();
-        unsafe { ffi::Pa_CloseStream(self.pa_stream.as_ptr_mut() as *mut _) }.as_result()?;
-        Ok(())
+        if self.pa_stream.is_null() {
+            return Ok(());
+        }
+        let result = unsafe { ffi::Pa_CloseStream(self.pa_stream.as_ptr_mut() as *mut _) }
+            .as_result()
+            .map(|_| ());
+        if result.is_err() {
+            Box::leak(self.cb_wrapper.take().unwrap());
+        }
+        self.pa_stream = RawPtr::dangling();
+        host::on_stream_closed(&_guard);
+        result
+    }
+
+    fn callback_wrapper(&self) -> &CallbackWrapper<Frame> {
+        self.cb_wrapper.as_ref().unwrap()
     }
 }
 
 impl<Frame> Drop for StreamImpl<Frame> {
     fn drop(&mut self) {
-        // Immediately stop execution and close.
-        self.close()
-            .expect("Could not close stream while dropping.");
+        // Immediately stop execution and close. Close leaves the stream in a safe state even if it
+        // fails, so the error is only logged.
+        if let Err(error) = self.close() {
+            log::error!("Could not close stream while dropping: {}", error);
+        }
+    }
+}
+
+/// Returns the state of a stream from whether its finished callback was called, and from
+/// `Pa_IsStreamActive`. The stream only becomes inactive on its own (i.e. the finished callback is
+/// called, or PortAudio errors) if its device failed, since the callback always continues.
+fn state(finished: bool, is_active: Result<c_int>, started: bool) -> StreamState {
+    if finished {
+        return StreamState::Disconnected;
+    }
+    match is_active {
+        Ok(1) => StreamState::Active,
+        Ok(_) if !started => StreamState::Stopped,
+        _ => StreamState::Disconnected,
     }
 }
 
 /// Wraps Callback in order to avoid dealing with fat closure pointers.
-struct CallbackWrapper<Frame>(Callback<Frame>);
+struct CallbackWrapper<Frame> {
+    callback: Callback<Frame>,
+    /// Set once the stream became inactive.
+    finished: AtomicBool,
+    /// Set while the stream must not exchange frames with its ports: the callback is skipped,
+    /// and the output is silent.
+    muted: AtomicBool,
+}
 
-extern "C" fn outstream_callback<Frame>(
+extern "C" fn stream_finished<Frame>(user_data: *mut c_void) {
+    let callback = unsafe { (user_data as *const CallbackWrapper<Frame>).as_ref() }
+        .expect("Could not create CallbackWrapper from user_data.");
+    callback.finished.store(true, Ordering::Release);
+}
+
+extern "C" fn outstream_callback<Frame: sample::Frame>(
     _input: *const c_void,
     output: *mut c_void,
     frame_count: c_ulong,
@@ -119,7 +353,34 @@ extern "C" fn outstream_callback<Frame>(
 
     let output =
         unsafe { std::slice::from_raw_parts_mut(output as *mut Frame, frame_count as usize) };
-    (callback.0)(output);
+    if callback.muted.load(Ordering::Acquire) {
+        output
+            .iter_mut()
+            .for_each(|frame| *frame = Frame::equilibrium());
+    } else {
+        (callback.callback)(output);
+    }
+    0
+}
+
+extern "C" fn instream_callback<Frame: sample::Frame>(
+    input: *const c_void,
+    _output: *mut c_void,
+    frame_count: c_ulong,
+    _time_info: *const ffi::PaStreamCallbackTimeInfo,
+    _status_flags: ffi::PaStreamCallbackFlags,
+    user_data: *mut c_void,
+) -> i32 {
+    let callback = unsafe { (user_data as *mut CallbackWrapper<Frame>).as_mut() }
+        .expect("Could not create CallbackWrapper from user_data.");
+
+    // The input buffer is owned by Portaudio for the duration of the callback, and is not read
+    // back, so it is safe to hand it out mutably.
+    let input =
+        unsafe { std::slice::from_raw_parts_mut(input as *mut Frame, frame_count as usize) };
+    if !callback.muted.load(Ordering::Acquire) {
+        (callback.callback)(input);
+    }
     0
 }
 
@@ -169,3 +430,75 @@ fn is_stream_spec_supported<F>(
     .as_result()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_finished_streams_as_disconnected() {
+        let wrapper = CallbackWrapper::<[f32; 2]> {
+            callback: Box::new(|_: &mut [[f32; 2]]| {}),
+            finished: AtomicBool::new(false),
+            muted: AtomicBool::new(false),
+        };
+        let finished = || wrapper.finished.load(Ordering::Acquire);
+        assert_eq!(state(finished(), Ok(1), true), StreamState::Active);
+        stream_finished::<[f32; 2]>(&wrapper as *const _ as *mut c_void);
+        assert_eq!(state(finished(), Ok(1), true), StreamState::Disconnected);
+    }
+
+    #[test]
+    fn reports_failing_streams_as_disconnected() {
+        assert_eq!(state(false, Ok(0), false), StreamState::Stopped);
+        // Started streams only stop on their own once their device failed.
+        assert_eq!(state(false, Ok(0), true), StreamState::Disconnected);
+        let unavailable = ffi::PaError(ffi::PaErrorCode::paDeviceUnavailable as c_int);
+        assert_eq!(
+            state(false, unavailable.as_result(), true),
+            StreamState::Disconnected
+        );
+    }
+
+    #[test]
+    fn muted_streams_skip_the_callback() {
+        let mut wrapper = CallbackWrapper::<[f32; 1]> {
+            callback: Box::new(|buffer: &mut [[f32; 1]]| buffer[0] = [1.0]),
+            finished: AtomicBool::new(false),
+            muted: AtomicBool::new(true),
+        };
+        let user_data = &mut wrapper as *mut _ as *mut c_void;
+        let mut output = [[0.5_f32]; 2];
+        let output_ptr = output.as_mut_ptr() as *mut c_void;
+        outstream_callback::<[f32; 1]>(
+            std::ptr::null(),
+            output_ptr,
+            2,
+            std::ptr::null(),
+            ffi::PaStreamCallbackFlags::empty(),
+            user_data,
+        );
+        assert_eq!(output, [[0.0], [0.0]]);
+        let mut input = [[0.5_f32]; 2];
+        let input_ptr = input.as_mut_ptr() as *const c_void;
+        instream_callback::<[f32; 1]>(
+            input_ptr,
+            std::ptr::null_mut(),
+            2,
+            std::ptr::null(),
+            ffi::PaStreamCallbackFlags::empty(),
+            user_data,
+        );
+        assert_eq!(input, [[0.5], [0.5]]);
+        wrapper.muted.store(false, Ordering::Release);
+        outstream_callback::<[f32; 1]>(
+            std::ptr::null(),
+            output_ptr,
+            2,
+            std::ptr::null(),
+            ffi::PaStreamCallbackFlags::empty(),
+            user_data,
+        );
+        assert_eq!(output, [[1.0], [0.0]]);
+    }
+}
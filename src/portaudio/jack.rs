@@ -0,0 +1,498 @@
+//! JACK-specific extensions of the PortAudio JACK host API.
+//!
+//! PortAudio registers a single JACK client per process, and one port per channel of every
+//! stream. Ports are patched through a short-lived helper client, as PortAudio does not expose
+//! its own.
+use lazy_static::lazy_static;
+use libportaudio_sys as ffi;
+use parking_lot::Mutex;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+
+use crate::error::{Error, Result};
+use crate::portaudio::error::PaErrorAsResult as _;
+use crate::portaudio::host::HostImpl;
+use crate::portaudio::LockGuard;
+use crate::stream_options::Direction;
+
+/// PortAudio's client name, unless set otherwise.
+const DEFAULT_CLIENT_NAME: &str = "PortAudio";
+
+lazy_static! {
+    /// PortAudio keeps a pointer to the client name instead of copying it.
+    static ref CLIENT_NAME: Mutex<Option<CString>> = Mutex::new(None);
+}
+
+/// Sets the name of the client PortAudio registers when it is initialized.
+///
+/// If PortAudio is already initialized, its client cannot be renamed: `name` must then match the
+/// name it was initialized with.
+pub fn set_client_name(name: &str, _guard: &LockGuard) -> Result<()> {
+    let mut client_name = CLIENT_NAME.lock();
+    let current = client_name
+        .as_ref()
+        .map_or(Ok(DEFAULT_CLIENT_NAME), |name| name.to_str())
+        .or(Err(Error::Invalid))?;
+    if unsafe { ffi::Pa_GetDeviceCount() } >= 0 {
+        return if current == name {
+            Ok(())
+        } else {
+            Err(Error::Invalid)
+        };
+    }
+    let name = CString::new(name).or(Err(Error::Invalid))?;
+    unsafe { ffi::jack::PaJack_SetClientName(name.as_ptr()) }.as_result()?;
+    *client_name = Some(name);
+    Ok(())
+}
+
+/// Returns the name the JACK server gave PortAudio's client. Expects PortAudio to be initialized.
+pub fn client_name(_guard: &LockGuard) -> Result<String> {
+    let mut name: *const c_char = std::ptr::null();
+    unsafe { ffi::jack::PaJack_GetClientName(&mut name) }.as_result()?;
+    if name.is_null() {
+        return Err(Error::BackendUnavailable);
+    }
+    Ok(unsafe { CStr::from_ptr(name) }
+        .to_string_lossy()
+        .into_owned())
+}
+
+/// Finds the ports PortAudio registers when opening a stream, by listing its client's ports
+/// before and after.
+pub struct PortTracker(Option<(String, Vec<String>)>);
+
+impl PortTracker {
+    /// Does nothing unless `host` is the JACK host API.
+    pub fn new(host: &HostImpl, guard: &LockGuard) -> Result<PortTracker> {
+        if !host.is(ffi::PaHostApiTypeId::paJACK) {
+            return Ok(PortTracker(None));
+        }
+        let client_name = client_name(guard)?;
+        let ports = Patchbay::open()?.client_ports(&client_name);
+        Ok(PortTracker(Some((client_name, ports))))
+    }
+
+    /// Returns the ports registered since the tracker was created.
+    pub fn stream_ports(
+        self,
+        direction: Direction,
+        autoconnect: bool,
+    ) -> Result<Option<StreamPorts>> {
+        let (client_name, before) = match self.0 {
+            Some(tracked) => tracked,
+            None => return Ok(None),
+        };
+        let names = Patchbay::open()?
+            .client_ports(&client_name)
+            .into_iter()
+            .filter(|port| !before.contains(port))
+            .collect();
+        Ok(Some(StreamPorts {
+            client_name,
+            names,
+            direction,
+            autoconnect,
+        }))
+    }
+}
+
+/// The JACK ports of a stream, one per channel.
+pub struct StreamPorts {
+    client_name: String,
+    /// Full port names (i.e. `client:port`).
+    names: Vec<String>,
+    direction: Direction,
+    /// Whether PortAudio's connections are kept when the stream starts.
+    autoconnect: bool,
+}
+
+impl StreamPorts {
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn autoconnects(&self) -> bool {
+        self.autoconnect
+    }
+
+    /// Renames the ports, given their short names (i.e. without the client prefix).
+    pub fn rename(&mut self, short_names: &[&str]) -> Result<()> {
+        if short_names.len() != self.names.len() {
+            return Err(Error::IncompatibleNChannels);
+        }
+        let patchbay = Patchbay::open()?;
+        for (name, short_name) in self.names.iter_mut().zip(short_names) {
+            patchbay.rename(name, short_name)?;
+            *name = format!("{}:{}", self.client_name, short_name);
+        }
+        Ok(())
+    }
+
+    /// Returns the full names of the ports the channel's port is connected to.
+    pub fn connections(&self, channel: usize) -> Result<Vec<String>> {
+        Patchbay::open()?.connections(self.name(channel)?)
+    }
+
+    /// Connects the channel's port to `other`, the full name of a port of another client. Does
+    /// nothing if they are already connected.
+    pub fn connect(&self, channel: usize, other: &str) -> Result<()> {
+        let (source, destination) = self.endpoints(channel, other)?;
+        Patchbay::open()?.connect(source, destination)
+    }
+
+    /// Disconnects the channel's port from `other`. Does nothing if they are not connected.
+    pub fn disconnect(&self, channel: usize, other: &str) -> Result<()> {
+        let (source, destination) = self.endpoints(channel, other)?;
+        Patchbay::open()?.disconnect(source, destination)
+    }
+
+    /// Returns the connections of each port, to tell PortAudio's connections apart once the
+    /// stream is started.
+    pub fn before_start(&self) -> Result<Vec<Vec<String>>> {
+        if self.autoconnect {
+            return Ok(Vec::new());
+        }
+        let patchbay = Patchbay::open()?;
+        self.names
+            .iter()
+            .map(|name| patchbay.connections(name))
+            .collect()
+    }
+
+    /// Undoes the connections PortAudio made when starting the stream, unless the host is
+    /// auto-connecting. PortAudio connects the ports before the stream's first callback, so the
+    /// stream must be kept silent until this returns.
+    pub fn after_start(&self, before: &[Vec<String>]) -> Result<()> {
+        if self.autoconnect {
+            return Ok(());
+        }
+        let patchbay = Patchbay::open()?;
+        for (channel, before) in before.iter().enumerate() {
+            for other in patchbay.connections(&self.names[channel])? {
+                if !before.contains(&other) {
+                    let (source, destination) = self.endpoints(channel, &other)?;
+                    patchbay.disconnect(source, destination)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn name(&self, channel: usize) -> Result<&str> {
+        self.names
+            .get(channel)
+            .map(String::as_str)
+            .ok_or(Error::IncompatibleNChannels)
+    }
+
+    /// Returns the (source, destination) pair connecting the channel's port and `other`.
+    fn endpoints<'a>(&'a self, channel: usize, other: &'a str) -> Result<(&'a str, &'a str)> {
+        let name = self.name(channel)?;
+        Ok(match self.direction {
+            Direction::Input => (other, name),
+            _ => (name, other),
+        })
+    }
+}
+
+/// A helper client, for listing and patching ports.
+struct Patchbay(*mut jack::jack_client_t);
+
+impl Patchbay {
+    fn open() -> Result<Patchbay> {
+        let mut status = 0;
+        let client = unsafe {
+            jack::jack_client_open(
+                b"audiohal-patchbay\0".as_ptr() as *const c_char,
+                jack::JackNoStartServer,
+                &mut status,
+            )
+        };
+        if client.is_null() {
+            return Err(Error::BackendUnavailable);
+        }
+        Ok(Patchbay(client))
+    }
+
+    /// Lists the full names of the ports of the client `client_name`, in registration order.
+    fn client_ports(&self, client_name: &str) -> Vec<String> {
+        let prefix = format!("{}:", client_name);
+        let ports = unsafe { jack::jack_get_ports(self.0, std::ptr::null(), std::ptr::null(), 0) };
+        take_port_names(ports)
+            .into_iter()
+            .filter(|port| port.starts_with(&prefix))
+            .collect()
+    }
+
+    fn port(&self, name: &str) -> Result<*mut jack::jack_port_t> {
+        let name = CString::new(name).or(Err(Error::Invalid))?;
+        let port = unsafe { jack::jack_port_by_name(self.0, name.as_ptr()) };
+        if port.is_null() {
+            return Err(Error::NoSuchDevice);
+        }
+        Ok(port)
+    }
+
+    fn connections(&self, name: &str) -> Result<Vec<String>> {
+        let port = self.port(name)?;
+        Ok(take_port_names(unsafe {
+            jack::jack_port_get_all_connections(self.0, port)
+        }))
+    }
+
+    fn connect(&self, source: &str, destination: &str) -> Result<()> {
+        self.port(source)?;
+        self.port(destination)?;
+        let source = CString::new(source).or(Err(Error::Invalid))?;
+        let destination = CString::new(destination).or(Err(Error::Invalid))?;
+        match unsafe { jack::jack_connect(self.0, source.as_ptr(), destination.as_ptr()) } {
+            0 | jack::EEXIST => Ok(()),
+            _ => Err(Error::Unknown("Could not connect the JACK ports.")),
+        }
+    }
+
+    fn disconnect(&self, source: &str, destination: &str) -> Result<()> {
+        if !self
+            .connections(source)?
+            .iter()
+            .any(|port| port == destination)
+        {
+            // Also checks that the destination exists.
+            return self.port(destination).and(Ok(()));
+        }
+        let source = CString::new(source).or(Err(Error::Invalid))?;
+        let destination = CString::new(destination).or(Err(Error::Invalid))?;
+        match unsafe { jack::jack_disconnect(self.0, source.as_ptr(), destination.as_ptr()) } {
+            0 => Ok(()),
+            _ => Err(Error::Unknown("Could not disconnect the JACK ports.")),
+        }
+    }
+
+    fn rename(&self, name: &str, short_name: &str) -> Result<()> {
+        let port = self.port(name)?;
+        let short_name = CString::new(short_name).or(Err(Error::Invalid))?;
+        match unsafe { jack::jack_port_rename(self.0, port, short_name.as_ptr()) } {
+            0 => Ok(()),
+            _ => Err(Error::Invalid),
+        }
+    }
+}
+
+impl Drop for Patchbay {
+    fn drop(&mut self) {
+        unsafe { jack::jack_client_close(self.0) };
+    }
+}
+
+/// Copies and frees a NULL-terminated array of port names, as returned by libjack.
+fn take_port_names(ports: *mut *const c_char) -> Vec<String> {
+    if ports.is_null() {
+        return Vec::new();
+    }
+    let mut names = Vec::new();
+    for index in 0.. {
+        let port = unsafe { *ports.add(index) };
+        if port.is_null() {
+            break;
+        }
+        names.push(
+            unsafe { CStr::from_ptr(port) }
+                .to_string_lossy()
+                .into_owned(),
+        );
+    }
+    unsafe { jack::jack_free(ports as *mut _) };
+    names
+}
+
+/// The subset of libjack used to patch ports.
+#[allow(non_camel_case_types, non_upper_case_globals)]
+mod jack {
+    use std::os::raw::{c_char, c_int, c_ulong, c_void};
+
+    pub enum jack_client_t {}
+    pub enum jack_port_t {}
+
+    /// `jack_options_t`: do not start a server if none is running.
+    pub const JackNoStartServer: c_int = 0x01;
+    /// Returned by `jack_connect` if the ports are already connected.
+    pub const EEXIST: c_int = 17;
+
+    #[link(name = "jack")]
+    extern "C" {
+        pub fn jack_client_open(
+            client_name: *const c_char,
+            options: c_int,
+            status: *mut c_int,
+            ...
+        ) -> *mut jack_client_t;
+        pub fn jack_client_close(client: *mut jack_client_t) -> c_int;
+        pub fn jack_get_ports(
+            client: *mut jack_client_t,
+            port_name_pattern: *const c_char,
+            type_name_pattern: *const c_char,
+            flags: c_ulong,
+        ) -> *mut *const c_char;
+        pub fn jack_port_by_name(
+            client: *mut jack_client_t,
+            port_name: *const c_char,
+        ) -> *mut jack_port_t;
+        pub fn jack_port_get_all_connections(
+            client: *const jack_client_t,
+            port: *const jack_port_t,
+        ) -> *mut *const c_char;
+        pub fn jack_port_rename(
+            client: *mut jack_client_t,
+            port: *mut jack_port_t,
+            port_name: *const c_char,
+        ) -> c_int;
+        pub fn jack_connect(
+            client: *mut jack_client_t,
+            source_port: *const c_char,
+            destination_port: *const c_char,
+        ) -> c_int;
+        pub fn jack_disconnect(
+            client: *mut jack_client_t,
+            source_port: *const c_char,
+            destination_port: *const c_char,
+        ) -> c_int;
+        pub fn jack_free(ptr: *mut c_void);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+    use crate::portaudio::test_prelude::*;
+    use crate::portaudio::{Host, Stream};
+    use crate::test_daemon::Daemon;
+    use crate::Backend;
+    use std::process::Command;
+
+    /// The dummy driver's playback ports.
+    const PLAYBACK_1: &str = "system:playback_1";
+    const PLAYBACK_2: &str = "system:playback_2";
+
+    /// Spawns a private `jackd` with a dummy driver, and makes it the default server of the
+    /// process.
+    ///
+    /// `JACK_DEFAULT_SERVER` is process-wide, so it is only set under the test lock, which every
+    /// test using PortAudio (and through it, libjack) holds.
+    fn spawn_server(name: &str, _test_lock: &LockGuard) -> Daemon {
+        let server_name = format!("audiohal-{}-{}", name, std::process::id());
+        std::env::set_var("JACK_DEFAULT_SERVER", &server_name);
+        Daemon::spawn(
+            Command::new("jackd")
+                .args(&["--no-realtime", "-n", &server_name])
+                .args(&["-d", "dummy", "-r", "48000", "-p", "256"])
+                .env("JACK_NO_AUDIO_RESERVATION", "1"),
+            None,
+            || super::Patchbay::open().is_ok(),
+        )
+    }
+
+    fn open_stream(host: &mut Host) -> Result<Stream<[f32; 2]>> {
+        host.default_output_device()?
+            .open_outstream(StreamOptions::<[f32; 2]>::default())
+    }
+
+    #[test]
+    #[ignore = "needs jackd"]
+    fn names_client() -> Result<()> {
+        let test_lock = test_lock();
+        let _server = spawn_server("name", &test_lock);
+        let host = Host::with_jack_options("audiohal-test", true)?;
+        assert!(host.jack_client_name()?.starts_with("audiohal-test"));
+        // The client cannot be renamed while it is registered.
+        assert_eq!(
+            Host::with_jack_options("audiohal-other", true).err(),
+            Some(Error::Invalid)
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs jackd"]
+    fn tracks_stream_ports() -> Result<()> {
+        let test_lock = test_lock();
+        let _server = spawn_server("ports", &test_lock);
+        let mut host = Host::with_jack_options("audiohal-test", true)?;
+        let prefix = format!("{}:", host.jack_client_name()?);
+        let first = open_stream(&mut host)?;
+        let second = open_stream(&mut host)?;
+        for stream in &[&first, &second] {
+            let names = stream.jack_ports()?.names();
+            assert_eq!(names.len(), 2);
+            assert!(names.iter().all(|name| name.starts_with(&prefix)));
+        }
+        assert_ne!(first.jack_ports()?.names(), second.jack_ports()?.names());
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs jackd"]
+    fn autoconnects_by_default() -> Result<()> {
+        let test_lock = test_lock();
+        let _server = spawn_server("autoconnect", &test_lock);
+        let mut host = Host::with_backend(Backend::Jack)?;
+        let mut stream = open_stream(&mut host)?;
+        stream.start()?;
+        assert_eq!(stream.jack_ports()?.connections(0)?, vec![PLAYBACK_1]);
+        assert_eq!(stream.jack_ports()?.connections(1)?, vec![PLAYBACK_2]);
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs jackd"]
+    fn connects_and_disconnects_ports() -> Result<()> {
+        let test_lock = test_lock();
+        let _server = spawn_server("connect", &test_lock);
+        let mut host = Host::with_jack_options("audiohal-test", false)?;
+        let mut stream = open_stream(&mut host)?;
+        stream.start()?;
+        let ports = stream.jack_ports()?;
+        assert!(ports.connections(0)?.is_empty());
+        // Swap the channels.
+        ports.connect(0, PLAYBACK_2)?;
+        ports.connect(0, PLAYBACK_2)?;
+        assert_eq!(ports.connections(0)?, vec![PLAYBACK_2]);
+        ports.disconnect(0, PLAYBACK_2)?;
+        ports.disconnect(0, PLAYBACK_2)?;
+        assert!(ports.connections(0)?.is_empty());
+        assert_eq!(
+            ports.connect(0, "nonexistent:port"),
+            Err(Error::NoSuchDevice)
+        );
+        assert_eq!(
+            ports.connect(2, PLAYBACK_1),
+            Err(Error::IncompatibleNChannels)
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs jackd"]
+    fn renames_ports() -> Result<()> {
+        let test_lock = test_lock();
+        let _server = spawn_server("rename", &test_lock);
+        let mut host = Host::with_jack_options("audiohal-test", false)?;
+        let client_name = host.jack_client_name()?;
+        let mut stream = open_stream(&mut host)?;
+        stream.jack_ports_mut()?.rename(&["music_l", "music_r"])?;
+        assert_eq!(
+            stream.jack_ports()?.names(),
+            &[
+                format!("{}:music_l", client_name),
+                format!("{}:music_r", client_name)
+            ]
+        );
+        stream.jack_ports()?.connect(1, PLAYBACK_2)?;
+        assert_eq!(stream.jack_ports()?.connections(1)?, vec![PLAYBACK_2]);
+        assert_eq!(
+            stream.jack_ports_mut()?.rename(&["mono"]),
+            Err(Error::IncompatibleNChannels)
+        );
+        Ok(())
+    }
+}
@@ -2,9 +2,15 @@ use lazy_static::lazy_static;
 use libportaudio_sys as ffi;
 use parking_lot::{ReentrantMutex, ReentrantMutexGuard};
 
+#[cfg(all(feature = "alsa", target_os = "linux"))]
+mod alsa;
 mod device;
 mod error;
 mod host;
+#[cfg(feature = "jack")]
+mod jack;
+#[cfg(feature = "oss")]
+mod oss;
 mod stream;
 mod stream_options;
 
@@ -16,6 +22,9 @@ pub use host::Host;
 pub use stream::Stream;
 
 lazy_static! {
+    /// Serializes the calls PortAudio requires to be serialized: initialization and termination,
+    /// the enumeration and probing of host APIs and devices, and the opening and closing of
+    /// streams, which PortAudio keeps in a global list.
     static ref GLOBAL_LOCK: ReentrantMutex<()> = ReentrantMutex::new(());
 }
 
@@ -25,6 +34,30 @@ fn global_lock() -> LockGuard {
     GLOBAL_LOCK.lock()
 }
 
+/// Returns the version of the linked PortAudio. Does not need PortAudio to be initialized.
+pub fn library_version() -> crate::LibraryVersion {
+    match unsafe { ffi::Pa_GetVersionInfo().as_ref() } {
+        Some(info) => crate::LibraryVersion {
+            major: info.versionMajor,
+            minor: info.versionMinor,
+            sub_minor: info.versionSubMinor,
+            revision: error::to_string(info.versionControlRevision),
+            text: error::to_string(info.versionText),
+        },
+        // Builds without version info still encode the version as 0xMMmmss.
+        None => {
+            let version = unsafe { ffi::Pa_GetVersion() };
+            crate::LibraryVersion {
+                major: version >> 16,
+                minor: (version >> 8) & 0xff,
+                sub_minor: version & 0xff,
+                revision: String::new(),
+                text: error::to_string(unsafe { ffi::Pa_GetVersionText() }),
+            }
+        }
+    }
+}
+
 impl std::convert::TryFrom<crate::Format> for ffi::PaSampleFormat {
     type Error = crate::error::Error;
     fn try_from(format: crate::Format) -> crate::error::Result<ffi::PaSampleFormat> {
@@ -0,0 +1,73 @@
+//! Tests of the PortAudio OSS host API, against the system's `/dev/dsp` or a local `osspd`
+//! emulation.
+
+#[cfg(test)]
+mod tests {
+    use crate::oss::{self, DeviceExt as _};
+    use crate::portaudio::test_prelude::*;
+    use crate::test_daemon::Daemon;
+    use crate::Backend;
+    use std::path::Path;
+    use std::process::Command;
+    use std::time::Duration;
+
+    /// Keeps `/dev/dsp` available, spawning `osspd` if there is no OSS driver. Panics if
+    /// `osspd` cannot create it (e.g. it is not installed, or CUSE is unavailable).
+    fn emulate_dsp() -> Option<Daemon> {
+        let dsp = Path::new("/dev/dsp");
+        if dsp.exists() {
+            return None;
+        }
+        Some(Daemon::spawn(Command::new("osspd").arg("-f"), None, || {
+            dsp.exists()
+        }))
+    }
+
+    #[test]
+    #[ignore = "needs /dev/dsp or osspd"]
+    fn enumerates_device_nodes() -> Result<()> {
+        begin!();
+        let _emulation = emulate_dsp();
+        let paths = crate::Host::with_backend(Backend::LinuxFallback)?
+            .devices()?
+            .iter()
+            .map(|device| device.oss_path())
+            .collect::<Result<Vec<_>>>()?;
+        let nodes = oss::device_paths()?;
+        assert!(!paths.is_empty());
+        assert_eq!(paths[0], Path::new("/dev/dsp"));
+        // PortAudio stops probing at the first missing node.
+        assert_eq!(paths, nodes[..paths.len()].to_vec());
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs /dev/dsp or osspd"]
+    fn calls_back_with_frames_per_buffer() -> Result<()> {
+        begin!();
+        let _emulation = emulate_dsp();
+        let mut host = crate::Host::with_backend(Backend::LinuxFallback)?;
+        // Fragments are a power of two bytes, which 300 frames are not.
+        for &frames_per_buffer in &[256, 300] {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            let mut stream = host
+                .default_output_device()?
+                .open_outstream(StreamOptions::<[i16; 2]> {
+                    format: crate::Format::I16,
+                    frames_per_buffer: Some(frames_per_buffer),
+                    callback: Box::new(move |buffer: &mut [[i16; 2]]| {
+                        let _ = sender.send(buffer.len());
+                    }),
+                    ..Default::default()
+                })?;
+            stream.start()?;
+            for _ in 0..4 {
+                assert_eq!(
+                    receiver.recv_timeout(Duration::from_secs(5)),
+                    Ok(frames_per_buffer as usize)
+                );
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,11 +1,16 @@
+use std::time::Duration;
+
+#[cfg(feature = "jack")]
+use crate::error::Error;
 use crate::error::Result;
 use crate::portaudio::device::DeviceHandle;
+#[cfg(feature = "jack")]
+use crate::portaudio::jack::StreamPorts;
+use crate::stream::StreamState;
 
 use crate::portaudio::internal::stream as internal;
 
-/// A stream represents the flow of data in and out of an audio device. It's defined by its audio
-/// data format, the number of channels, and whether it is an input stream (e.g. a microphone) or
-/// an output stream (e.g. speakers).
+/// A Portaudio stream.
 pub struct Stream<Frame>(internal::StreamImpl<Frame>);
 
 // impl<Frame> StreamImpl<Frame> {
@@ -28,26 +33,73 @@ impl<Frame> Stream<Frame> {
         self.0.start()
     }
 
-    pub fn close(mut self) {
-        self.0
-            .close()
-            .expect("Could not close stream. No obvious way to cleanly handle error.")
+    pub fn latency(&self) -> Duration {
+        self.0.latency()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.0.is_active()
+    }
+
+    pub fn state(&self) -> StreamState {
+        self.0.state()
+    }
+
+    #[cfg(all(feature = "alsa", target_os = "linux"))]
+    pub fn enable_alsa_realtime_scheduling(&mut self, enable: bool) -> Result<()> {
+        self.0.enable_alsa_realtime_scheduling(enable)
+    }
+
+    #[cfg(all(feature = "alsa", target_os = "linux"))]
+    pub fn alsa_card(&self) -> Result<i32> {
+        self.0.alsa_card()
+    }
+
+    /// Returns the stream's JACK ports. Returns [`Error::WrongBackend`] if the stream does not
+    /// belong to the JACK host API.
+    #[cfg(feature = "jack")]
+    pub fn jack_ports(&self) -> Result<&StreamPorts> {
+        self.0.jack_ports().ok_or(Error::WrongBackend)
+    }
+
+    #[cfg(feature = "jack")]
+    pub fn jack_ports_mut(&mut self) -> Result<&mut StreamPorts> {
+        self.0.jack_ports_mut().ok_or(Error::WrongBackend)
+    }
+
+    pub fn close(mut self) -> Result<()> {
+        self.0.close()
     }
 }
 
 pub fn new_outstream<Frame>(
     params: internal::StreamOpenParams<Frame>,
     device: DeviceHandle,
-) -> Result<Stream<Frame>> {
+) -> Result<Stream<Frame>>
+where
+    Frame: sample::Frame + Send + 'static,
+{
     Ok(Stream(internal::StreamImpl::new_outstream(params, device)?))
 }
 
+pub fn new_instream<Frame>(
+    params: internal::StreamOpenParams<Frame>,
+    device: DeviceHandle,
+) -> Result<Stream<Frame>>
+where
+    Frame: sample::Frame + Send + 'static,
+{
+    Ok(Stream(internal::StreamImpl::new_instream(params, device)?))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::error::Error;
     use crate::portaudio::test_prelude::*;
-    use crate::SampleRate;
+    use crate::portaudio::{Host, Stream};
+    use crate::stream::StreamState;
+    use crate::{Latency, SampleRate, StreamFlags};
     use std::sync::Arc;
     use std::sync::{Condvar, Mutex};
     use std::thread;
@@ -100,6 +152,47 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn reports_latency() -> Result<()> {
+        begin!();
+        let stream = make_stream_with(StreamOptions {
+            latency: Latency::Exact(Duration::from_millis(50)),
+            ..Default::default()
+        })?;
+        assert_gt!(stream.latency(), Duration::from_millis(0));
+        Ok(())
+    }
+
+    #[test]
+    fn calls_back_with_fixed_block_size() -> Result<()> {
+        begin!();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut stream = make_stream_with(StreamOptions {
+            callback: Box::new(move |buffer: &mut [[f32; 2]]| {
+                let _ = sender.send(buffer.len());
+            }),
+            block_size: Some(256),
+            ..Default::default()
+        })?;
+        stream.start()?;
+        for _ in 0..4 {
+            assert_eq!(receiver.recv_timeout(Duration::from_secs(20)), Ok(256));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn errors_if_invalid_block_size() {
+        begin!();
+        assert_that!(
+            &make_stream_with(StreamOptions {
+                block_size: Some(0),
+                ..Default::default()
+            }),
+            maybe_err(eq(Error::InvalidBlockSize))
+        );
+    }
+
     #[test]
     fn errors_if_invalid_sample_rate() {
         begin!();
@@ -134,6 +227,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn errors_if_invalid_flags() {
+        begin!();
+        assert_that!(
+            &make_stream_with(StreamOptions {
+                flags: StreamFlags {
+                    never_drop_input: true,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            maybe_err(eq(Error::InvalidFlags))
+        );
+    }
+
     #[test]
     fn errors_if_unsupported_n_channels() {
         begin!();
@@ -152,4 +260,66 @@ mod tests {
             maybe_err(eq(Error::IncompatibleNChannels))
         );
     }
+
+    #[test]
+    fn controls_stream_while_global_lock_is_held() -> Result<()> {
+        begin!();
+        let mut stream = make_stream_with(StreamOptions::default())?;
+        // E.g. a slow Pa_OpenStream, or device probing, on another thread.
+        let guard = global_lock();
+        let (controlled, is_controlled) = std::sync::mpsc::channel();
+        let controller = thread::spawn(move || {
+            let result = stream
+                .start()
+                .map(|()| (stream.is_active(), stream.state()));
+            controlled.send(result).unwrap();
+            stream
+        });
+        let controlled = is_controlled.recv_timeout(Duration::from_secs(1));
+        drop(guard);
+        let stream = controller.join().unwrap();
+        assert_eq!(
+            controlled.expect("Controlling the stream waited for the global lock."),
+            Ok((true, StreamState::Active))
+        );
+        stream.close()
+    }
+
+    #[test]
+    fn opens_and_controls_streams_concurrently() -> Result<()> {
+        begin!();
+        const N_THREADS: usize = 4;
+        const N_ITERATIONS: usize = 20;
+        let host = Host::with_default_backend()?;
+        let mut threads: Vec<thread::JoinHandle<Result<()>>> = (0..N_THREADS)
+            .map(|_| {
+                let mut host = host.clone();
+                thread::spawn(move || {
+                    for _ in 0..N_ITERATIONS {
+                        let mut stream = host
+                            .default_output_device()?
+                            .open_outstream(StreamOptions::<[f32; 2]>::default())?;
+                        stream.start()?;
+                        assert_ne!(stream.state(), StreamState::Disconnected);
+                        stream.close()?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        // Probes devices in the meantime.
+        let mut prober = host.clone();
+        threads.push(thread::spawn(move || {
+            for _ in 0..N_ITERATIONS {
+                for device in prober.devices()? {
+                    let _ = device.id();
+                }
+            }
+            Ok(())
+        }));
+        for thread in threads {
+            thread.join().unwrap()?;
+        }
+        Ok(())
+    }
 }
@@ -1,9 +1,23 @@
 use libportaudio_sys as ffi;
 
-use ffi::PaSampleFormat;
+use ffi::{PaSampleFormat, PaStreamFlags};
 
 use crate::error::{Error, Result};
-use crate::stream_options::Format;
+use crate::stream_options::{Format, StreamFlags};
+
+impl From<StreamFlags> for PaStreamFlags {
+    fn from(flags: StreamFlags) -> PaStreamFlags {
+        let mut pa_flags = PaStreamFlags::PaNoFlag;
+        pa_flags.set(PaStreamFlags::PaClipOff, flags.clip_off);
+        pa_flags.set(PaStreamFlags::PaDitherOff, flags.dither_off);
+        pa_flags.set(PaStreamFlags::PaNeverDropInput, flags.never_drop_input);
+        pa_flags.set(
+            PaStreamFlags::PaPrimeOutputBuffersUsingStreamCallback,
+            flags.prime_output,
+        );
+        pa_flags
+    }
+}
 
 #[allow(dead_code)]
 pub fn unpack_pa_formats(format_bitfield: ffi::PaSampleFormat) -> Result<Vec<Format>> {
@@ -48,4 +62,20 @@ mod tests {
         )
         .is_err());
     }
+
+    #[test]
+    fn converts_stream_flags() {
+        assert_eq!(
+            PaStreamFlags::from(StreamFlags::default()),
+            PaStreamFlags::PaNoFlag
+        );
+        assert_eq!(
+            PaStreamFlags::from(StreamFlags {
+                clip_off: true,
+                prime_output: true,
+                ..Default::default()
+            }),
+            PaStreamFlags::PaClipOff | PaStreamFlags::PaPrimeOutputBuffersUsingStreamCallback
+        );
+    }
 }
@@ -0,0 +1,236 @@
+use std::ptr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::pulseaudio::stream::{Simple, Stream};
+use crate::pulseaudio::{error_from_code, ffi, to_c_string, PulseAudioOptions};
+use crate::reblock;
+use crate::stream_options::{check_frame_size, Direction, StreamOptions};
+use crate::{Backend, DeviceId, Format, Latency, SampleRate};
+
+/// The number of frames per callback when `frames_per_buffer` is unspecified.
+const DEFAULT_FRAMES_PER_BUFFER: i32 = 512;
+
+/// The server-side buffering requested for `Latency::Low` and `Latency::High`.
+const LOW_LATENCY: Duration = Duration::from_millis(20);
+const HIGH_LATENCY: Duration = Duration::from_millis(100);
+
+/// A sink or source, as reported by the server.
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    /// The server's identifier for the device (e.g. `alsa_output.pci-0000_00_1f.3.analog-stereo`).
+    pub name: String,
+    pub description: String,
+    pub direction: Direction,
+    pub sample_rate: i32,
+    pub n_channels: i32,
+}
+
+/// A sink or source of the PulseAudio backend.
+pub struct Device {
+    options: Arc<PulseAudioOptions>,
+    info: DeviceInfo,
+    /// Whether streams should follow the server's default device, instead of this device.
+    follows_default: bool,
+}
+
+impl Device {
+    pub fn new(options: Arc<PulseAudioOptions>, info: DeviceInfo, follows_default: bool) -> Device {
+        Device {
+            options,
+            info,
+            follows_default,
+        }
+    }
+
+    /// The device's description (e.g. "Built-in Audio Analog Stereo").
+    pub fn name(&self) -> &str {
+        &self.info.description
+    }
+
+    /// Server identifiers are unique, so the occurrence is always 0.
+    pub fn id(&self) -> DeviceId {
+        DeviceId {
+            backend: Backend::PulseAudio,
+            name: self.info.name.clone(),
+            occurrence: 0,
+            direction: self.info.direction,
+        }
+    }
+
+    pub fn open_outstream<Frame>(&mut self, options: StreamOptions<Frame>) -> Result<Stream<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        self.open(options, Direction::Output)
+    }
+
+    pub fn open_instream<Frame>(&mut self, options: StreamOptions<Frame>) -> Result<Stream<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        self.open(options, Direction::Input)
+    }
+
+    fn open<Frame>(
+        &self,
+        options: StreamOptions<Frame>,
+        direction: Direction,
+    ) -> Result<Stream<Frame>>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        if direction != self.info.direction {
+            return Err(Error::IncompatibleDirection);
+        }
+        options
+            .flags
+            .validate(direction, options.frames_per_buffer)?;
+        if options.n_channels <= 0 || options.n_channels > ffi::PA_CHANNELS_MAX {
+            return Err(Error::IncompatibleNChannels);
+        }
+        let format = sample_format(options.format)?;
+        check_frame_size::<Frame>(options.format, options.n_channels)?;
+        // The server resamples streams to the device's rate.
+        let sample_rate = match options.sample_rate {
+            SampleRate::Exact(rate) | SampleRate::NearestTo(rate) => rate,
+            SampleRate::DeviceDefault => self.info.sample_rate,
+            _ => panic!("Non-exhaustive sample rate."),
+        };
+        if sample_rate <= 0 {
+            return Err(Error::IncompatibleSampleRate);
+        }
+        let frames_per_buffer = options
+            .frames_per_buffer
+            .unwrap_or(DEFAULT_FRAMES_PER_BUFFER);
+        if frames_per_buffer <= 0 {
+            return Err(Error::InvalidFramesPerBuffer);
+        }
+        let latency = match options.latency {
+            Latency::Low => LOW_LATENCY,
+            Latency::High => HIGH_LATENCY,
+            Latency::Exact(latency) => latency,
+            _ => panic!("Non-exhaustive latency."),
+        };
+
+        let frame_size = std::mem::size_of::<Frame>() as u32;
+        let buffer_bytes = frames_per_buffer as u32 * frame_size;
+        let latency_bytes =
+            (latency.as_secs_f64() * f64::from(sample_rate)).round() as u32 * frame_size;
+        let buffer_attr = ffi::pa_buffer_attr {
+            maxlength: u32::max_value(),
+            tlength: latency_bytes.max(buffer_bytes),
+            prebuf: u32::max_value(),
+            minreq: buffer_bytes,
+            fragsize: buffer_bytes,
+        };
+        let sample_spec = ffi::pa_sample_spec {
+            format,
+            rate: sample_rate as u32,
+            channels: options.n_channels as u8,
+        };
+        let server = self.options.server()?;
+        let application_name = self.options.application_name()?;
+        let device_name = to_c_string(&self.info.name)?;
+        let stream_name = to_c_string(options.name.as_deref().unwrap_or(match direction {
+            Direction::Input => "Capture",
+            _ => "Playback",
+        }))?;
+        let mut error = 0;
+        let simple = unsafe {
+            ffi::pa_simple_new(
+                server
+                    .as_ref()
+                    .map_or(ptr::null(), |server| server.as_ptr()),
+                application_name.as_ptr(),
+                match direction {
+                    Direction::Input => ffi::PA_STREAM_RECORD,
+                    _ => ffi::PA_STREAM_PLAYBACK,
+                },
+                // Without a device, the server routes the stream to its default device, and moves
+                // it when the default changes.
+                if self.follows_default {
+                    ptr::null()
+                } else {
+                    device_name.as_ptr()
+                },
+                stream_name.as_ptr(),
+                &sample_spec,
+                ptr::null(),
+                &buffer_attr,
+                &mut error,
+            )
+        };
+        if simple.is_null() {
+            return Err(error_from_code(error));
+        }
+        let simple = Simple::new(simple);
+        let callback = reblock::wrap(options.callback, options.block_size, direction)?;
+        let latency = latency
+            + options
+                .block_size
+                .map_or(Duration::default(), |block_size| {
+                    reblock::added_latency(block_size, sample_rate)
+                });
+        Ok(Stream::new(
+            simple,
+            callback,
+            direction,
+            frames_per_buffer as usize,
+            latency,
+        ))
+    }
+}
+
+/// Maps `format` to the native-endian Pulse sample format.
+fn sample_format(format: Format) -> Result<ffi::pa_sample_format_t> {
+    let big_endian = cfg!(target_endian = "big");
+    Ok(match format {
+        Format::F32 if big_endian => ffi::PA_SAMPLE_FLOAT32BE,
+        Format::F32 => ffi::PA_SAMPLE_FLOAT32LE,
+        Format::I32 if big_endian => ffi::PA_SAMPLE_S32BE,
+        Format::I32 => ffi::PA_SAMPLE_S32LE,
+        Format::I24 if big_endian => ffi::PA_SAMPLE_S24BE,
+        Format::I24 => ffi::PA_SAMPLE_S24LE,
+        Format::I16 if big_endian => ffi::PA_SAMPLE_S16BE,
+        Format::I16 => ffi::PA_SAMPLE_S16LE,
+        Format::U8 => ffi::PA_SAMPLE_U8,
+        format => return Err(Error::IncompatibleFormat(format)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_sample_formats() {
+        assert!(sample_format(Format::F32).is_ok());
+        assert_eq!(sample_format(Format::U8), Ok(ffi::PA_SAMPLE_U8));
+        assert_eq!(
+            sample_format(Format::I8),
+            Err(Error::IncompatibleFormat(Format::I8))
+        );
+    }
+
+    #[test]
+    fn errors_if_opened_in_wrong_direction() {
+        let mut sink = Device::new(
+            Arc::new(PulseAudioOptions::default()),
+            DeviceInfo {
+                name: "sink".to_string(),
+                description: "Sink".to_string(),
+                direction: Direction::Output,
+                sample_rate: 48_000,
+                n_channels: 2,
+            },
+            false,
+        );
+        assert_eq!(
+            sink.open_instream(StreamOptions::<[f32; 2]>::default())
+                .err(),
+            Some(Error::IncompatibleDirection)
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! The subset of the libpulse and libpulse-simple C APIs used by the PulseAudio backend.
+#![allow(non_camel_case_types)]
+use std::os::raw::{c_char, c_int, c_void};
+
+pub enum pa_mainloop {}
+pub enum pa_mainloop_api {}
+pub enum pa_context {}
+pub enum pa_operation {}
+pub enum pa_simple {}
+pub enum pa_channel_map {}
+
+pub type pa_context_state_t = c_int;
+pub const PA_CONTEXT_READY: pa_context_state_t = 4;
+pub const PA_CONTEXT_FAILED: pa_context_state_t = 5;
+pub const PA_CONTEXT_TERMINATED: pa_context_state_t = 6;
+
+pub type pa_operation_state_t = c_int;
+pub const PA_OPERATION_RUNNING: pa_operation_state_t = 0;
+
+pub type pa_stream_direction_t = c_int;
+pub const PA_STREAM_PLAYBACK: pa_stream_direction_t = 1;
+pub const PA_STREAM_RECORD: pa_stream_direction_t = 2;
+
+pub type pa_sample_format_t = c_int;
+pub const PA_SAMPLE_U8: pa_sample_format_t = 0;
+pub const PA_SAMPLE_S16LE: pa_sample_format_t = 3;
+pub const PA_SAMPLE_S16BE: pa_sample_format_t = 4;
+pub const PA_SAMPLE_FLOAT32LE: pa_sample_format_t = 5;
+pub const PA_SAMPLE_FLOAT32BE: pa_sample_format_t = 6;
+pub const PA_SAMPLE_S32LE: pa_sample_format_t = 7;
+pub const PA_SAMPLE_S32BE: pa_sample_format_t = 8;
+pub const PA_SAMPLE_S24LE: pa_sample_format_t = 9;
+pub const PA_SAMPLE_S24BE: pa_sample_format_t = 10;
+
+/// No such entity (e.g. an unknown sink or source).
+pub const PA_ERR_NOENTITY: c_int = 5;
+pub const PA_ERR_CONNECTIONREFUSED: c_int = 6;
+pub const PA_ERR_INVALID: c_int = 3;
+pub const PA_ERR_TIMEOUT: c_int = 8;
+pub const PA_ERR_CONNECTIONTERMINATED: c_int = 11;
+/// The server killed the stream (e.g. because its device was removed).
+pub const PA_ERR_KILLED: c_int = 12;
+pub const PA_ERR_NOTSUPPORTED: c_int = 19;
+
+/// The maximum number of channels supported by PulseAudio.
+pub const PA_CHANNELS_MAX: i32 = 32;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct pa_sample_spec {
+    pub format: pa_sample_format_t,
+    pub rate: u32,
+    pub channels: u8,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct pa_buffer_attr {
+    pub maxlength: u32,
+    pub tlength: u32,
+    pub prebuf: u32,
+    pub minreq: u32,
+    pub fragsize: u32,
+}
+
+/// The leading fields of `pa_sink_info` and `pa_source_info`, which share the same layout. The
+/// structs are only ever accessed through pointers provided by libpulse.
+#[repr(C)]
+pub struct pa_device_info_prefix {
+    pub name: *const c_char,
+    pub index: u32,
+    pub description: *const c_char,
+    pub sample_spec: pa_sample_spec,
+}
+
+/// The leading fields of `pa_server_info`.
+#[repr(C)]
+pub struct pa_server_info_prefix {
+    pub user_name: *const c_char,
+    pub host_name: *const c_char,
+    pub server_version: *const c_char,
+    pub server_name: *const c_char,
+    pub sample_spec: pa_sample_spec,
+    pub default_sink_name: *const c_char,
+    pub default_source_name: *const c_char,
+}
+
+pub type pa_device_info_cb_t = extern "C" fn(
+    context: *mut pa_context,
+    info: *const pa_device_info_prefix,
+    eol: c_int,
+    userdata: *mut c_void,
+);
+pub type pa_server_info_cb_t = extern "C" fn(
+    context: *mut pa_context,
+    info: *const pa_server_info_prefix,
+    userdata: *mut c_void,
+);
+
+#[link(name = "pulse")]
+extern "C" {
+    pub fn pa_mainloop_new() -> *mut pa_mainloop;
+    pub fn pa_mainloop_get_api(mainloop: *mut pa_mainloop) -> *mut pa_mainloop_api;
+    pub fn pa_mainloop_iterate(
+        mainloop: *mut pa_mainloop,
+        block: c_int,
+        retval: *mut c_int,
+    ) -> c_int;
+    pub fn pa_mainloop_free(mainloop: *mut pa_mainloop);
+
+    pub fn pa_context_new(api: *mut pa_mainloop_api, name: *const c_char) -> *mut pa_context;
+    pub fn pa_context_connect(
+        context: *mut pa_context,
+        server: *const c_char,
+        flags: c_int,
+        api: *const c_void,
+    ) -> c_int;
+    pub fn pa_context_get_state(context: *mut pa_context) -> pa_context_state_t;
+    pub fn pa_context_disconnect(context: *mut pa_context);
+    pub fn pa_context_unref(context: *mut pa_context);
+    pub fn pa_context_get_server_info(
+        context: *mut pa_context,
+        callback: pa_server_info_cb_t,
+        userdata: *mut c_void,
+    ) -> *mut pa_operation;
+    // The callbacks actually receive pa_sink_info and pa_source_info.
+    pub fn pa_context_get_sink_info_list(
+        context: *mut pa_context,
+        callback: pa_device_info_cb_t,
+        userdata: *mut c_void,
+    ) -> *mut pa_operation;
+    pub fn pa_context_get_source_info_list(
+        context: *mut pa_context,
+        callback: pa_device_info_cb_t,
+        userdata: *mut c_void,
+    ) -> *mut pa_operation;
+
+    pub fn pa_operation_get_state(operation: *mut pa_operation) -> pa_operation_state_t;
+    pub fn pa_operation_unref(operation: *mut pa_operation);
+
+    pub fn pa_strerror(error: c_int) -> *const c_char;
+}
+
+#[link(name = "pulse-simple")]
+extern "C" {
+    pub fn pa_simple_new(
+        server: *const c_char,
+        name: *const c_char,
+        direction: pa_stream_direction_t,
+        device: *const c_char,
+        stream_name: *const c_char,
+        sample_spec: *const pa_sample_spec,
+        channel_map: *const pa_channel_map,
+        buffer_attr: *const pa_buffer_attr,
+        error: *mut c_int,
+    ) -> *mut pa_simple;
+    pub fn pa_simple_write(
+        simple: *mut pa_simple,
+        data: *const c_void,
+        bytes: usize,
+        error: *mut c_int,
+    ) -> c_int;
+    pub fn pa_simple_read(
+        simple: *mut pa_simple,
+        data: *mut c_void,
+        bytes: usize,
+        error: *mut c_int,
+    ) -> c_int;
+    pub fn pa_simple_free(simple: *mut pa_simple);
+}
@@ -0,0 +1,253 @@
+use std::ffi::CStr;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+use crate::pulseaudio::device::{Device, DeviceInfo};
+use crate::pulseaudio::{ffi, PulseAudioOptions};
+use crate::stream_options::Direction;
+
+/// A host of the PulseAudio backend.
+///
+/// The host does not keep a connection to the server: every query connects anew, so that results
+/// always reflect the server's current state.
+#[derive(Clone)]
+pub struct Host(Arc<PulseAudioOptions>);
+
+impl Host {
+    /// Creates a host, checking that the server is reachable.
+    pub fn new(options: PulseAudioOptions) -> Result<Host> {
+        Connection::new(&options)?;
+        Ok(Host(Arc::new(options)))
+    }
+
+    pub fn name(&self) -> &str {
+        "PulseAudio"
+    }
+
+    /// Returns the server's sinks, followed by its sources.
+    pub fn devices(&self) -> Result<Vec<Device>> {
+        let connection = Connection::new(&self.0)?;
+        let mut devices = connection.devices(Direction::Output)?;
+        devices.extend(connection.devices(Direction::Input)?);
+        Ok(devices
+            .into_iter()
+            .map(|info| Device::new(Arc::clone(&self.0), info, false))
+            .collect())
+    }
+
+    pub fn default_output_device(&self) -> Result<Device> {
+        self.default_device(Direction::Output)
+    }
+
+    pub fn default_input_device(&self) -> Result<Device> {
+        self.default_device(Direction::Input)
+    }
+
+    fn default_device(&self, direction: Direction) -> Result<Device> {
+        let connection = Connection::new(&self.0)?;
+        let (default_sink, default_source) = connection.default_device_names()?;
+        let default_name = match direction {
+            Direction::Input => default_source,
+            _ => default_sink,
+        }
+        .ok_or(Error::NoSuchDevice)?;
+        let info = connection
+            .devices(direction)?
+            .into_iter()
+            .find(|info| info.name == default_name)
+            .ok_or(Error::NoSuchDevice)?;
+        Ok(Device::new(Arc::clone(&self.0), info, true))
+    }
+}
+
+/// A short-lived connection to the server, for queries.
+struct Connection {
+    mainloop: *mut ffi::pa_mainloop,
+    context: *mut ffi::pa_context,
+}
+
+impl Connection {
+    fn new(options: &PulseAudioOptions) -> Result<Connection> {
+        let application_name = options.application_name()?;
+        let server = options.server()?;
+        let mainloop = unsafe { ffi::pa_mainloop_new() };
+        if mainloop.is_null() {
+            return Err(Error::OutOfMemory);
+        }
+        let context = unsafe {
+            ffi::pa_context_new(
+                ffi::pa_mainloop_get_api(mainloop),
+                application_name.as_ptr(),
+            )
+        };
+        // From now on, Drop cleans up.
+        let connection = Connection { mainloop, context };
+        if context.is_null() {
+            return Err(Error::OutOfMemory);
+        }
+        let server = server
+            .as_ref()
+            .map_or(ptr::null(), |server| server.as_ptr());
+        if unsafe { ffi::pa_context_connect(context, server, 0, ptr::null()) } < 0 {
+            return Err(Error::BackendUnavailable);
+        }
+        loop {
+            match unsafe { ffi::pa_context_get_state(context) } {
+                ffi::PA_CONTEXT_READY => return Ok(connection),
+                ffi::PA_CONTEXT_FAILED | ffi::PA_CONTEXT_TERMINATED => {
+                    return Err(Error::BackendUnavailable)
+                }
+                _ => connection.iterate()?,
+            }
+        }
+    }
+
+    fn iterate(&self) -> Result<()> {
+        if unsafe { ffi::pa_mainloop_iterate(self.mainloop, 1, ptr::null_mut()) } < 0 {
+            return Err(Error::BackendUnavailable);
+        }
+        Ok(())
+    }
+
+    /// Waits for `operation` to complete.
+    fn wait(&self, operation: *mut ffi::pa_operation) -> Result<()> {
+        if operation.is_null() {
+            return Err(Error::Unknown("Could not query the Pulse server."));
+        }
+        let mut result = Ok(());
+        while unsafe { ffi::pa_operation_get_state(operation) } == ffi::PA_OPERATION_RUNNING {
+            result = self.iterate();
+            if result.is_err() {
+                break;
+            }
+        }
+        unsafe { ffi::pa_operation_unref(operation) };
+        result
+    }
+
+    /// Returns the names of the default sink and source.
+    fn default_device_names(&self) -> Result<(Option<String>, Option<String>)> {
+        extern "C" fn callback(
+            _context: *mut ffi::pa_context,
+            info: *const ffi::pa_server_info_prefix,
+            userdata: *mut c_void,
+        ) {
+            let names = unsafe { &mut *(userdata as *mut (Option<String>, Option<String>)) };
+            if let Some(info) = unsafe { info.as_ref() } {
+                *names = (
+                    to_string(info.default_sink_name),
+                    to_string(info.default_source_name),
+                );
+            }
+        }
+        let mut names = (None, None);
+        self.wait(unsafe {
+            ffi::pa_context_get_server_info(
+                self.context,
+                callback,
+                &mut names as *mut _ as *mut c_void,
+            )
+        })?;
+        Ok(names)
+    }
+
+    /// Returns the sinks (for `Output`) or sources (for `Input`).
+    fn devices(&self, direction: Direction) -> Result<Vec<DeviceInfo>> {
+        extern "C" fn callback(
+            _context: *mut ffi::pa_context,
+            info: *const ffi::pa_device_info_prefix,
+            _eol: c_int,
+            userdata: *mut c_void,
+        ) {
+            let query = unsafe { &mut *(userdata as *mut (Direction, Vec<DeviceInfo>)) };
+            // The list ends with a null info.
+            if let Some(info) = unsafe { info.as_ref() } {
+                query.1.push(DeviceInfo {
+                    name: to_string(info.name).unwrap_or_default(),
+                    description: to_string(info.description).unwrap_or_default(),
+                    direction: query.0,
+                    sample_rate: info.sample_spec.rate as i32,
+                    n_channels: i32::from(info.sample_spec.channels),
+                });
+            }
+        }
+        let mut query = (direction, Vec::new());
+        let userdata = &mut query as *mut _ as *mut c_void;
+        self.wait(unsafe {
+            match direction {
+                Direction::Input => {
+                    ffi::pa_context_get_source_info_list(self.context, callback, userdata)
+                }
+                _ => ffi::pa_context_get_sink_info_list(self.context, callback, userdata),
+            }
+        })?;
+        Ok(query.1)
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.context.is_null() {
+                ffi::pa_context_disconnect(self.context);
+                ffi::pa_context_unref(self.context);
+            }
+            ffi::pa_mainloop_free(self.mainloop);
+        }
+    }
+}
+
+fn to_string(string: *const std::os::raw::c_char) -> Option<String> {
+    if string.is_null() {
+        return None;
+    }
+    Some(
+        unsafe { CStr::from_ptr(string) }
+            .to_string_lossy()
+            .into_owned(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pulseaudio::test_server::{TestServer, SINK_DESCRIPTION};
+
+    #[test]
+    fn errors_if_server_unavailable() {
+        let result = Host::new(PulseAudioOptions {
+            server: Some("unix:/nonexistent/audiohal/native".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(result.err(), Some(Error::BackendUnavailable));
+    }
+
+    #[test]
+    #[ignore = "needs pulseaudio"]
+    fn enumerates_sinks_and_sources() -> Result<()> {
+        let server = TestServer::spawn("enumerate");
+        let host = Host::new(server.options())?;
+        let names: Vec<_> = host
+            .devices()?
+            .iter()
+            .map(|device| device.name().to_string())
+            .collect();
+        assert!(names.iter().any(|name| name == SINK_DESCRIPTION));
+        // Every sink has a monitor source.
+        assert!(names
+            .iter()
+            .any(|name| name.contains(SINK_DESCRIPTION) && name != SINK_DESCRIPTION));
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs pulseaudio"]
+    fn tracks_default_sink() -> Result<()> {
+        let server = TestServer::spawn("default");
+        let host = Host::new(server.options())?;
+        assert_eq!(host.default_output_device()?.name(), SINK_DESCRIPTION);
+        Ok(())
+    }
+}
@@ -0,0 +1,150 @@
+//! A native PulseAudio backend, talking to the Pulse server through libpulse instead of going
+//! through PortAudio's ALSA host API. Requires the `native-pulseaudio` feature.
+//!
+//! Devices are the server's sinks (outputs) and sources (inputs). The default devices follow the
+//! server's default sink and source: streams opened on them are moved by the server whenever the
+//! default changes.
+use std::ffi::{CStr, CString};
+
+use crate::backend::Backend;
+use crate::error::{Error, Result};
+
+mod device;
+mod ffi;
+mod host;
+mod stream;
+
+// Public API exports.
+pub use device::Device;
+pub use host::Host;
+pub use stream::Stream;
+
+/// Configures a host of the PulseAudio backend.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PulseAudioOptions {
+    /// The name the server shows for the application (e.g. in volume controls).
+    pub application_name: String,
+    /// The server to connect to (e.g. `unix:/run/user/1000/pulse/native`). If `None`, the default
+    /// server is used.
+    pub server: Option<String>,
+}
+
+impl Default for PulseAudioOptions {
+    fn default() -> PulseAudioOptions {
+        PulseAudioOptions {
+            application_name: "audiohal".to_string(),
+            server: None,
+        }
+    }
+}
+
+impl PulseAudioOptions {
+    fn application_name(&self) -> Result<CString> {
+        to_c_string(&self.application_name)
+    }
+
+    fn server(&self) -> Result<Option<CString>> {
+        self.server
+            .as_ref()
+            .map(|server| to_c_string(server))
+            .transpose()
+    }
+}
+
+fn to_c_string(string: &str) -> Result<CString> {
+    CString::new(string).or(Err(Error::Invalid))
+}
+
+/// Maps a libpulse error code.
+fn error_from_code(code: std::os::raw::c_int) -> Error {
+    match code {
+        ffi::PA_ERR_NOENTITY => Error::NoSuchDevice,
+        ffi::PA_ERR_CONNECTIONREFUSED => Error::BackendUnavailable,
+        ffi::PA_ERR_INVALID => Error::Invalid,
+        ffi::PA_ERR_NOTSUPPORTED => Error::Unknown("Operation not supported by the Pulse server."),
+        ffi::PA_ERR_TIMEOUT => Error::TimedOut,
+        _ => {
+            let text = unsafe { ffi::pa_strerror(code) };
+            Error::HostError {
+                backend: Backend::PulseAudio,
+                code: i64::from(code),
+                text: if text.is_null() {
+                    String::new()
+                } else {
+                    unsafe { CStr::from_ptr(text) }
+                        .to_string_lossy()
+                        .into_owned()
+                },
+            }
+        }
+    }
+}
+
+/// Whether a libpulse error code means that the stream lost its server or device, rather than
+/// failed.
+fn is_disconnection(code: std::os::raw::c_int) -> bool {
+    matches!(
+        code,
+        ffi::PA_ERR_CONNECTIONTERMINATED | ffi::PA_ERR_KILLED | ffi::PA_ERR_NOENTITY
+    )
+}
+
+/// A private Pulse server with a single null sink, for tests.
+#[cfg(test)]
+pub mod test_server {
+    use super::PulseAudioOptions;
+    use crate::test_daemon::Daemon;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    pub const SINK_DESCRIPTION: &str = "Test-Sink";
+
+    pub struct TestServer {
+        runtime_dir: PathBuf,
+        _daemon: Daemon,
+    }
+
+    impl TestServer {
+        /// Spawns `pulseaudio --daemonize=no` with the null sink module.
+        pub fn spawn(name: &str) -> TestServer {
+            let runtime_dir = Daemon::runtime_dir("pulse", name);
+            let socket = runtime_dir.join("native");
+            let daemon = Daemon::spawn(
+                Command::new("pulseaudio")
+                    .args(&[
+                        "-n",
+                        "--daemonize=no",
+                        "--use-pid-file=no",
+                        "--exit-idle-time=-1",
+                    ])
+                    .arg(format!(
+                        "--load=module-native-protocol-unix socket={} auth-anonymous=1",
+                        socket.display()
+                    ))
+                    .arg(format!(
+                        "--load=module-null-sink sink_name=audiohal_test \
+                         sink_properties=device.description={}",
+                        SINK_DESCRIPTION
+                    ))
+                    .env("XDG_RUNTIME_DIR", &runtime_dir)
+                    .env("HOME", &runtime_dir),
+                Some(&runtime_dir),
+                || socket.exists(),
+            );
+            TestServer {
+                runtime_dir,
+                _daemon: daemon,
+            }
+        }
+
+        pub fn options(&self) -> PulseAudioOptions {
+            PulseAudioOptions {
+                server: Some(format!(
+                    "unix:{}",
+                    self.runtime_dir.join("native").display()
+                )),
+                ..Default::default()
+            }
+        }
+    }
+}
@@ -0,0 +1,233 @@
+use std::marker::PhantomData;
+use std::os::raw::{c_int, c_void};
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::pulseaudio::{error_from_code, ffi, is_disconnection};
+use crate::stream::StreamState;
+use crate::stream_options::{Callback, Direction};
+
+/// An open connection of the simple API, for a single stream.
+pub struct Simple(*mut ffi::pa_simple);
+
+// The simple API can be used from any thread, as long as it is not used concurrently.
+unsafe impl Send for Simple {}
+
+impl Simple {
+    pub fn new(simple: *mut ffi::pa_simple) -> Simple {
+        debug_assert!(!simple.is_null());
+        Simple(simple)
+    }
+
+    /// Blocks until `data` was written. Returns the libpulse error code on failure.
+    fn write(&mut self, data: &[u8]) -> std::result::Result<(), c_int> {
+        let mut error = 0;
+        let result = unsafe {
+            ffi::pa_simple_write(
+                self.0,
+                data.as_ptr() as *const c_void,
+                data.len(),
+                &mut error,
+            )
+        };
+        if result < 0 {
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Blocks until `data` was filled. Returns the libpulse error code on failure.
+    fn read(&mut self, data: &mut [u8]) -> std::result::Result<(), c_int> {
+        let mut error = 0;
+        let result = unsafe {
+            ffi::pa_simple_read(
+                self.0,
+                data.as_mut_ptr() as *mut c_void,
+                data.len(),
+                &mut error,
+            )
+        };
+        if result < 0 {
+            return Err(error);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Simple {
+    fn drop(&mut self) {
+        unsafe { ffi::pa_simple_free(self.0) };
+    }
+}
+
+/// A stream of the PulseAudio backend, driven by its own thread doing blocking reads or writes.
+pub struct Stream<Frame> {
+    /// Processes a single buffer, and returns the libpulse error code that ends the stream, if
+    /// any. Moved to the stream's thread once started.
+    process: Option<Box<dyn FnMut() -> std::result::Result<(), c_int> + Send>>,
+    latency: Duration,
+    running: Arc<AtomicBool>,
+    /// The libpulse error code that ended the stream, or 0.
+    error: Arc<AtomicI32>,
+    thread: Option<JoinHandle<()>>,
+    _callback: PhantomData<Callback<Frame>>,
+}
+
+impl<Frame> Stream<Frame> {
+    pub fn new(
+        mut simple: Simple,
+        mut callback: Callback<Frame>,
+        direction: Direction,
+        frames_per_buffer: usize,
+        latency: Duration,
+    ) -> Stream<Frame>
+    where
+        Frame: sample::Frame + Send + 'static,
+    {
+        let mut buffer = vec![Frame::equilibrium(); frames_per_buffer];
+        let process = move || {
+            // Frames are arrays of samples, whose size was checked against the stream's format.
+            let n_bytes = buffer.len() * std::mem::size_of::<Frame>();
+            if direction == Direction::Input {
+                let bytes = unsafe {
+                    std::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut u8, n_bytes)
+                };
+                simple.read(bytes)?;
+                callback(&mut buffer);
+                Ok(())
+            } else {
+                callback(&mut buffer);
+                let bytes =
+                    unsafe { std::slice::from_raw_parts(buffer.as_ptr() as *const u8, n_bytes) };
+                simple.write(bytes)
+            }
+        };
+        Stream {
+            process: Some(Box::new(process)),
+            latency,
+            running: Arc::new(AtomicBool::new(false)),
+            error: Arc::new(AtomicI32::new(0)),
+            thread: None,
+            _callback: PhantomData,
+        }
+    }
+
+    /// Stream is inactive (i.e. no callback) until this method is called.
+    pub fn start(&mut self) -> Result<()> {
+        let mut process = self.process.take().ok_or(Error::StreamAlreadyStarted)?;
+        let running = Arc::clone(&self.running);
+        let error = Arc::clone(&self.error);
+        running.store(true, Ordering::Release);
+        self.thread = Some(
+            thread::Builder::new()
+                .name("audiohal-pulse-stream".to_string())
+                .spawn(move || {
+                    // The server paces the stream through the blocking reads and writes.
+                    while running.load(Ordering::Acquire) {
+                        if let Err(code) = process() {
+                            error.store(code, Ordering::Relaxed);
+                            running.store(false, Ordering::Release);
+                        }
+                    }
+                })
+                .or(Err(Error::Unknown(
+                    "Could not spawn the PulseAudio stream thread.",
+                )))?,
+        );
+        Ok(())
+    }
+
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.running.load(Ordering::Acquire)
+    }
+
+    /// The stream's thread only stops on its own if a read or write failed.
+    pub fn state(&self) -> StreamState {
+        if self.is_active() {
+            return StreamState::Active;
+        }
+        match self.error.load(Ordering::Relaxed) {
+            0 => StreamState::Stopped,
+            code if is_disconnection(code) => StreamState::Disconnected,
+            code => StreamState::Failed(error_from_code(code)),
+        }
+    }
+
+    pub fn close(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<Frame> Drop for Stream<Frame> {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            // A panicking callback already reported its panic on the stream thread.
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pulseaudio::test_server::TestServer;
+    use crate::pulseaudio::Host;
+    use crate::StreamOptions;
+    use std::sync::mpsc;
+
+    #[test]
+    fn stream_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<Stream<[f32; 2]>>();
+    }
+
+    #[test]
+    #[ignore = "needs pulseaudio"]
+    fn plays_to_null_sink() -> Result<()> {
+        let server = TestServer::spawn("play");
+        let (sender, receiver) = mpsc::channel();
+        let mut stream = Host::new(server.options())?
+            .default_output_device()?
+            .open_outstream(StreamOptions {
+                callback: Box::new(move |buffer: &mut [[f32; 2]]| {
+                    let _ = sender.send(buffer.len());
+                }),
+                frames_per_buffer: Some(256),
+                name: Some("audiohal test".to_string()),
+                ..Default::default()
+            })?;
+        stream.start()?;
+        for _ in 0..4 {
+            assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), Ok(256));
+        }
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs pulseaudio"]
+    fn records_from_monitor_source() -> Result<()> {
+        let server = TestServer::spawn("record");
+        let (sender, receiver) = mpsc::channel();
+        let mut stream = Host::new(server.options())?
+            .default_input_device()?
+            .open_instream(StreamOptions {
+                callback: Box::new(move |buffer: &mut [[i16; 2]]| {
+                    let _ = sender.send(buffer.len());
+                }),
+                frames_per_buffer: Some(128),
+                ..Default::default()
+            })?;
+        stream.start()?;
+        assert_eq!(receiver.recv_timeout(Duration::from_secs(5)), Ok(128));
+        assert!(stream.is_active());
+        Ok(())
+    }
+}
@@ -0,0 +1,176 @@
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::stream_options::{Callback, Direction};
+
+/// Adapts a stream callback so that it is always called with exactly `block_size` frames, no
+/// matter how many frames the backend asks for.
+///
+/// Output is rendered one block ahead: whatever the backend does not consume is kept until the
+/// next backend callback. Input is accumulated until a full block is available.
+pub struct Reblocker<Frame> {
+    callback: Callback<Frame>,
+    block: Vec<Frame>,
+    /// Index of the next frame in `block` to be exchanged with the backend.
+    position: usize,
+}
+
+impl<Frame> Reblocker<Frame>
+where
+    Frame: sample::Frame,
+{
+    pub fn for_output(callback: Callback<Frame>, block_size: usize) -> Reblocker<Frame> {
+        debug_assert_gt!(block_size, 0);
+        Reblocker {
+            callback,
+            block: vec![Frame::equilibrium(); block_size],
+            // Start with an exhausted block so that the first request renders a new one.
+            position: block_size,
+        }
+    }
+
+    pub fn for_input(callback: Callback<Frame>, block_size: usize) -> Reblocker<Frame> {
+        debug_assert_gt!(block_size, 0);
+        Reblocker {
+            callback,
+            block: vec![Frame::equilibrium(); block_size],
+            position: 0,
+        }
+    }
+
+    /// Fills `output` with frames rendered by the user callback in fixed-size blocks.
+    pub fn fill_output(&mut self, output: &mut [Frame]) {
+        let mut written = 0;
+        while written < output.len() {
+            if self.position == self.block.len() {
+                (self.callback)(&mut self.block);
+                self.position = 0;
+            }
+            let n_frames = (self.block.len() - self.position).min(output.len() - written);
+            output[written..written + n_frames]
+                .copy_from_slice(&self.block[self.position..self.position + n_frames]);
+            self.position += n_frames;
+            written += n_frames;
+        }
+    }
+
+    /// Accumulates `input`, calling the user callback for every full block.
+    pub fn consume_input(&mut self, input: &[Frame]) {
+        let mut read = 0;
+        while read < input.len() {
+            let n_frames = (self.block.len() - self.position).min(input.len() - read);
+            self.block[self.position..self.position + n_frames]
+                .copy_from_slice(&input[read..read + n_frames]);
+            self.position += n_frames;
+            read += n_frames;
+            if self.position == self.block.len() {
+                (self.callback)(&mut self.block);
+                self.position = 0;
+            }
+        }
+    }
+}
+
+/// Wraps `callback` into a callback that can be called with any number of frames.
+pub fn reblock_output<Frame>(callback: Callback<Frame>, block_size: usize) -> Callback<Frame>
+where
+    Frame: sample::Frame + Send + 'static,
+{
+    let mut reblocker = Reblocker::for_output(callback, block_size);
+    Box::new(move |output| reblocker.fill_output(output))
+}
+
+/// Wraps `callback` into a callback that can be called with any number of input frames.
+pub fn reblock_input<Frame>(callback: Callback<Frame>, block_size: usize) -> Callback<Frame>
+where
+    Frame: sample::Frame + Send + 'static,
+{
+    let mut reblocker = Reblocker::for_input(callback, block_size);
+    Box::new(move |input| reblocker.consume_input(input))
+}
+
+/// Re-blocks `callback` if a block size was requested.
+pub fn wrap<Frame>(
+    callback: Callback<Frame>,
+    block_size: Option<i32>,
+    direction: Direction,
+) -> Result<Callback<Frame>>
+where
+    Frame: sample::Frame + Send + 'static,
+{
+    Ok(match block_size {
+        Some(block_size) if block_size <= 0 => return Err(Error::InvalidBlockSize),
+        Some(block_size) if direction == Direction::Input => {
+            reblock_input(callback, block_size as usize)
+        }
+        Some(block_size) => reblock_output(callback, block_size as usize),
+        None => callback,
+    })
+}
+
+/// The worst-case latency added by re-blocking.
+pub fn added_latency(block_size: i32, sample_rate: i32) -> Duration {
+    Duration::from_secs_f64(f64::from(block_size) / f64::from(sample_rate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Creates a callback that writes an increasing counter, and records its block sizes.
+    fn counting_callback(block_sizes: Arc<Mutex<Vec<usize>>>) -> Callback<[f32; 1]> {
+        let mut counter = 0.0;
+        Box::new(move |buffer: &mut [[f32; 1]]| {
+            block_sizes.lock().unwrap().push(buffer.len());
+            for frame in buffer.iter_mut() {
+                *frame = [counter];
+                counter += 1.0;
+            }
+        })
+    }
+
+    #[test]
+    fn delivers_fixed_size_blocks() {
+        let block_sizes = Arc::default();
+        let mut reblocker = Reblocker::for_output(counting_callback(Arc::clone(&block_sizes)), 4);
+        let mut output = Vec::new();
+        for &request in &[3, 1, 7, 2, 5] {
+            let mut buffer = vec![[0.0]; request];
+            reblocker.fill_output(&mut buffer);
+            output.extend(buffer);
+        }
+        assert!(block_sizes.lock().unwrap().iter().all(|&size| size == 4));
+        // The output must be a continuous ramp, without any dropped or repeated frames.
+        for (i, frame) in output.iter().enumerate() {
+            assert_eq!(frame[0], i as f32);
+        }
+    }
+
+    #[test]
+    fn does_not_render_ahead_when_aligned() {
+        let block_sizes = Arc::default();
+        let mut reblocker = Reblocker::for_output(counting_callback(Arc::clone(&block_sizes)), 4);
+        reblocker.fill_output(&mut [[0.0]; 8]);
+        assert_eq!(*block_sizes.lock().unwrap(), vec![4, 4]);
+    }
+
+    #[test]
+    fn accumulates_input_into_fixed_size_blocks() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let received_clone = Arc::clone(&received);
+        let mut reblocker = Reblocker::for_input(
+            Box::new(move |buffer: &mut [[f32; 1]]| {
+                assert_eq!(buffer.len(), 4);
+                received_clone.lock().unwrap().extend_from_slice(buffer);
+            }),
+            4,
+        );
+        let input: Vec<_> = (0..15).map(|i| [i as f32]).collect();
+        for chunk in input.chunks(3) {
+            reblocker.consume_input(chunk);
+        }
+        // Only full blocks are delivered; the last 3 frames are still pending.
+        assert_eq!(*received.lock().unwrap(), input[..12].to_vec());
+    }
+}
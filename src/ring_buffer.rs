@@ -0,0 +1,142 @@
+//! A lock-free single-producer single-consumer ring buffer, used to exchange frames with the
+//! real-time audio callback without locking.
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+struct Shared<T> {
+    buffer: Box<[UnsafeCell<T>]>,
+    /// Total number of elements ever read. Only written by the reader.
+    read_count: AtomicUsize,
+    /// Total number of elements ever written. Only written by the writer.
+    write_count: AtomicUsize,
+}
+
+// The reader and writer never access the same slots at the same time.
+unsafe impl<T: Send> Sync for Shared<T> {}
+
+impl<T> Shared<T> {
+    fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    fn len(&self) -> usize {
+        self.write_count
+            .load(Ordering::Acquire)
+            .wrapping_sub(self.read_count.load(Ordering::Acquire))
+    }
+
+    fn slot(&self, count: usize) -> *mut T {
+        self.buffer[count % self.capacity()].get()
+    }
+}
+
+/// The writing half of a ring buffer.
+pub struct Writer<T>(Arc<Shared<T>>);
+
+/// The reading half of a ring buffer.
+pub struct Reader<T>(Arc<Shared<T>>);
+
+/// Creates a ring buffer that holds at most `capacity` elements. `fill` is only used to
+/// initialize the storage, and is never read.
+pub fn new<T: Copy>(capacity: usize, fill: T) -> (Writer<T>, Reader<T>) {
+    assert_gt!(capacity, 0);
+    let shared = Arc::new(Shared {
+        buffer: (0..capacity).map(|_| UnsafeCell::new(fill)).collect(),
+        read_count: AtomicUsize::new(0),
+        write_count: AtomicUsize::new(0),
+    });
+    (Writer(Arc::clone(&shared)), Reader(shared))
+}
+
+impl<T: Copy> Writer<T> {
+    /// Writes as many elements of `data` as fit, and returns how many were written.
+    pub fn push_slice(&mut self, data: &[T]) -> usize {
+        let write_count = self.0.write_count.load(Ordering::Relaxed);
+        let n = self.free_len().min(data.len());
+        for (i, &value) in data[..n].iter().enumerate() {
+            unsafe { *self.0.slot(write_count.wrapping_add(i)) = value };
+        }
+        self.0
+            .write_count
+            .store(write_count.wrapping_add(n), Ordering::Release);
+        n
+    }
+
+    /// The number of elements that can currently be written.
+    pub fn free_len(&self) -> usize {
+        self.0.capacity() - self.0.len()
+    }
+}
+
+impl<T: Copy> Reader<T> {
+    /// Reads as many elements as are available into `data`, and returns how many were read.
+    pub fn pop_slice(&mut self, data: &mut [T]) -> usize {
+        let read_count = self.0.read_count.load(Ordering::Relaxed);
+        let n = self.len().min(data.len());
+        for (i, value) in data[..n].iter_mut().enumerate() {
+            *value = unsafe { *self.0.slot(read_count.wrapping_add(i)) };
+        }
+        self.0
+            .read_count
+            .store(read_count.wrapping_add(n), Ordering::Release);
+        n
+    }
+
+    /// The number of elements that can currently be read.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_back_what_was_written() {
+        let (mut writer, mut reader) = new(4, 0);
+        assert_eq!(writer.push_slice(&[1, 2, 3]), 3);
+        let mut data = [0; 2];
+        assert_eq!(reader.pop_slice(&mut data), 2);
+        assert_eq!(data, [1, 2]);
+        // Wraps around the end of the storage.
+        assert_eq!(writer.push_slice(&[4, 5, 6, 7]), 3);
+        let mut data = [0; 8];
+        assert_eq!(reader.pop_slice(&mut data), 4);
+        assert_eq!(&data[..4], &[3, 4, 5, 6]);
+        assert_eq!(reader.len(), 0);
+    }
+
+    #[test]
+    fn never_exceeds_capacity() {
+        let (mut writer, reader) = new(3, 0);
+        assert_eq!(writer.push_slice(&[1, 2, 3, 4, 5]), 3);
+        assert_eq!(writer.free_len(), 0);
+        assert_eq!(writer.push_slice(&[6]), 0);
+        assert_eq!(reader.len(), 3);
+    }
+
+    #[test]
+    fn transfers_across_threads_in_order() {
+        const N: usize = 10_000;
+        let (mut writer, mut reader) = new(64, 0);
+        let producer = std::thread::spawn(move || {
+            let data: Vec<usize> = (0..N).collect();
+            let mut written = 0;
+            while written < N {
+                written += writer.push_slice(&data[written..]);
+            }
+        });
+        let mut expected = 0;
+        let mut data = [0; 17];
+        while expected < N {
+            let n = reader.pop_slice(&mut data);
+            for &value in &data[..n] {
+                assert_eq!(value, expected);
+                expected += 1;
+            }
+        }
+        producer.join().unwrap();
+    }
+}
@@ -0,0 +1,141 @@
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::follow;
+use crate::null;
+#[cfg(feature = "native-pipewire")]
+use crate::pipewire;
+use crate::portaudio;
+#[cfg(feature = "native-pulseaudio")]
+use crate::pulseaudio;
+
+/// A stream represents the flow of data in and out of an audio device. It's defined by its audio
+/// data format, the number of channels, and whether it is an input stream (e.g. a microphone) or
+/// an output stream (e.g. speakers).
+pub struct Stream<Frame>(StreamImpl<Frame>);
+
+/// The state of a stream, see [`Stream::state`].
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamState {
+    /// The stream was not started yet, or stopped on its own (e.g. at the end of a file).
+    Stopped,
+    /// The stream is calling its callback.
+    Active,
+    /// The stream's device was disconnected (e.g. unplugged), or failed. The stream no longer
+    /// calls its callback, unless reopened according to its
+    /// [`Reconnect`](crate::Reconnect) policy.
+    Disconnected,
+    /// The stream stopped because of an error, e.g. its file could not be read or written, or
+    /// its server rejected its samples.
+    Failed(Error),
+}
+
+pub enum StreamImpl<Frame> {
+    PortAudio(portaudio::Stream<Frame>),
+    Null(null::Stream<Frame>),
+    Following(follow::Stream<Frame>),
+    #[cfg(feature = "native-pulseaudio")]
+    PulseAudio(pulseaudio::Stream<Frame>),
+    #[cfg(feature = "native-pipewire")]
+    PipeWire(pipewire::Stream<Frame>),
+}
+
+impl<Frame> Stream<Frame> {
+    pub(crate) fn new(stream: StreamImpl<Frame>) -> Stream<Frame> {
+        Stream(stream)
+    }
+
+    /// Returns the PortAudio stream, for the extensions of its host APIs.
+    #[cfg(any(all(feature = "alsa", target_os = "linux"), feature = "jack"))]
+    pub(crate) fn as_portaudio(&self) -> Option<&portaudio::Stream<Frame>> {
+        match &self.0 {
+            StreamImpl::PortAudio(stream) => Some(stream),
+            _ => None,
+        }
+    }
+
+    #[cfg(any(all(feature = "alsa", target_os = "linux"), feature = "jack"))]
+    pub(crate) fn as_portaudio_mut(&mut self) -> Option<&mut portaudio::Stream<Frame>> {
+        match &mut self.0 {
+            StreamImpl::PortAudio(stream) => Some(stream),
+            _ => None,
+        }
+    }
+
+    /// Starts the stream. The stream is inactive (i.e. its callback is not called) until this
+    /// method is called.
+    pub fn start(&mut self) -> Result<()> {
+        match &mut self.0 {
+            StreamImpl::PortAudio(stream) => stream.start(),
+            StreamImpl::Null(stream) => stream.start(),
+            StreamImpl::Following(stream) => stream.start(),
+            #[cfg(feature = "native-pulseaudio")]
+            StreamImpl::PulseAudio(stream) => stream.start(),
+            #[cfg(feature = "native-pipewire")]
+            StreamImpl::PipeWire(stream) => stream.start(),
+        }
+    }
+
+    /// Returns the stream's latency, as reported by the backend once the stream was opened.
+    ///
+    /// This may differ from the [`Latency`](crate::Latency) requested in
+    /// [`StreamOptions`](crate::StreamOptions).
+    pub fn latency(&self) -> Duration {
+        match &self.0 {
+            StreamImpl::PortAudio(stream) => stream.latency(),
+            StreamImpl::Null(stream) => stream.latency(),
+            StreamImpl::Following(stream) => stream.latency(),
+            #[cfg(feature = "native-pulseaudio")]
+            StreamImpl::PulseAudio(stream) => stream.latency(),
+            #[cfg(feature = "native-pipewire")]
+            StreamImpl::PipeWire(stream) => stream.latency(),
+        }
+    }
+
+    /// Returns whether the stream is currently calling its callback.
+    ///
+    /// A stream becomes active once started. Streams of file input devices become inactive on
+    /// their own at the end of the file.
+    pub fn is_active(&self) -> bool {
+        match &self.0 {
+            StreamImpl::PortAudio(stream) => stream.is_active(),
+            StreamImpl::Null(stream) => stream.is_active(),
+            StreamImpl::Following(stream) => stream.is_active(),
+            #[cfg(feature = "native-pulseaudio")]
+            StreamImpl::PulseAudio(stream) => stream.is_active(),
+            #[cfg(feature = "native-pipewire")]
+            StreamImpl::PipeWire(stream) => stream.is_active(),
+        }
+    }
+
+    /// Returns the stream's state. Unlike the stream's callback, this can be polled from a control
+    /// thread (e.g. to notice that a device was unplugged).
+    pub fn state(&self) -> StreamState {
+        match &self.0 {
+            StreamImpl::PortAudio(stream) => stream.state(),
+            StreamImpl::Null(stream) => stream.state(),
+            StreamImpl::Following(stream) => stream.state(),
+            #[cfg(feature = "native-pulseaudio")]
+            StreamImpl::PulseAudio(stream) => stream.state(),
+            #[cfg(feature = "native-pipewire")]
+            StreamImpl::PipeWire(stream) => stream.state(),
+        }
+    }
+
+    /// Closes the stream. Dropping the stream closes it too, but can only log errors.
+    ///
+    /// Even if closing fails (e.g. because the device was disconnected), the stream no longer
+    /// calls its callback.
+    pub fn close(self) -> Result<()> {
+        match self.0 {
+            StreamImpl::PortAudio(stream) => stream.close(),
+            StreamImpl::Null(stream) => stream.close(),
+            StreamImpl::Following(stream) => stream.close(),
+            #[cfg(feature = "native-pulseaudio")]
+            StreamImpl::PulseAudio(stream) => stream.close(),
+            #[cfg(feature = "native-pipewire")]
+            StreamImpl::PipeWire(stream) => stream.close(),
+        }
+    }
+}
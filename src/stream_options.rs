@@ -1,3 +1,8 @@
+use std::time::Duration;
+
+use crate::device_id::DeviceId;
+use crate::error::{Error, Result};
+
 #[non_exhaustive]
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Format {
@@ -9,7 +14,20 @@ pub enum Format {
     U8,
 }
 
+impl Format {
+    /// The size of a single sample, in bytes.
+    pub(crate) fn sample_size(self) -> usize {
+        match self {
+            Format::F32 | Format::I32 => 4,
+            Format::I24 => 3,
+            Format::I16 => 2,
+            Format::I8 | Format::U8 => 1,
+        }
+    }
+}
+
 #[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum SampleRate {
     Exact(i32),
     NearestTo(i32),
@@ -22,8 +40,132 @@ impl Default for SampleRate {
     }
 }
 
+/// The latency requested when opening a stream.
+///
+/// This is independent of `frames_per_buffer`: a stream can use a large device buffer (high
+/// latency) while still calling the callback with small blocks, or vice-versa. The backend may
+/// round the requested latency to what the device supports. The actual latency is available
+/// through [`Stream::latency`](crate::Stream::latency) once the stream is opened.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Latency {
+    /// The device's default latency for interactive applications.
+    Low,
+    /// The device's default latency for robust non-interactive playback.
+    High,
+    /// An explicit latency.
+    Exact(Duration),
+}
+
+impl Default for Latency {
+    fn default() -> Latency {
+        Latency::High
+    }
+}
+
 pub type Callback<Frame> = Box<dyn FnMut(&mut [Frame]) + Send>;
 
+/// Optional processing behaviors of a stream. All flags are off by default.
+///
+/// Some flags only make sense for certain kinds of streams. Opening a stream with a flag that does
+/// not apply to its direction returns [`Error::InvalidFlags`](crate::Error::InvalidFlags).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct StreamFlags {
+    /// Disables clipping of out-of-range samples.
+    pub clip_off: bool,
+    /// Disables dithering when the backend converts between sample formats.
+    pub dither_off: bool,
+    /// Never discards overflowed input samples without calling the callback.
+    ///
+    /// Only valid for duplex streams, and only if `frames_per_buffer` is unspecified.
+    pub never_drop_input: bool,
+    /// Calls the callback to fill the initial output buffers, instead of priming them with
+    /// silence.
+    ///
+    /// Only valid for streams with an output.
+    pub prime_output: bool,
+}
+
+/// What a stream does once its device is disconnected (see
+/// [`StreamState::Disconnected`](crate::StreamState::Disconnected)).
+///
+/// Honored by PortAudio backends. Sound servers (i.e. the native PulseAudio and PipeWire
+/// backends) move the streams of removed devices themselves, and the devices of the null, file and
+/// loopback backends are never disconnected.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reconnect {
+    /// The stream stays disconnected.
+    Never,
+    /// The stream is reopened on the host's default device.
+    ToDefault,
+    /// The stream is reopened on the first of these devices that is available, if any.
+    ToDevices(Vec<DeviceId>),
+}
+
+impl Default for Reconnect {
+    fn default() -> Reconnect {
+        Reconnect::Never
+    }
+}
+
+/// The direction of a stream, or the directions a device supports (see
+/// [`DeviceId`](crate::DeviceId)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Direction {
+    Input,
+    Output,
+    /// Both input and output.
+    Duplex,
+}
+
+impl Direction {
+    /// Returns the directions of a device with the given channel counts.
+    pub(crate) fn of_device(max_input_channels: i32, max_output_channels: i32) -> Direction {
+        match (max_input_channels > 0, max_output_channels > 0) {
+            (true, true) => Direction::Duplex,
+            (true, false) => Direction::Input,
+            _ => Direction::Output,
+        }
+    }
+
+    /// Returns whether a device of these directions supports streams of `direction`.
+    pub(crate) fn supports(self, direction: Direction) -> bool {
+        self == direction || self == Direction::Duplex
+    }
+}
+
+/// Verifies that `Frame` is the size of `n_channels` samples of `format`.
+pub(crate) fn check_frame_size<Frame>(format: Format, n_channels: i32) -> Result<()> {
+    let frame_size = format.sample_size() * n_channels as usize;
+    if std::mem::size_of::<Frame>() != frame_size {
+        return Err(Error::InvalidFrameSize {
+            expected: frame_size,
+            actual: std::mem::size_of::<Frame>(),
+        });
+    }
+    Ok(())
+}
+
+impl StreamFlags {
+    /// Verifies that the flags can be used with a stream of the given direction.
+    pub(crate) fn validate(
+        self,
+        direction: Direction,
+        frames_per_buffer: Option<i32>,
+    ) -> Result<()> {
+        if self.never_drop_input && (direction != Direction::Duplex || frames_per_buffer.is_some())
+        {
+            return Err(Error::InvalidFlags);
+        }
+        if self.prime_output && direction == Direction::Input {
+            return Err(Error::InvalidFlags);
+        }
+        Ok(())
+    }
+}
+
 /// Configures the creation of input/output streams.
 ///
 /// This struct sets properties of a stream such as its format, number of channels, sample rate, and
@@ -46,7 +188,25 @@ pub struct StreamOptions<Frame> {
     pub n_channels: i32,
 
     pub frames_per_buffer: Option<i32>,
+    /// If set, the callback is always called with exactly this many frames, regardless of
+    /// `frames_per_buffer` or the backend's buffer size.
+    ///
+    /// The difference is buffered internally, which adds up to `block_size` frames of latency.
+    /// This added latency is included in [`Stream::latency`](crate::Stream::latency).
+    pub block_size: Option<i32>,
     pub sample_rate: SampleRate,
+    pub latency: Latency,
+
+    pub flags: StreamFlags,
+    /// The stream's name, shown by sound servers that support it (e.g. in PulseAudio's volume
+    /// control). Ignored by other backends.
+    pub name: Option<String>,
+    /// Additional properties describing the stream to sound servers that support them, as
+    /// key-value pairs (e.g. `("media.role", "Music")` for PipeWire). Ignored by other backends.
+    pub properties: Vec<(String, String)>,
+    /// Reopens the stream on another device once its device is disconnected. The stream is
+    /// reopened with the same options and callback, which keeps its state, and fades in.
+    pub reconnect: Reconnect,
 
     pub callback: Callback<Frame>,
 }
@@ -65,6 +225,12 @@ where
             n_channels: Frame::N_CHANNELS,
             sample_rate: SampleRate::default(),
             frames_per_buffer: None,
+            block_size: None,
+            latency: Latency::default(),
+            flags: StreamFlags::default(),
+            name: None,
+            properties: Vec::new(),
+            reconnect: Reconnect::default(),
 
             callback: Box::new(dummy_callback),
         }
@@ -104,4 +270,31 @@ mod tests {
         assert_eq!(StreamOptions::<[f32; 1]>::default().n_channels, 1);
         assert_eq!(StreamOptions::<[f32; 2]>::default().n_channels, 2);
     }
+
+    #[test]
+    fn validates_flags_against_direction() {
+        let never_drop_input = StreamFlags {
+            never_drop_input: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            never_drop_input.validate(Direction::Output, None),
+            Err(Error::InvalidFlags)
+        );
+        assert_eq!(never_drop_input.validate(Direction::Duplex, None), Ok(()));
+        assert_eq!(
+            never_drop_input.validate(Direction::Duplex, Some(256)),
+            Err(Error::InvalidFlags)
+        );
+
+        let prime_output = StreamFlags {
+            prime_output: true,
+            ..Default::default()
+        };
+        assert_eq!(prime_output.validate(Direction::Output, None), Ok(()));
+        assert_eq!(
+            prime_output.validate(Direction::Input, None),
+            Err(Error::InvalidFlags)
+        );
+    }
 }
@@ -0,0 +1,72 @@
+//! Spawns the audio daemons that backend tests run against.
+//!
+//! Tests that need a daemon (or other system audio support) are `#[ignore]`d, so that they are
+//! reported as ignored rather than silently passing where it is missing. Run them with
+//! `cargo test -- --ignored`: a missing daemon then fails the test.
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// How long a daemon may take to become ready.
+const READY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A running daemon, killed (and its runtime directory removed) when dropped.
+pub struct Daemon {
+    child: Child,
+    runtime_dir: Option<PathBuf>,
+}
+
+impl Daemon {
+    /// Creates an empty runtime directory for the daemon of a single test.
+    #[cfg(any(feature = "native-pulseaudio", feature = "native-pipewire"))]
+    pub fn runtime_dir(daemon: &str, test: &str) -> PathBuf {
+        let runtime_dir = std::env::temp_dir().join(format!(
+            "audiohal-{}-{}-{}",
+            daemon,
+            test,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&runtime_dir);
+        std::fs::create_dir_all(&runtime_dir).unwrap();
+        runtime_dir
+    }
+
+    /// Spawns `command`, and waits until `is_ready`. `runtime_dir` is removed along with the
+    /// daemon.
+    ///
+    /// Panics if the daemon cannot be spawned, exits, or is not ready in time.
+    pub fn spawn(
+        command: &mut Command,
+        runtime_dir: Option<&Path>,
+        mut is_ready: impl FnMut() -> bool,
+    ) -> Daemon {
+        let child = command
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap_or_else(|error| panic!("Could not spawn {:?}: {}", command, error));
+        let mut daemon = Daemon {
+            child,
+            runtime_dir: runtime_dir.map(Path::to_path_buf),
+        };
+        let start = Instant::now();
+        while !is_ready() {
+            if let Ok(Some(status)) = daemon.child.try_wait() {
+                panic!("{:?} exited before it was ready: {}", command, status);
+            }
+            assert_lt!(start.elapsed(), READY_TIMEOUT, "{:?} is not ready", command);
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        daemon
+    }
+}
+
+impl Drop for Daemon {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        if let Some(runtime_dir) = &self.runtime_dir {
+            let _ = std::fs::remove_dir_all(runtime_dir);
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use crate::portaudio;
+
+/// The version of the PortAudio library audiohal was built with (or links against, with the
+/// `system-portaudio` feature).
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibraryVersion {
+    pub major: i32,
+    pub minor: i32,
+    pub sub_minor: i32,
+    /// The revision PortAudio was built from (currently a git hash), if known.
+    pub revision: String,
+    /// A human-readable description, e.g. "PortAudio V19.6.0-devel, revision 396fe4b6699a".
+    pub text: String,
+}
+
+/// Returns the version of the PortAudio library, e.g. for bug reports.
+///
+/// # Examples
+/// ```
+/// let version = audiohal::library_version();
+/// assert_eq!(version.major, 19);
+/// ```
+pub fn library_version() -> LibraryVersion {
+    portaudio::library_version()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_version() {
+        let version = library_version();
+        assert!(version.text.contains(&format!(
+            "{}.{}.{}",
+            version.major, version.minor, version.sub_minor
+        )));
+    }
+}
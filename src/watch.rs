@@ -0,0 +1,221 @@
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use crate::error::{Error, Result};
+use crate::host::Host;
+
+/// How often watchers refresh the devices at most. Refreshing a PortAudio backend re-initializes
+/// PortAudio, which probes every device again and, with JACK, registers its client anew.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A change of the devices of a host, reported by [`Host::watch_devices`]. Devices are identified
+/// by name.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeviceEvent {
+    /// A device was plugged in.
+    Added(String),
+    /// A device was unplugged.
+    Removed(String),
+    /// The default output device changed, or there no longer is one.
+    DefaultOutputChanged(Option<String>),
+    /// The default input device changed, or there no longer is one.
+    DefaultInputChanged(Option<String>),
+}
+
+/// Watches the devices of a host, until dropped. See [`Host::watch_devices`].
+pub struct DeviceWatcher {
+    /// Dropping the sender stops the thread.
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl DeviceWatcher {
+    pub(crate) fn spawn(
+        mut host: Host,
+        interval: Duration,
+        mut callback: impl FnMut(DeviceEvent) + Send + 'static,
+    ) -> Result<DeviceWatcher> {
+        let mut previous = Snapshot::new(&mut host)?;
+        let mut last_refresh = Instant::now();
+        let (stop, stopped) = mpsc::channel();
+        let thread = std::thread::spawn(move || loop {
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => {}
+                _ => return,
+            }
+            if last_refresh.elapsed() >= REFRESH_INTERVAL {
+                last_refresh = Instant::now();
+                match host.refresh_devices_if_idle() {
+                    Ok(()) | Err(Error::StreamsOpen) => {}
+                    // The backend may be temporarily unavailable (e.g. a restarting server).
+                    Err(_) => continue,
+                }
+            }
+            let current = match Snapshot::new(&mut host) {
+                Ok(current) => current,
+                Err(_) => continue,
+            };
+            previous
+                .changes(&current)
+                .into_iter()
+                .for_each(&mut callback);
+            previous = current;
+        });
+        Ok(DeviceWatcher {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for DeviceWatcher {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            // Only fails if the callback panicked, which was already reported.
+            let _ = thread.join();
+        }
+    }
+}
+
+/// The devices of a host at one poll.
+#[derive(Debug, Default)]
+struct Snapshot {
+    /// Sorted, as several devices may have the same name.
+    devices: Vec<String>,
+    default_output: Option<String>,
+    default_input: Option<String>,
+}
+
+impl Snapshot {
+    fn new(host: &mut Host) -> Result<Snapshot> {
+        let mut devices: Vec<String> = host
+            .devices()?
+            .iter()
+            .map(|device| device.name().to_string())
+            .collect();
+        devices.sort();
+        Ok(Snapshot {
+            devices,
+            default_output: default_name(host.default_output_device())?,
+            default_input: default_name(host.default_input_device())?,
+        })
+    }
+
+    /// Returns the events that lead from this snapshot to `current`.
+    fn changes(&self, current: &Snapshot) -> Vec<DeviceEvent> {
+        let mut events = Vec::new();
+        let (mut previous, mut current_devices) = (
+            self.devices.iter().peekable(),
+            current.devices.iter().peekable(),
+        );
+        loop {
+            match (previous.peek(), current_devices.peek()) {
+                (Some(old), Some(new)) if old == new => {
+                    previous.next();
+                    current_devices.next();
+                }
+                (Some(old), Some(new)) if old < new => {
+                    events.push(DeviceEvent::Removed((*old).clone()));
+                    previous.next();
+                }
+                (Some(old), None) => {
+                    events.push(DeviceEvent::Removed((*old).clone()));
+                    previous.next();
+                }
+                (_, Some(new)) => {
+                    events.push(DeviceEvent::Added((*new).clone()));
+                    current_devices.next();
+                }
+                (None, None) => break,
+            }
+        }
+        if self.default_output != current.default_output {
+            events.push(DeviceEvent::DefaultOutputChanged(
+                current.default_output.clone(),
+            ));
+        }
+        if self.default_input != current.default_input {
+            events.push(DeviceEvent::DefaultInputChanged(
+                current.default_input.clone(),
+            ));
+        }
+        events
+    }
+}
+
+fn default_name(device: Result<crate::Device>) -> Result<Option<String>> {
+    match device {
+        Ok(device) => Ok(Some(device.name().to_string())),
+        Err(Error::NoSuchDevice) => Ok(None),
+        Err(error) => Err(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NullDeviceOptions, NullHostOptions};
+
+    fn snapshot(devices: &[&str], default_output: Option<&str>) -> Snapshot {
+        let mut devices: Vec<String> = devices.iter().map(|name| name.to_string()).collect();
+        devices.sort();
+        Snapshot {
+            devices,
+            default_output: default_output.map(str::to_string),
+            default_input: None,
+        }
+    }
+
+    #[test]
+    fn reports_added_and_removed_devices() {
+        let before = snapshot(&["Speakers", "HDMI", "Webcam"], Some("Speakers"));
+        let after = snapshot(
+            &["Speakers", "USB Headset", "Webcam", "Webcam"],
+            Some("USB Headset"),
+        );
+        assert_eq!(
+            before.changes(&after),
+            vec![
+                DeviceEvent::Removed("HDMI".to_string()),
+                DeviceEvent::Added("USB Headset".to_string()),
+                DeviceEvent::Added("Webcam".to_string()),
+                DeviceEvent::DefaultOutputChanged(Some("USB Headset".to_string())),
+            ]
+        );
+        assert_eq!(after.changes(&after), vec![]);
+        assert_eq!(
+            after.changes(&snapshot(&[], None)).len(),
+            5,
+            "All devices should be removed, and the default output changed."
+        );
+    }
+
+    #[test]
+    fn watches_until_dropped() -> Result<()> {
+        let host = Host::with_null_backend(NullHostOptions {
+            devices: vec![NullDeviceOptions::default()],
+            ..Default::default()
+        })?;
+        let (sender, receiver) = mpsc::channel();
+        let watcher = host.watch_devices(Duration::from_millis(1), move |event| {
+            let _ = sender.send(event);
+        })?;
+        std::thread::sleep(Duration::from_millis(20));
+        drop(watcher);
+        // The null backend's devices never change, and the callback was dropped with the thread.
+        assert_eq!(receiver.recv(), Err(mpsc::RecvError));
+        Ok(())
+    }
+
+    #[test]
+    fn refreshing_is_a_no_op_for_native_backends() -> Result<()> {
+        let mut host = Host::with_null_backend(NullHostOptions::default())?;
+        let n_devices = host.devices()?.len();
+        host.refresh_devices()?;
+        assert_eq!(host.devices()?.len(), n_devices);
+        Ok(())
+    }
+}
@@ -0,0 +1,383 @@
+//! Minimal RIFF/WAVE support for the file backend.
+//!
+//! Samples are exchanged with the rest of the crate as raw bytes in the stream's [`Format`], in
+//! native endianness. WAV files are always little-endian, and store 8-bit samples as unsigned.
+use std::convert::TryFrom;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use crate::stream_options::Format;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xfffe;
+/// The size of the largest fmt chunk, that of `WAVE_FORMAT_EXTENSIBLE` files. Longer chunks are
+/// rejected rather than allocated.
+const MAX_FMT_CHUNK_LEN: u32 = 40;
+
+/// The size of the RIFF, fmt and data chunk headers written by [`WavWriter`].
+const HEADER_LEN: u32 = 44;
+
+/// The sample layout of a WAV file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WavSpec {
+    pub format: Format,
+    pub n_channels: u16,
+    pub sample_rate: u32,
+}
+
+impl WavSpec {
+    fn format_tag(self) -> u16 {
+        match self.format {
+            Format::F32 => WAVE_FORMAT_IEEE_FLOAT,
+            _ => WAVE_FORMAT_PCM,
+        }
+    }
+
+    fn bits_per_sample(self) -> u16 {
+        self.format.sample_size() as u16 * 8
+    }
+
+    fn block_align(self) -> u16 {
+        self.n_channels * self.format.sample_size() as u16
+    }
+}
+
+impl WavSpec {
+    fn from_fmt_chunk(chunk: &[u8]) -> io::Result<WavSpec> {
+        if chunk.len() < 16 {
+            return Err(invalid_data("Truncated fmt chunk."));
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([chunk[i], chunk[i + 1]]);
+        let mut format_tag = u16_at(0);
+        // The actual format of extensible files is in the first bytes of the sub-format GUID.
+        if format_tag == WAVE_FORMAT_EXTENSIBLE && chunk.len() >= 26 {
+            format_tag = u16_at(24);
+        }
+        let format = match (format_tag, u16_at(14)) {
+            (WAVE_FORMAT_IEEE_FLOAT, 32) => Format::F32,
+            (WAVE_FORMAT_PCM, 32) => Format::I32,
+            (WAVE_FORMAT_PCM, 24) => Format::I24,
+            (WAVE_FORMAT_PCM, 16) => Format::I16,
+            (WAVE_FORMAT_PCM, 8) => Format::U8,
+            _ => return Err(invalid_data("Unsupported WAV sample format.")),
+        };
+        let spec = WavSpec {
+            format,
+            n_channels: u16_at(2),
+            sample_rate: u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]),
+        };
+        if spec.n_channels == 0 || spec.sample_rate == 0 || spec.block_align() != u16_at(12) {
+            return Err(invalid_data("Invalid WAV fmt chunk."));
+        }
+        Ok(spec)
+    }
+}
+
+fn invalid_data(message: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Converts samples between native-endian stream bytes and WAV bytes, in place. The conversion is
+/// its own inverse.
+pub fn swap_wav_bytes(format: Format, samples: &mut [u8]) {
+    match format {
+        Format::I8 => {
+            for byte in samples.iter_mut() {
+                *byte ^= 0x80;
+            }
+        }
+        Format::U8 => (),
+        _ if cfg!(target_endian = "big") => {
+            for sample in samples.chunks_exact_mut(format.sample_size()) {
+                sample.reverse();
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Writes a WAV file. The header is completed by [`finalize`](WavWriter::finalize), which reports
+/// errors, or when the writer is dropped, which ignores them.
+pub struct WavWriter<W: Write + Seek> {
+    writer: W,
+    data_len: u32,
+    scratch: Vec<u8>,
+    format: Format,
+    finalized: bool,
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut writer: W, spec: WavSpec) -> io::Result<WavWriter<W>> {
+        writer.write_all(b"RIFF")?;
+        // The RIFF and data chunk sizes are patched once the length of the data is known.
+        writer.write_all(&(HEADER_LEN - 8).to_le_bytes())?;
+        writer.write_all(b"WAVEfmt ")?;
+        writer.write_all(&16_u32.to_le_bytes())?;
+        writer.write_all(&spec.format_tag().to_le_bytes())?;
+        writer.write_all(&spec.n_channels.to_le_bytes())?;
+        writer.write_all(&spec.sample_rate.to_le_bytes())?;
+        let byte_rate = spec.sample_rate * u32::from(spec.block_align());
+        writer.write_all(&byte_rate.to_le_bytes())?;
+        writer.write_all(&spec.block_align().to_le_bytes())?;
+        writer.write_all(&spec.bits_per_sample().to_le_bytes())?;
+        writer.write_all(b"data")?;
+        writer.write_all(&0_u32.to_le_bytes())?;
+        Ok(WavWriter {
+            writer,
+            data_len: 0,
+            scratch: Vec::new(),
+            format: spec.format,
+            finalized: false,
+        })
+    }
+
+    /// Appends interleaved samples, given as native-endian bytes in the file's format.
+    pub fn write_samples(&mut self, samples: &[u8]) -> io::Result<()> {
+        let data_len = u32::try_from(samples.len())
+            .ok()
+            .and_then(|len| self.data_len.checked_add(len))
+            .filter(|&len| len <= u32::max_value() - HEADER_LEN)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "WAV file is too large."))?;
+        self.scratch.clear();
+        self.scratch.extend_from_slice(samples);
+        swap_wav_bytes(self.format, &mut self.scratch);
+        self.writer.write_all(&self.scratch)?;
+        self.data_len = data_len;
+        Ok(())
+    }
+
+    /// Completes the header with the length of the data written so far.
+    pub fn finalize(&mut self) -> io::Result<()> {
+        self.finalized = true;
+        // Chunks are padded to an even size.
+        let pad = self.data_len % 2;
+        if pad != 0 {
+            self.writer.write_all(&[0])?;
+        }
+        self.writer.seek(SeekFrom::Start(4))?;
+        self.writer
+            .write_all(&(HEADER_LEN - 8 + self.data_len + pad).to_le_bytes())?;
+        self.writer
+            .seek(SeekFrom::Start(u64::from(HEADER_LEN) - 4))?;
+        self.writer.write_all(&self.data_len.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()
+    }
+}
+
+impl<W: Write + Seek> Drop for WavWriter<W> {
+    fn drop(&mut self) {
+        if !self.finalized {
+            // There is nobody left to report the error to.
+            let _ = self.finalize();
+        }
+    }
+}
+
+/// Reads the samples of a WAV file, as native-endian bytes.
+///
+/// 8-bit files are read as [`Format::U8`].
+pub struct WavReader<R: Read + Seek> {
+    reader: R,
+    spec: WavSpec,
+    data_start: u64,
+    data_len: u64,
+    /// The number of bytes of the data chunk read so far.
+    position: u64,
+}
+
+impl<R: Read + Seek> WavReader<R> {
+    pub fn new(mut reader: R) -> io::Result<WavReader<R>> {
+        let mut header = [0; 12];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != b"RIFF" || &header[8..] != b"WAVE" {
+            return Err(invalid_data("Not a RIFF/WAVE file."));
+        }
+        let mut spec = None;
+        loop {
+            let mut chunk_header = [0; 8];
+            reader.read_exact(&mut chunk_header)?;
+            let chunk_len = u32::from_le_bytes([
+                chunk_header[4],
+                chunk_header[5],
+                chunk_header[6],
+                chunk_header[7],
+            ]);
+            match &chunk_header[..4] {
+                b"fmt " => {
+                    if chunk_len > MAX_FMT_CHUNK_LEN {
+                        return Err(invalid_data("WAV fmt chunk is too long."));
+                    }
+                    let mut chunk = vec![0; chunk_len as usize];
+                    reader.read_exact(&mut chunk)?;
+                    spec = Some(WavSpec::from_fmt_chunk(&chunk)?);
+                    if chunk_len % 2 != 0 {
+                        reader.seek(SeekFrom::Current(1))?;
+                    }
+                }
+                b"data" => {
+                    let spec = spec.ok_or_else(|| invalid_data("Missing WAV fmt chunk."))?;
+                    let data_start = reader.seek(SeekFrom::Current(0))?;
+                    let data_len = u64::from(chunk_len);
+                    return Ok(WavReader {
+                        reader,
+                        spec,
+                        data_start,
+                        // Ignore a trailing partial frame.
+                        data_len: data_len - data_len % u64::from(spec.block_align()),
+                        position: 0,
+                    });
+                }
+                _ => {
+                    // Chunks are padded to an even length.
+                    let padded_len = i64::from(chunk_len) + i64::from(chunk_len & 1);
+                    reader.seek(SeekFrom::Current(padded_len))?;
+                }
+            }
+        }
+    }
+
+    pub fn spec(&self) -> WavSpec {
+        self.spec
+    }
+
+    /// Reads as many samples as are left into `samples`, and returns the number of bytes read.
+    /// Always reads whole samples.
+    pub fn read_samples(&mut self, samples: &mut [u8]) -> io::Result<usize> {
+        let sample_size = self.spec.format.sample_size();
+        let len = (samples.len() as u64).min(self.data_len - self.position) as usize;
+        let len = len - len % sample_size;
+        self.reader.read_exact(&mut samples[..len])?;
+        self.position += len as u64;
+        swap_wav_bytes(self.spec.format, &mut samples[..len]);
+        Ok(len)
+    }
+
+    /// Restarts reading from the first sample.
+    pub fn rewind(&mut self) -> io::Result<()> {
+        self.reader.seek(SeekFrom::Start(self.data_start))?;
+        self.position = 0;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data_len == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn write_wav(spec: WavSpec, samples: &[u8]) -> Vec<u8> {
+        let mut file = Cursor::new(Vec::new());
+        let mut writer = WavWriter::new(&mut file, spec).unwrap();
+        writer.write_samples(samples).unwrap();
+        writer.finalize().unwrap();
+        drop(writer);
+        file.into_inner()
+    }
+
+    #[test]
+    fn writes_pcm_header() {
+        let samples: Vec<u8> = [1_i16, -1, 2, -2]
+            .iter()
+            .flat_map(|sample| sample.to_ne_bytes().to_vec())
+            .collect();
+        let file = write_wav(
+            WavSpec {
+                format: Format::I16,
+                n_channels: 2,
+                sample_rate: 44_100,
+            },
+            &samples,
+        );
+        let mut expected = b"RIFF".to_vec();
+        expected.extend(&44_u32.to_le_bytes());
+        expected.extend(b"WAVEfmt ");
+        expected.extend(&[16, 0, 0, 0, 1, 0, 2, 0]);
+        expected.extend(&44_100_u32.to_le_bytes());
+        expected.extend(&(44_100_u32 * 4).to_le_bytes());
+        expected.extend(&[4, 0, 16, 0]);
+        expected.extend(b"data");
+        expected.extend(&8_u32.to_le_bytes());
+        expected.extend(&[1, 0, 0xff, 0xff, 2, 0, 0xfe, 0xff]);
+        assert_eq!(file, expected);
+    }
+
+    #[test]
+    fn reads_back_written_samples() {
+        let spec = WavSpec {
+            format: Format::I24,
+            n_channels: 1,
+            sample_rate: 16_000,
+        };
+        let samples = [1, 2, 3, 4, 5, 6];
+        let mut reader = WavReader::new(Cursor::new(write_wav(spec, &samples))).unwrap();
+        assert_eq!(reader.spec(), spec);
+        let mut read = [0; 4];
+        assert_eq!(reader.read_samples(&mut read).unwrap(), 3);
+        assert_eq!(reader.read_samples(&mut read[3..]).unwrap(), 0);
+        assert_eq!(reader.read_samples(&mut read).unwrap(), 3);
+        assert_eq!(&read[..3], &samples[3..]);
+        assert_eq!(reader.read_samples(&mut read).unwrap(), 0);
+        reader.rewind().unwrap();
+        assert_eq!(reader.read_samples(&mut read).unwrap(), 3);
+        assert_eq!(&read[..3], &samples[..3]);
+    }
+
+    #[test]
+    fn rejects_unsupported_files() {
+        assert_eq!(
+            WavReader::new(Cursor::new(b"RIFF\0\0\0\0AVI LIST".to_vec()))
+                .err()
+                .map(|error| error.kind()),
+            Some(io::ErrorKind::InvalidData)
+        );
+    }
+
+    #[test]
+    fn rejects_oversized_chunks() {
+        let file_with_chunk = |id: &[u8], len: u32| {
+            let mut file = b"RIFF\0\0\0\0WAVE".to_vec();
+            file.extend_from_slice(id);
+            file.extend_from_slice(&len.to_le_bytes());
+            WavReader::new(Cursor::new(file))
+                .err()
+                .map(|error| error.kind())
+        };
+        assert_eq!(
+            file_with_chunk(b"fmt ", u32::max_value()),
+            Some(io::ErrorKind::InvalidData)
+        );
+        // Skipping the padded chunk must not overflow.
+        assert_eq!(
+            file_with_chunk(b"LIST", u32::max_value()),
+            Some(io::ErrorKind::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn writes_float_and_8_bit_samples() {
+        let spec = WavSpec {
+            format: Format::F32,
+            n_channels: 1,
+            sample_rate: 8000,
+        };
+        let file = write_wav(spec, &0.5_f32.to_ne_bytes());
+        assert_eq!(&file[20..22], &WAVE_FORMAT_IEEE_FLOAT.to_le_bytes());
+        assert_eq!(&file[44..], &0.5_f32.to_le_bytes());
+
+        let spec = WavSpec {
+            format: Format::I8,
+            ..spec
+        };
+        // 8-bit WAV samples are unsigned, and odd-sized data is padded.
+        let file = write_wav(spec, &[0, 0x7f, 0x80]);
+        assert_eq!(&file[34..36], &8_u16.to_le_bytes());
+        assert_eq!(&file[40..44], &3_u32.to_le_bytes());
+        assert_eq!(&file[4..8], &40_u32.to_le_bytes());
+        assert_eq!(&file[44..], &[0x80, 0xff, 0, 0]);
+    }
+}